@@ -1,231 +1,390 @@
 use crate::{
-    bitcoin,
     commit::{CoinTossingKeys, Commitment, Opening},
-    grin,
-    messages::{Message0, Message1, Message2, Message3, Message4},
+    expiry::ExpiryValidator,
+    ledger::{AliceFunder0, AliceFunder1, AliceRedeemer0, AliceRedeemer1},
+    messages::{
+        bob_initiates, Abort, Message0, Message1, Message2, Message3, Message4, Phase, ReasonCode,
+        SwapId,
+    },
+    negotiation::OfferPair,
+    transcript::Transcript,
     KeyPair,
 };
-use grin::bulletproof;
+use std::convert::TryInto;
 
 pub struct Alice0<AL, BL> {
+    swap_id: SwapId,
     alpha_state: AL,
     beta_state: BL,
     y: KeyPair,
+    transcript: Transcript,
 }
 
-impl Alice0<grin::AliceFunder0, bitcoin::AliceRedeemer0> {
+impl<AL, BL> Alice0<AL, BL>
+where
+    AL: AliceFunder0,
+    BL: AliceRedeemer0,
+{
     pub fn new(
-        offer_grin: grin::Offer,
-        outputs_grin: grin::SpecialOutputs,
-        output_keypairs_grin_funder: grin::SpecialOutputKeyPairsFunder,
-        offer_bitcoin: bitcoin::Offer,
-        outputs_bitcoin: bitcoin::WalletOutputs,
-    ) -> anyhow::Result<(Self, Message0)> {
-        let grin_state =
-            grin::alice::AliceFunder0::new(offer_grin, outputs_grin, output_keypairs_grin_funder)?;
-        let bitcoin_state = bitcoin::alice::AliceRedeemer0::new(offer_bitcoin, outputs_bitcoin);
-
-        Ok(Alice0::state_and_message(
-            grin_state.clone(),
-            bitcoin_state,
-            grin_state.bulletproof_round_1_self,
-        ))
-    }
+        swap_id: SwapId,
+        alpha_state: AL,
+        beta_state: BL,
+        expiry_validator: &ExpiryValidator,
+    ) -> crate::Result<(Self, Message0<AL::BulletproofRound1, BL::BulletproofRound1>)> {
+        expiry_validator.validate(alpha_state.offer(), beta_state.offer())?;
 
-    pub fn receive(
-        self,
-        message: Message1<grin::PKs, bitcoin::PKs>,
-    ) -> anyhow::Result<(
-        Alice1<grin::AliceFunder1, bitcoin::AliceRedeemer1>,
-        Message2<bitcoin::Signature>,
-    )> {
-        let opening = self.opening();
+        let y = KeyPair::new_random();
 
-        let grin_state = self
-            .alpha_state
-            .transition(message.PKs_alpha, message.bulletproof_round_1_bob)?;
-        let (bitcoin_state, bitcoin_redeemer_refund_sig) =
-            self.beta_state.transition(message.PKs_beta)?;
+        let offers = OfferPair {
+            alpha: alpha_state.offer().clone(),
+            beta: beta_state.offer().clone(),
+        };
 
-        Ok((
-            Alice1 {
-                y: self.y,
-                alpha_state: grin_state,
-                beta_state: bitcoin_state,
-            },
-            Message2 {
-                opening,
-                beta_redeemer_sigs: bitcoin_redeemer_refund_sig,
-            },
-        ))
-    }
-}
+        let mut transcript = Transcript::for_swap(&swap_id, &offers);
 
-impl Alice0<bitcoin::AliceFunder0, grin::AliceRedeemer0> {
-    pub fn new(
-        offer_bitcoin: bitcoin::Offer,
-        outputs_bitcoin: bitcoin::WalletOutputs,
-        offer_grin: grin::Offer,
-        outputs_grin: grin::SpecialOutputs,
-        output_keypairs_grin_redeemer: grin::SpecialOutputKeyPairsRedeemer,
-    ) -> anyhow::Result<(Self, Message0)> {
-        let bitcoin_state = bitcoin::alice::AliceFunder0::new(offer_bitcoin, outputs_bitcoin);
-        let grin_state = grin::alice::AliceRedeemer0::new(
-            offer_grin,
-            outputs_grin,
-            output_keypairs_grin_redeemer,
-        )?;
+        let commitment = Commitment::commit(
+            &transcript,
+            alpha_state.clone().into(),
+            beta_state.clone().into(),
+            Some(&y.public_key),
+        );
 
-        Ok(Alice0::state_and_message(
-            bitcoin_state,
-            grin_state.clone(),
-            grin_state.bulletproof_round_1_self,
-        ))
+        let message = Message0 {
+            swap_id,
+            commitment,
+            bulletproof_round_1_alice: (
+                alpha_state.bulletproof_round_1(),
+                beta_state.bulletproof_round_1(),
+            ),
+        };
+        message.append_to(&mut transcript);
+
+        let state = Alice0 {
+            swap_id,
+            alpha_state,
+            beta_state,
+            y,
+            transcript,
+        };
+
+        Ok((state, message))
     }
 
     #[allow(clippy::type_complexity)]
     pub fn receive(
         mut self,
-        message: Message1<bitcoin::PKs, grin::PKs>,
-    ) -> anyhow::Result<(
-        Alice1<bitcoin::AliceFunder1, grin::AliceRedeemer1>,
-        Message2<(grin::RedeemerSigs, grin::bulletproof::Round2)>,
-    )> {
+        message: Message1<AL::PKs, BL::PKs, AL::BulletproofRound1, BL::BulletproofRound1>,
+    ) -> crate::Result<(Alice1<AL::Next, BL::Next>, Message2<BL::RedeemerSigs>)> {
+        self.swap_id.check(message.swap_id)?;
+
         // Building the opening must happen now, because some keys may change when
-        // transitioning Grin's state and Alice has already committed to the original
-        // ones
+        // transitioning the redeemer's state and Alice has already committed to the
+        // original ones
         let opening = self.opening();
 
-        let bitcoin_state = self.alpha_state.transition(message.PKs_alpha);
-        let (grin_state, grin_redeemer_sigs, bulletproof_round_2_alice) =
-            self.beta_state.transition(
-                message.PKs_beta,
-                &mut self.y,
-                message.bulletproof_round_1_bob,
-            )?;
+        message.append_to(&mut self.transcript);
+        opening.append_to(&mut self.transcript);
+
+        let (bob_alpha_round_1, bob_beta_round_1) = message.bulletproof_round_1_bob;
+        let alpha_state =
+            self.alpha_state
+                .transition(message.PKs_alpha, bob_alpha_round_1, &self.transcript)?;
+        let (beta_state, beta_redeemer_sigs) = self.beta_state.transition(
+            message.PKs_beta,
+            &mut self.y,
+            bob_beta_round_1,
+            &self.transcript,
+        )?;
 
         Ok((
             Alice1 {
+                swap_id: self.swap_id,
                 y: self.y,
-                alpha_state: bitcoin_state,
-                beta_state: grin_state,
+                alpha_state,
+                beta_state,
             },
             Message2 {
+                swap_id: self.swap_id,
                 opening,
-                beta_redeemer_sigs: (grin_redeemer_sigs, bulletproof_round_2_alice),
+                beta_redeemer_sigs,
             },
         ))
     }
-}
-
-impl<A, B> Alice0<A, B>
-where
-    A: Into<CoinTossingKeys> + Clone,
-    B: Into<CoinTossingKeys> + Clone,
-{
-    pub fn state_and_message(
-        alpha_state: A,
-        beta_state: B,
-        bulletproof_round_1_alice: grin::bulletproof::Round1,
-    ) -> (Self, Message0) {
-        let y = KeyPair::new_random();
 
-        let commitment = Commitment::commit(
-            alpha_state.clone().into(),
-            beta_state.clone().into(),
-            &y.public_key,
-        );
-
-        let state = Alice0 {
-            alpha_state,
-            beta_state,
-            y,
-        };
+    /// Give up on the swap before revealing the opening. Nothing has been
+    /// signed yet, so dropping the state is all the cleanup needed.
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::KeyExchange,
+        }
+    }
 
-        let message = Message0 {
-            commitment,
-            bulletproof_round_1_alice,
-        };
+    /// Bob is not going to continue, so the keys can be discarded.
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
 
-        (state, message)
+    pub fn swap_id(&self) -> SwapId {
+        self.swap_id
     }
 
     pub fn opening(&self) -> Opening {
         Opening::new(
             self.alpha_state.clone().into(),
             self.beta_state.clone().into(),
-            self.y.public_key,
+            Some(self.y.public_key),
         )
     }
 }
 
 pub struct Alice1<AL, BL> {
+    swap_id: SwapId,
     alpha_state: AL,
     beta_state: BL,
     y: KeyPair,
 }
 
-impl Alice1<grin::AliceFunder1, bitcoin::AliceRedeemer1> {
+impl<AL, BL> Alice1<AL, BL>
+where
+    AL: AliceFunder1,
+    BL: AliceRedeemer1,
+{
+    #[allow(clippy::type_complexity)]
     pub fn receive(
         self,
-        message: Message3<(grin::RedeemerSigs, bulletproof::Round2), bitcoin::EncryptedSignature>,
-    ) -> anyhow::Result<(
-        Alice2<grin::AliceFunder2, bitcoin::AliceRedeemer2>,
-        Message4<grin::EncryptedSignature>,
-    )> {
-        let (grin_state, grin_redeem_encsig) = self.alpha_state.transition(
-            message.alpha_redeemer_sigs.0,
-            &self.y,
-            message.alpha_redeemer_sigs.1,
-        )?;
-        let bitcoin_state = self
+        message: Message3<AL::RedeemerSigs, BL::EncryptedSignature>,
+    ) -> crate::Result<(Alice2<AL::Next, BL::Next>, Message4<AL::EncryptedSignature>)> {
+        self.swap_id.check(message.swap_id)?;
+
+        let (alpha_state, alpha_redeem_encsig) = self
+            .alpha_state
+            .transition(message.alpha_redeemer_sigs, &self.y)?;
+        let beta_state = self
             .beta_state
             .transition(message.beta_redeem_encsig, &self.y)?;
 
         let state = Alice2 {
-            alpha_state: grin_state,
-            beta_state: bitcoin_state,
+            swap_id: self.swap_id,
+            alpha_state,
+            beta_state,
         };
 
         let message = Message4 {
-            alpha_redeem_encsig: grin_redeem_encsig,
+            swap_id: self.swap_id,
+            alpha_redeem_encsig,
         };
 
         Ok((state, message))
     }
+
+    /// Give up on the swap before funding. Alice has only signed for Bob's
+    /// transactions on the beta ledger, which Bob will never fund without her
+    /// encrypted signature, so dropping the state is enough.
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::Signing,
+        }
+    }
+
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
+}
+
+pub struct Alice2<AL, BL> {
+    pub swap_id: SwapId,
+    pub alpha_state: AL,
+    pub beta_state: BL,
 }
 
-impl Alice1<bitcoin::AliceFunder1, grin::AliceRedeemer1> {
+/// Alice's side of the protocol when Bob sends the first message.
+pub struct AliceResponder0<AL, BL>
+where
+    AL: AliceFunder0,
+    BL: AliceRedeemer0,
+{
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    y: KeyPair,
+    bob_commitment: Commitment,
+    bob_bulletproof_round_1: (AL::BulletproofRound1, BL::BulletproofRound1),
+    transcript: Transcript,
+}
+
+impl<AL, BL> AliceResponder0<AL, BL>
+where
+    AL: AliceFunder0,
+    BL: AliceRedeemer0,
+    CoinTossingKeys: TryInto<AL::PKs, Error = crate::Error>,
+    CoinTossingKeys: TryInto<BL::PKs, Error = crate::Error>,
+{
+    pub fn new(
+        swap_id: SwapId,
+        alpha_state: AL,
+        beta_state: BL,
+        expiry_validator: &ExpiryValidator,
+        message: bob_initiates::Message0<AL::BulletproofRound1, BL::BulletproofRound1>,
+    ) -> crate::Result<(
+        Self,
+        bob_initiates::Message1<AL::BulletproofRound1, BL::BulletproofRound1>,
+    )> {
+        swap_id.check(message.swap_id)?;
+        expiry_validator.validate(alpha_state.offer(), beta_state.offer())?;
+
+        let y = KeyPair::new_random();
+
+        let offers = OfferPair {
+            alpha: alpha_state.offer().clone(),
+            beta: beta_state.offer().clone(),
+        };
+
+        let mut transcript = Transcript::for_swap(&swap_id, &offers);
+        message.append_to(&mut transcript);
+
+        let message1 = bob_initiates::Message1 {
+            swap_id,
+            PKs_alpha: alpha_state.clone().into(),
+            PKs_beta: beta_state.clone().into(),
+            Y: y.public_key,
+            bulletproof_round_1_alice: (
+                alpha_state.bulletproof_round_1(),
+                beta_state.bulletproof_round_1(),
+            ),
+        };
+
+        message1.append_to(&mut transcript);
+
+        let state = AliceResponder0 {
+            swap_id,
+            alpha_state,
+            beta_state,
+            y,
+            bob_commitment: message.commitment,
+            bob_bulletproof_round_1: message.bulletproof_round_1_bob,
+            transcript,
+        };
+
+        Ok((state, message1))
+    }
+
+    #[allow(clippy::type_complexity)]
     pub fn receive(
-        self,
-        Message3 {
-            alpha_redeemer_sigs: bob_bitcoin_refund_signature,
-            beta_redeem_encsig: grin_redeem_encsig,
-            ..
-        }: Message3<bitcoin::Signature, grin::EncryptedSignature>,
-    ) -> anyhow::Result<(
-        Alice2<bitcoin::AliceFunder2, grin::AliceRedeemer2>,
-        Message4<bitcoin::EncryptedSignature>,
+        mut self,
+        message: bob_initiates::Message2<<AL::Next as AliceFunder1>::RedeemerSigs>,
+    ) -> crate::Result<(
+        AliceResponder1<<AL::Next as AliceFunder1>::Next, BL::Next>,
+        bob_initiates::Message3<<AL::Next as AliceFunder1>::EncryptedSignature, BL::RedeemerSigs>,
     )> {
-        let (bitcoin_state, bitcoin_redeem_encsig) = self
+        self.swap_id.check(message.swap_id)?;
+
+        let offers = OfferPair {
+            alpha: self.alpha_state.offer().clone(),
+            beta: self.beta_state.offer().clone(),
+        };
+
+        message.opening.append_to(&mut self.transcript);
+
+        let (bob_PKs_alpha, bob_PKs_beta, _) = message.opening.open(
+            self.bob_commitment,
+            &Transcript::for_swap(&self.swap_id, &offers),
+        )?;
+
+        // As when Alice initiates, `y` may change on the beta ledger before she
+        // signs anything on the alpha ledger
+        let (bob_alpha_round_1, bob_beta_round_1) = self.bob_bulletproof_round_1;
+        let (beta_state, beta_redeemer_sigs) = self.beta_state.transition(
+            bob_PKs_beta.try_into()?,
+            &mut self.y,
+            bob_beta_round_1,
+            &self.transcript,
+        )?;
+        let (alpha_state, alpha_redeem_encsig) = self
             .alpha_state
-            .transition(bob_bitcoin_refund_signature, &self.y)?;
-        let grin_state = self.beta_state.transition(self.y, grin_redeem_encsig)?;
+            .transition(
+                bob_PKs_alpha.try_into()?,
+                bob_alpha_round_1,
+                &self.transcript,
+            )?
+            .transition(message.alpha_redeemer_sigs, &self.y)?;
 
-        let state = Alice2 {
-            alpha_state: bitcoin_state,
-            beta_state: grin_state,
+        let state = AliceResponder1 {
+            swap_id: self.swap_id,
+            alpha_state,
+            beta_state,
+            y: self.y,
         };
 
-        let message = Message4 {
-            alpha_redeem_encsig: bitcoin_redeem_encsig,
+        let message = bob_initiates::Message3 {
+            swap_id: self.swap_id,
+            alpha_redeem_encsig,
+            beta_redeemer_sigs,
         };
 
         Ok((state, message))
     }
+
+    pub fn swap_id(&self) -> SwapId {
+        self.swap_id
+    }
+
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::KeyExchange,
+        }
+    }
+
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
 }
 
-pub struct Alice2<AL, BL> {
-    pub alpha_state: AL,
-    pub beta_state: BL,
+pub struct AliceResponder1<AL, BL> {
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    y: KeyPair,
+}
+
+impl<AL, BL> AliceResponder1<AL, BL>
+where
+    BL: AliceRedeemer1,
+{
+    pub fn receive(
+        self,
+        message: bob_initiates::Message4<BL::EncryptedSignature>,
+    ) -> crate::Result<Alice2<AL, BL::Next>> {
+        self.swap_id.check(message.swap_id)?;
+
+        let beta_state = self
+            .beta_state
+            .transition(message.beta_redeem_encsig, &self.y)?;
+
+        Ok(Alice2 {
+            swap_id: self.swap_id,
+            alpha_state: self.alpha_state,
+            beta_state,
+        })
+    }
+
+    /// Give up on the swap before funding. Bob cannot redeem on the alpha
+    /// ledger without learning `y`, which Alice only reveals by redeeming on
+    /// the beta ledger.
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::Signing,
+        }
+    }
+
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
 }
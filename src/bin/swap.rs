@@ -0,0 +1,374 @@
+//! Run one side of a swap against a peer.
+//!
+//! The maker publishes a quote with `make-offer` and waits for a taker, who
+//! connects with `take`. Both then run the protocol and the execution of the
+//! swap until it is redeemed or refunded. Swaps are saved in the swap directory
+//! and can be continued with `resume` if the process was stopped. What
+//! happened in each swap can be listed with `history`.
+//!
+//! Node and wallet settings are read from a TOML file, see `swap::config`.
+
+use grin_btc_poc::{
+    error::RequestError,
+    history::{self, Query},
+    keypair::SECP,
+    negotiation::{Party, Quote, Rate, Roles},
+    swap::{
+        self,
+        config::{parse_public_key, Config},
+        AnyQuote, Offers, Peer, SwapFile,
+    },
+};
+use std::{
+    io::{self, BufRead},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "swap", about = "Atomic swaps between Grin and Bitcoin")]
+struct Opt {
+    #[structopt(long, default_value = "swap.toml", parse(from_os_str))]
+    config: PathBuf,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Publish a quote and run the swap with the first taker
+    MakeOffer {
+        #[structopt(long, default_value = "127.0.0.1:9735")]
+        listen: String,
+        /// Ledger of the alpha asset, "grin" or "bitcoin"
+        #[structopt(long)]
+        alpha: String,
+        /// Beta asset per unit of alpha asset, as numerator and denominator
+        #[structopt(long)]
+        rate_numerator: u64,
+        #[structopt(long)]
+        rate_denominator: u64,
+        #[structopt(long)]
+        min_alpha_asset: u64,
+        #[structopt(long)]
+        max_alpha_asset: u64,
+        /// Hours until the refund on the alpha ledger becomes valid
+        #[structopt(long, default_value = "48")]
+        alpha_expiry_hours: u64,
+        /// Hours until the refund on the beta ledger becomes valid
+        #[structopt(long, default_value = "24")]
+        beta_expiry_hours: u64,
+        /// Who generates the secret and funds first, "maker" or "taker"
+        #[structopt(long, default_value = "taker")]
+        alice: String,
+        /// Who sends the first message of the protocol, "maker" or "taker"
+        #[structopt(long, default_value = "maker")]
+        initiator: String,
+    },
+    /// Take the quote of a maker
+    Take {
+        #[structopt(long)]
+        connect: String,
+        /// Identity key of the maker, hex encoded
+        #[structopt(long)]
+        maker: String,
+        /// Amount of the alpha asset to swap
+        #[structopt(long)]
+        amount: u64,
+        /// Do not ask for confirmation of the offers
+        #[structopt(long)]
+        yes: bool,
+    },
+    /// List the saved swaps
+    Status,
+    /// Continue a saved swap until it is over
+    Resume { swap_id: String },
+    /// Refund a saved swap without waiting for the peer any longer
+    Refund { swap_id: String },
+    /// Sweep Grin left in special outputs of a saved swap into the wallet, and
+    /// refund an unspent Bitcoin fund output once its refund is unlocked
+    Recover { swap_id: String },
+    /// Print the recorded history of one swap, or of all of them
+    History {
+        swap_id: Option<String>,
+        /// Print as CSV instead of JSON lines
+        #[structopt(long)]
+        csv: bool,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    let config = Config::load(&opt.config)?;
+    let identity = config.identity()?;
+    let wallets = config.wallets()?;
+    let poll_interval = config.poll_interval();
+    let watchtower = config.watchtower()?;
+
+    match opt.command {
+        Command::MakeOffer {
+            listen,
+            alpha,
+            rate_numerator,
+            rate_denominator,
+            min_alpha_asset,
+            max_alpha_asset,
+            alpha_expiry_hours,
+            beta_expiry_hours,
+            alice,
+            initiator,
+        } => {
+            let rate = Rate {
+                numerator: rate_numerator,
+                denominator: rate_denominator,
+            };
+            let roles = Roles {
+                alice: parse_party(&alice)?,
+                initiator: parse_party(&initiator)?,
+            };
+
+            let quote = match alpha.as_str() {
+                "grin" => AnyQuote::GrinBitcoin(Quote {
+                    alpha: config.grin_offer(&wallets, alpha_expiry_hours)?,
+                    beta: config.bitcoin_offer(beta_expiry_hours)?,
+                    rate,
+                    min_alpha_asset,
+                    max_alpha_asset,
+                    maker: identity.public_key,
+                    roles,
+                }),
+                "bitcoin" => AnyQuote::BitcoinGrin(Quote {
+                    alpha: config.bitcoin_offer(alpha_expiry_hours)?,
+                    beta: config.grin_offer(&wallets, beta_expiry_hours)?,
+                    rate,
+                    min_alpha_asset,
+                    max_alpha_asset,
+                    maker: identity.public_key,
+                    roles,
+                }),
+                other => return Err(anyhow::anyhow!("unknown ledger {}", other)),
+            };
+
+            let listener = TcpListener::bind(&listen)?;
+            println!(
+                "Listening on {} as {}",
+                listen,
+                hex::encode(&identity.public_key.serialize_vec(&*SECP, true)[..])
+            );
+
+            let (stream, address) = listener.accept()?;
+            println!("Taker connected from {}", address);
+
+            let mut peer = Peer::accept(stream, identity.clone())?
+                .with_history(history::in_swap_dir(&config.swap_dir));
+            let terms = swap::setup::make(&mut peer, &quote, &identity)?;
+            print_offers(&terms.offers);
+
+            let (file, execution) = swap::start(&mut peer, &terms, &wallets, &config.swap_dir)?;
+            println!("Swap {} started as {:?}", file.swap_id, file.role);
+
+            let status = swap::run(
+                file,
+                &execution,
+                &wallets,
+                &config.swap_dir,
+                poll_interval,
+                watchtower.as_ref(),
+                report_retry,
+            )?;
+            println!("Swap is {:?}", status);
+        }
+        Command::Take {
+            connect,
+            maker,
+            amount,
+            yes,
+        } => {
+            let maker = parse_public_key(&maker)?;
+
+            let mut peer = Peer::connect(TcpStream::connect(&connect)?, identity.clone())?
+                .with_history(history::in_swap_dir(&config.swap_dir));
+            if peer.remote_identity() != &maker {
+                return Err(anyhow::anyhow!("peer is not the expected maker"));
+            }
+
+            let terms = swap::setup::take(&mut peer, amount, &identity, |offers, roles| {
+                print_offers(offers);
+                println!(
+                    "You are {}",
+                    if roles.is_alice(Party::Taker) {
+                        "Alice: you fund the alpha ledger first"
+                    } else {
+                        "Bob: you fund the beta ledger once alpha is funded"
+                    }
+                );
+
+                if yes || confirm()? {
+                    Ok(())
+                } else {
+                    Err(RequestError::Refused("offers declined".into()).into())
+                }
+            })?;
+
+            let (file, execution) = swap::start(&mut peer, &terms, &wallets, &config.swap_dir)?;
+            println!("Swap {} started as {:?}", file.swap_id, file.role);
+
+            let status = swap::run(
+                file,
+                &execution,
+                &wallets,
+                &config.swap_dir,
+                poll_interval,
+                watchtower.as_ref(),
+                report_retry,
+            )?;
+            println!("Swap is {:?}", status);
+        }
+        Command::Status => {
+            for file in SwapFile::list(&config.swap_dir)? {
+                println!(
+                    "{} {:?} {} {:?} -> {} {:?} {:?}",
+                    file.swap_id,
+                    file.role,
+                    file.alpha_asset,
+                    file.alpha_ledger,
+                    file.beta_asset,
+                    file.beta_ledger,
+                    file.status,
+                );
+            }
+        }
+        Command::Resume { swap_id } => {
+            let file = SwapFile::load(&config.swap_dir, &swap_id)?;
+            let execution = file.execution()?;
+
+            let status = swap::run(
+                file,
+                &execution,
+                &wallets,
+                &config.swap_dir,
+                poll_interval,
+                watchtower.as_ref(),
+                report_retry,
+            )?;
+            println!("Swap is {:?}", status);
+        }
+        Command::Refund { swap_id } => {
+            let mut file = SwapFile::load(&config.swap_dir, &swap_id)?;
+            let execution = file.execution()?;
+
+            let step = execution.refund(file.status, &wallets)?;
+            file.set_status(step.status);
+            file.save(&config.swap_dir)?;
+
+            let history = history::in_swap_dir(&config.swap_dir);
+            history.append_all(&swap_id, step.entries)?;
+            history.append(&swap_id, history::Entry::StatusChanged {
+                status: step.status,
+            })?;
+
+            if let Some(watchtower) = &watchtower {
+                watchtower.cancel(swap_id.parse()?)?;
+            }
+            println!("Swap is {:?}", step.status);
+        }
+        Command::Recover { swap_id } => {
+            let file = SwapFile::load(&config.swap_dir, &swap_id)?;
+            let execution = file.execution()?;
+
+            let mut recovered = false;
+            for recovery_data in execution.grin_recovery_data(&wallets)? {
+                if recovery_data.scan(&*wallets.grin)?.is_some() {
+                    let receipt = recovery_data.sweep(&*wallets.grin)?;
+                    println!(
+                        "Swept {:?} into the wallet, paying {} in fees",
+                        receipt.amounts, receipt.fee
+                    );
+                    history::in_swap_dir(&config.swap_dir).append(
+                        &swap_id,
+                        history::Entry::ActionExecuted {
+                            action: history::Action::Sweep,
+                            receipt,
+                        },
+                    )?;
+                    recovered = true;
+                }
+            }
+
+            // The refund transaction is timelocked, so this fails before the
+            // expiry of the Bitcoin offer
+            if let Some(recovery_data) = execution.bitcoin_recovery_data() {
+                let wallet = wallets.bitcoin_funder()?;
+                if recovery_data.scan(wallet)?.is_some() {
+                    let receipt = recovery_data.sweep(wallet)?;
+                    println!(
+                        "Refunded {:?} into the wallet, paying {} in fees",
+                        receipt.amounts, receipt.fee
+                    );
+                    history::in_swap_dir(&config.swap_dir).append(
+                        &swap_id,
+                        history::Entry::ActionExecuted {
+                            action: history::Action::Sweep,
+                            receipt,
+                        },
+                    )?;
+                    recovered = true;
+                }
+            }
+
+            if !recovered {
+                println!("Nothing to recover");
+            }
+        }
+        Command::History { swap_id, csv } => {
+            let history = history::in_swap_dir(&config.swap_dir);
+            let query = Query {
+                swap_id,
+                ..Query::default()
+            };
+
+            if csv {
+                history.export_csv(&query, io::stdout().lock())?;
+            } else {
+                for record in history.query(&query)? {
+                    println!("{}", serde_json::to_string(&record)?);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_offers(offers: &Offers) {
+    let (alpha_ledger, alpha_asset) = offers.alpha();
+    let (beta_ledger, beta_asset) = offers.beta();
+
+    println!(
+        "Offers: {} on {:?} for {} on {:?}",
+        alpha_asset, alpha_ledger, beta_asset, beta_ledger
+    );
+}
+
+fn report_retry(error: &grin_btc_poc::Error) {
+    eprintln!("Retrying after error: {:#}", error);
+}
+
+fn confirm() -> io::Result<bool> {
+    println!("Take these offers? [y/N]");
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+fn parse_party(party: &str) -> anyhow::Result<Party> {
+    match party {
+        "maker" => Ok(Party::Maker),
+        "taker" => Ok(Party::Taker),
+        other => Err(anyhow::anyhow!("unknown party {}", other)),
+    }
+}
@@ -0,0 +1,75 @@
+//! Run swaps in the background, controlled through a local JSON-RPC API.
+//!
+//! The daemon reads the same TOML configuration as `swap`, and listens for
+//! API connections on `rpc_address`, which must be a loopback address unless
+//! `rpc_allow_remote` is set. Slates are exchanged with the Grin wallet through
+//! `grin.slate_dir`, since there is no terminal to paste them into.
+//!
+//! If the configuration has a `[maker]` section, takers connecting to
+//! `maker.listen` are quoted and their swaps run automatically.
+
+use grin_btc_poc::{
+    daemon::{maker::Maker, rpc, Daemon},
+    keypair::SECP,
+    swap::config::Config,
+};
+use std::{net::TcpListener, path::PathBuf, thread};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "swapd", about = "Atomic swap daemon")]
+struct Opt {
+    #[structopt(long, default_value = "swap.toml", parse(from_os_str))]
+    config: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    let config = Config::load(&opt.config)?;
+    if config.grin.slate_dir.is_none() {
+        return Err(anyhow::anyhow!("swapd needs grin.slate_dir to be set"));
+    }
+
+    let daemon = Daemon::new(
+        config.identity()?,
+        config.wallets()?,
+        config.swap_dir.clone(),
+        config.poll_interval(),
+        Some(config.grin.inventory),
+        config.watchtower()?,
+    );
+
+    let events = daemon.subscribe();
+    thread::spawn(move || {
+        for event in events {
+            println!(
+                "{}",
+                serde_json::to_string(&event).expect("events serialize")
+            );
+        }
+    });
+
+    daemon.resume()?;
+
+    let listener = rpc::bind(&config.rpc_address, config.rpc_allow_remote)?;
+    println!(
+        "Listening for API requests on {} as {}",
+        config.rpc_address,
+        hex::encode(&daemon.identity().serialize_vec(&*SECP, true)[..])
+    );
+
+    if let Some(maker_config) = &config.maker {
+        let maker_listener = TcpListener::bind(&maker_config.listen)?;
+        println!("Quoting takers on {}", maker_config.listen);
+
+        let maker = Maker::new(daemon.clone(), config)?;
+        thread::spawn(move || {
+            if let Err(e) = maker.run(maker_listener) {
+                eprintln!("maker stopped: {}", e);
+            }
+        });
+    }
+
+    Ok(rpc::serve(daemon, listener)?)
+}
@@ -0,0 +1,57 @@
+//! Broadcast the refunds of swaps whose funder went offline.
+//!
+//! Swap processes configured with a `[watchtower]` section deposit the refund
+//! of every swap they fund through the Unix socket of the tower, see
+//! `watchtower`.
+
+use grin_btc_poc::{
+    keypair::SECP,
+    watchtower::{Event, TowerConfig},
+};
+use std::{fs, os::unix::net::UnixListener, path::PathBuf, sync::Arc, thread};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "watchtower", about = "Refunds swaps on behalf of their funder")]
+struct Opt {
+    #[structopt(long, default_value = "watchtower.toml", parse(from_os_str))]
+    config: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+
+    let config = TowerConfig::load(&opt.config)?;
+    let tower = Arc::new(config.tower()?);
+
+    // Left behind if the previous run was killed
+    if config.socket.exists() {
+        fs::remove_file(&config.socket)?;
+    }
+    let listener = UnixListener::bind(&config.socket)?;
+    println!(
+        "Listening on {} as {}",
+        config.socket.display(),
+        hex::encode(&tower.identity().serialize_vec(&*SECP, true)[..])
+    );
+
+    let watcher = tower.clone();
+    thread::spawn(move || {
+        if let Err(e) = watcher.watch(print_event) {
+            eprintln!("watching stopped: {}", e);
+            std::process::exit(1);
+        }
+    });
+
+    Ok(tower.serve(listener, print_event)?)
+}
+
+fn print_event(event: Event) {
+    match event {
+        Event::RequestFailed(e) => eprintln!("watchtower request failed: {}", e),
+        Event::Refunded { path } => println!("Broadcast the refund of {}", path.display()),
+        Event::RefundFailed { path, error } => {
+            eprintln!("failed to refund {}: {}", path.display(), error)
+        }
+    }
+}
@@ -6,11 +6,11 @@ use crate::{
         Client, Offer, PKs, SKs, Signature, Transaction,
     },
     ecdsa,
+    error::Context,
     keypair::KeyPair,
-    Execute,
+    Execute, Receipt,
 };
 use ::bitcoin::{hashes::Hash, util::bip143::SighashComponents, Script};
-use anyhow::Context;
 use secp256k1zkp::Message;
 
 #[derive(Clone)]
@@ -18,17 +18,25 @@ pub struct Fund {
     pub transaction: Transaction,
 }
 
+impl_wire!(Fund { transaction });
+
 #[derive(Clone)]
 pub struct Refund {
     pub transaction: Transaction,
+    // Paid from the fund output, which the transaction does not tell the value
+    // of
+    pub fee: u64,
 }
 
+impl_wire!(Refund { transaction, fee });
+
 impl Refund {
     pub fn new(
         transaction: Transaction,
         redeemer_sig: Signature,
         funder_sig: Signature,
         fund_output_script: Script,
+        fee: u64,
     ) -> Self {
         let mut completed_transaction = transaction;
         let funder_witness = signature_into_witness(funder_sig);
@@ -44,17 +52,28 @@ impl Refund {
 
         Refund {
             transaction: completed_transaction,
+            fee,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct EncryptedRedeem {
     pub transaction: Transaction,
     pub redeemer_sig: Signature,
     pub funder_encsig: ecdsa::EncryptedSignature,
     pub fund_output_script: Script,
+    pub fee: u64,
 }
 
+impl_wire!(EncryptedRedeem {
+    transaction,
+    redeemer_sig,
+    funder_encsig,
+    fund_output_script,
+    fee,
+});
+
 impl EncryptedRedeem {
     pub fn new(
         offer: &Offer,
@@ -62,7 +81,7 @@ impl EncryptedRedeem {
         redeemer_SKs: &SKs,
         funder_PKs: &PKs,
         funder_encsig: ecdsa::EncryptedSignature,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let (fund_transaction, fund_output_script) = fund_transaction(
             &offer,
             &wallet_outputs,
@@ -90,6 +109,7 @@ impl EncryptedRedeem {
             redeemer_sig,
             funder_encsig,
             fund_output_script,
+            fee: offer.fee,
         })
     }
 
@@ -110,6 +130,7 @@ impl EncryptedRedeem {
 
         Redeem {
             transaction: completed_transaction,
+            fee: self.fee,
         }
     }
 }
@@ -117,34 +138,55 @@ impl EncryptedRedeem {
 #[derive(Clone)]
 pub struct Redeem {
     pub transaction: Transaction,
+    pub fee: u64,
 }
 
+impl_wire!(Redeem { transaction, fee });
+
 impl Execute for Fund {
     type Wallet = FunderWallet;
-    type Return = ();
-    fn execute(self, wallet: &Self::Wallet) -> anyhow::Result<Self::Return> {
+    fn execute(self, wallet: &Self::Wallet) -> crate::Result<Receipt> {
+        let spent = self
+            .transaction
+            .input
+            .iter()
+            .map(|input| wallet.owned_output(&input.previous_output))
+            .collect::<crate::Result<Vec<_>>>()?
+            .iter()
+            .map(|output| output.txout.value)
+            .sum::<u64>();
+        let paid = self
+            .transaction
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<u64>();
+
         let transaction = wallet.sign_input(self.transaction)?;
+        wallet.send_rawtransaction(&transaction).context("fund")?;
 
-        wallet.send_rawtransaction(&transaction).context("fund")
+        Ok(Receipt::bitcoin(&transaction, spent.saturating_sub(paid)))
     }
 }
 
 impl Execute for Redeem {
     type Wallet = RedeemerWallet;
-    type Return = ();
-    fn execute(self, wallet: &Self::Wallet) -> anyhow::Result<Self::Return> {
+    fn execute(self, wallet: &Self::Wallet) -> crate::Result<Receipt> {
         wallet
             .send_rawtransaction(&self.transaction)
-            .context("redeem")
+            .context("redeem")?;
+
+        Ok(Receipt::bitcoin(&self.transaction, self.fee))
     }
 }
 
 impl Execute for Refund {
     type Wallet = FunderWallet;
-    type Return = ();
-    fn execute(self, wallet: &Self::Wallet) -> anyhow::Result<Self::Return> {
+    fn execute(self, wallet: &Self::Wallet) -> crate::Result<Receipt> {
         wallet
             .send_rawtransaction(&self.transaction)
-            .context("refund")
+            .context("refund")?;
+
+        Ok(Receipt::bitcoin(&self.transaction, self.fee))
     }
 }
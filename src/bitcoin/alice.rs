@@ -1,9 +1,12 @@
 use crate::{
     bitcoin::{
-        action, sign::FunderActions, wallet_outputs::WalletOutputs, EncryptedSignature, Funder0,
-        Funder1, Offer, PKs, Redeemer0, Redeemer1, Signature,
+        action, cooperative, recovery::RecoveryData, sign::FunderActions,
+        wallet_outputs::WalletOutputs, EncryptedSignature, Funder0, Funder1, Offer, PKs, Redeemer0,
+        Redeemer1, Signature,
     },
     commit::CoinTossingKeys,
+    ledger,
+    transcript::Transcript,
     KeyPair,
 };
 use std::convert::TryInto;
@@ -15,20 +18,50 @@ impl AliceFunder0 {
     pub fn new(offer: Offer, wallet_outputs: WalletOutputs) -> Self {
         Self(Funder0::new(offer, wallet_outputs))
     }
+}
+
+impl ledger::AliceFunder0 for AliceFunder0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = ();
+    type Next = AliceFunder1;
+
+    fn offer(&self) -> &Offer {
+        &self.0.offer
+    }
+
+    fn bulletproof_round_1(&self) {}
 
-    pub fn transition(self, PKs_other: PKs) -> AliceFunder1 {
-        AliceFunder1(self.0.transition(PKs_other))
+    fn transition(
+        self,
+        PKs_other: PKs,
+        _: (),
+        transcript: &Transcript,
+    ) -> crate::Result<AliceFunder1> {
+        Ok(AliceFunder1(self.0.transition(PKs_other, transcript)))
     }
 }
 
 pub struct AliceFunder1(pub Funder1);
 
-impl AliceFunder1 {
-    pub fn transition(
+impl ledger::AliceFunder1 for AliceFunder1 {
+    type RedeemerSigs = Signature;
+    type EncryptedSignature = EncryptedSignature;
+    type Next = AliceFunder2;
+
+    fn transition(
         self,
         redeemer_refund_sig: Signature,
         y: &KeyPair,
-    ) -> anyhow::Result<(AliceFunder2, EncryptedSignature)> {
+    ) -> crate::Result<(AliceFunder2, EncryptedSignature)> {
+        let cooperative_refund = cooperative::Funder::new(
+            self.0.offer.clone(),
+            self.0.wallet_outputs.clone(),
+            self.0.SKs_self.clone(),
+            self.0.PKs_other.clone(),
+        );
+        let recovery_data = self.0.recovery_data(redeemer_refund_sig);
+
         let (FunderActions { fund, refund }, redeem_encsig) =
             self.0.sign(&y.public_key, redeemer_refund_sig)?;
 
@@ -36,6 +69,8 @@ impl AliceFunder1 {
             AliceFunder2 {
                 fund_action: fund,
                 refund_action: refund,
+                cooperative_refund,
+                recovery_data,
             },
             redeem_encsig,
         ))
@@ -45,6 +80,8 @@ impl AliceFunder1 {
 pub struct AliceFunder2 {
     pub fund_action: action::Fund,
     pub refund_action: action::Refund,
+    pub cooperative_refund: cooperative::Funder,
+    pub recovery_data: RecoveryData,
 }
 
 #[derive(Clone)]
@@ -54,8 +91,28 @@ impl AliceRedeemer0 {
     pub fn new(offer: Offer, wallet_outputs: WalletOutputs) -> Self {
         Self(Redeemer0::new(offer, wallet_outputs))
     }
+}
+
+impl ledger::AliceRedeemer0 for AliceRedeemer0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = ();
+    type RedeemerSigs = Signature;
+    type Next = AliceRedeemer1;
 
-    pub fn transition(self, PKs_other: PKs) -> anyhow::Result<(AliceRedeemer1, Signature)> {
+    fn offer(&self) -> &Offer {
+        &self.0.offer
+    }
+
+    fn bulletproof_round_1(&self) {}
+
+    fn transition(
+        self,
+        PKs_other: PKs,
+        _: &mut KeyPair,
+        _: (),
+        _: &Transcript,
+    ) -> crate::Result<(AliceRedeemer1, Signature)> {
         let (state, redeemer_refund_sig) = self.0.transition(PKs_other)?;
 
         Ok((AliceRedeemer1(state), redeemer_refund_sig))
@@ -65,12 +122,15 @@ impl AliceRedeemer0 {
 #[derive(Clone)]
 pub struct AliceRedeemer1(pub Redeemer1);
 
-impl AliceRedeemer1 {
-    pub fn transition(
+impl ledger::AliceRedeemer1 for AliceRedeemer1 {
+    type EncryptedSignature = EncryptedSignature;
+    type Next = AliceRedeemer2;
+
+    fn transition(
         self,
         redeem_encsig: EncryptedSignature,
         y: &KeyPair,
-    ) -> anyhow::Result<AliceRedeemer2> {
+    ) -> crate::Result<AliceRedeemer2> {
         let encrypted_redeem_action = action::EncryptedRedeem::new(
             &self.0.offer,
             &self.0.wallet_outputs,
@@ -80,12 +140,23 @@ impl AliceRedeemer1 {
         )?;
         let redeem_action = encrypted_redeem_action.decrypt(&y);
 
-        Ok(AliceRedeemer2 { redeem_action })
+        let cooperative_refund = cooperative::Redeemer::new(
+            self.0.offer,
+            self.0.wallet_outputs,
+            self.0.SKs_self,
+            self.0.PKs_other,
+        );
+
+        Ok(AliceRedeemer2 {
+            redeem_action,
+            cooperative_refund,
+        })
     }
 }
 
 pub struct AliceRedeemer2 {
     pub redeem_action: action::Redeem,
+    pub cooperative_refund: cooperative::Redeemer,
 }
 
 impl Into<CoinTossingKeys> for AliceFunder0 {
@@ -102,9 +173,15 @@ impl Into<CoinTossingKeys> for AliceRedeemer0 {
     }
 }
 
+impl Into<CoinTossingKeys> for PKs {
+    fn into(self) -> CoinTossingKeys {
+        vec![self.X]
+    }
+}
+
 impl TryInto<PKs> for CoinTossingKeys {
-    type Error = anyhow::Error;
-    fn try_into(self) -> anyhow::Result<PKs> {
+    type Error = crate::Error;
+    fn try_into(self) -> crate::Result<PKs> {
         Ok(PKs { X: self[0] })
     }
 }
@@ -1,9 +1,12 @@
 use crate::{
     bitcoin::{
-        action, event, sign::FunderActions, wallet_outputs::WalletOutputs, EncryptedSignature,
-        Funder0, Funder1, Offer, PKs, PublicKey, Redeemer0, Redeemer1, Signature,
+        action, cooperative, event, recovery::RecoveryData, sign::FunderActions,
+        wallet_outputs::WalletOutputs, EncryptedSignature, Funder0, Funder1, Offer, PKs, PublicKey,
+        Redeemer0, Redeemer1, Signature,
     },
     ecdsa::{self, RecoveryKey},
+    ledger,
+    transcript::Transcript,
 };
 
 #[derive(Clone)]
@@ -13,14 +16,36 @@ impl BobFunder0 {
     pub fn new(offer: Offer, wallet_outputs: WalletOutputs) -> Self {
         Self(Funder0::new(offer, wallet_outputs))
     }
+}
+
+impl ledger::BobFunder0 for BobFunder0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = ();
+    type RedeemerSigs = Signature;
+    type EncryptedSignature = EncryptedSignature;
+    type Next = BobFunder1;
+
+    fn offer(&self) -> &Offer {
+        &self.0.offer
+    }
+
+    fn bulletproof_round_1(&self) {}
+
+    fn public_keys(&self) -> PKs {
+        self.0.SKs_self.clone().into()
+    }
 
-    pub fn transition(
+    fn transition(
         self,
         PKs_other: PKs,
         redeemer_refund_sig: Signature,
         Y: &PublicKey,
-    ) -> anyhow::Result<(BobFunder1, EncryptedSignature)> {
-        let state = self.0.transition(PKs_other);
+        _: (),
+        transcript: &Transcript,
+    ) -> crate::Result<(BobFunder1, EncryptedSignature)> {
+        let state = self.0.transition(PKs_other, transcript);
+        let recovery_data = state.recovery_data(redeemer_refund_sig);
 
         let (FunderActions { fund, refund }, redeem_encsig) =
             state.clone().sign(Y, redeemer_refund_sig)?;
@@ -32,6 +57,7 @@ impl BobFunder0 {
                 fund_action: fund,
                 refund_action: refund,
                 recovery_key,
+                recovery_data,
             },
             redeem_encsig,
         ))
@@ -43,22 +69,32 @@ pub struct BobFunder1 {
     fund_action: action::Fund,
     refund_action: action::Refund,
     recovery_key: RecoveryKey,
+    recovery_data: RecoveryData,
 }
 
-impl BobFunder1 {
-    pub fn transition(self) -> anyhow::Result<BobFunder2> {
+impl ledger::BobFunder1 for BobFunder1 {
+    type Next = BobFunder2;
+
+    fn transition(self) -> crate::Result<BobFunder2> {
         let redeem_event = event::Redeem::new(
             &self.common.offer,
             &self.common.wallet_outputs,
             &self.common.PKs_other,
-            &self.common.SKs_self.into(),
+            &self.common.SKs_self.clone().into(),
         )?;
 
         Ok(BobFunder2 {
             fund_action: self.fund_action,
             refund_action: self.refund_action,
+            cooperative_refund: cooperative::Funder::new(
+                self.common.offer,
+                self.common.wallet_outputs,
+                self.common.SKs_self,
+                self.common.PKs_other,
+            ),
             recovery_key: self.recovery_key,
             redeem_event,
+            recovery_data: self.recovery_data,
         })
     }
 }
@@ -66,8 +102,10 @@ impl BobFunder1 {
 pub struct BobFunder2 {
     pub fund_action: action::Fund,
     pub refund_action: action::Refund,
+    pub cooperative_refund: cooperative::Funder,
     pub recovery_key: RecoveryKey,
     pub redeem_event: event::Redeem,
+    pub recovery_data: RecoveryData,
 }
 
 #[derive(Clone)]
@@ -77,8 +115,32 @@ impl BobRedeemer0 {
     pub fn new(offer: Offer, wallet_outputs: WalletOutputs) -> Self {
         Self(Redeemer0::new(offer, wallet_outputs))
     }
+}
+
+impl ledger::BobRedeemer0 for BobRedeemer0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = ();
+    type RedeemerSigs = Signature;
+    type Next = BobRedeemer1;
+
+    fn offer(&self) -> &Offer {
+        &self.0.offer
+    }
+
+    fn bulletproof_round_1(&self) {}
 
-    pub fn transition(self, PKs_other: PKs) -> anyhow::Result<(BobRedeemer1, Signature)> {
+    fn public_keys(&self) -> PKs {
+        self.0.SKs_self.clone().into()
+    }
+
+    fn transition(
+        self,
+        PKs_other: PKs,
+        _: &mut PublicKey,
+        _: (),
+        _: &Transcript,
+    ) -> crate::Result<(BobRedeemer1, Signature)> {
         let (state, redeemer_refund_sig) = self.0.transition(PKs_other)?;
 
         Ok((BobRedeemer1(state), redeemer_refund_sig))
@@ -87,8 +149,15 @@ impl BobRedeemer0 {
 
 pub struct BobRedeemer1(pub Redeemer1);
 
-impl BobRedeemer1 {
-    pub fn transition(self, redeem_encsig: EncryptedSignature) -> anyhow::Result<BobRedeemer2> {
+impl ledger::BobRedeemer1 for BobRedeemer1 {
+    type EncryptedSignature = EncryptedSignature;
+    type Next = BobRedeemer2;
+
+    fn transition(
+        self,
+        _: &PublicKey,
+        redeem_encsig: EncryptedSignature,
+    ) -> crate::Result<BobRedeemer2> {
         let encrypted_redeem_action = action::EncryptedRedeem::new(
             &self.0.offer,
             &self.0.wallet_outputs,
@@ -99,22 +168,17 @@ impl BobRedeemer1 {
 
         Ok(BobRedeemer2 {
             encrypted_redeem_action,
+            cooperative_refund: cooperative::Redeemer::new(
+                self.0.offer,
+                self.0.wallet_outputs,
+                self.0.SKs_self,
+                self.0.PKs_other,
+            ),
         })
     }
 }
 
 pub struct BobRedeemer2 {
     pub encrypted_redeem_action: action::EncryptedRedeem,
-}
-
-impl Into<PKs> for BobFunder0 {
-    fn into(self) -> PKs {
-        self.0.SKs_self.into()
-    }
-}
-
-impl Into<PKs> for BobRedeemer0 {
-    fn into(self) -> PKs {
-        self.0.SKs_self.into()
-    }
+    pub cooperative_refund: cooperative::Redeemer,
 }
@@ -1,17 +1,22 @@
-use crate::bitcoin::Transaction;
-use bitcoin::{consensus::encode::Encodable, hashes::sha256d, util::psbt::serialize::Deserialize};
+use crate::{
+    bitcoin::{OutPoint, Transaction, TxOut},
+    error::BitcoinError,
+};
+use bitcoin::{
+    consensus::encode::Encodable, hashes::sha256d, util::psbt::serialize::Deserialize, Script,
+};
 
 pub trait Client {
     fn node_url(&self) -> String;
-    fn generate_blocks(&self, n: u32) -> anyhow::Result<()> {
+    fn generate_blocks(&self, n: u32) -> crate::Result<()> {
         ureq::post(&self.node_url())
             .send_json(ureq::json!({"jsonrpc": "1.0", "method": "generate", "params": [n] }))
             .into_json()
             .map(|_| ())
-            .map_err(|e| anyhow::anyhow!("failed to generate blocks: {}", e))
+            .map_err(|e| BitcoinError::Node(format!("failed to generate blocks: {}", e)).into())
     }
 
-    fn get_rawtransaction(&self, txid: &sha256d::Hash) -> anyhow::Result<Transaction> {
+    fn get_rawtransaction(&self, txid: &sha256d::Hash) -> crate::Result<Transaction> {
         let res = ureq::post(&Client::node_url(self))
         .send_json(ureq::json!({"jsonrpc": "1.0", "method": "getrawtransaction", "params": [format!("{}", txid), 1] }));
 
@@ -27,13 +32,111 @@ pub trait Client {
                 .as_str()
                 .expect("value is string");
 
-            Ok(Transaction::deserialize(&hex::decode(hex_tx)?)?)
+            hex::decode(hex_tx)
+                .map_err(|e| e.to_string())
+                .and_then(|raw_tx| Transaction::deserialize(&raw_tx).map_err(|e| e.to_string()))
+                .map_err(|e| BitcoinError::Node(format!("invalid raw transaction: {}", e)).into())
         } else {
-            Err(anyhow::anyhow!("failed to get raw transaction"))
+            Err(BitcoinError::Node("failed to get raw transaction".into()).into())
         }
     }
 
-    fn send_rawtransaction(&self, transaction: &Transaction) -> anyhow::Result<()> {
+    fn get_block_count(&self) -> crate::Result<u64> {
+        let res = ureq::post(&Client::node_url(self))
+            .send_json(ureq::json!({"jsonrpc": "1.0", "method": "getblockcount", "params": [] }));
+
+        if res.ok() {
+            let json = res.into_json()?;
+
+            Ok(json["result"].as_u64().expect("value is number"))
+        } else {
+            Err(BitcoinError::Node("failed to get block count".into()).into())
+        }
+    }
+
+    /// The median time of the last 11 blocks, which timestamp lock times are
+    /// compared against.
+    fn get_median_time_past(&self) -> crate::Result<u64> {
+        let res = ureq::post(&Client::node_url(self)).send_json(
+            ureq::json!({"jsonrpc": "1.0", "method": "getblockchaininfo", "params": [] }),
+        );
+
+        if res.ok() {
+            let json = res.into_json()?;
+
+            Ok(json["result"]["mediantime"]
+                .as_u64()
+                .expect("value is number"))
+        } else {
+            Err(BitcoinError::Node("failed to get median time past".into()).into())
+        }
+    }
+
+    /// Returns the transaction output at `outpoint` if it is unspent.
+    fn get_txout(&self, outpoint: &OutPoint) -> crate::Result<Option<TxOut>> {
+        let res = ureq::post(&Client::node_url(self))
+        .send_json(ureq::json!({"jsonrpc": "1.0", "method": "gettxout", "params": [format!("{}", outpoint.txid), outpoint.vout] }));
+
+        if !res.ok() {
+            return Err(BitcoinError::Node("failed to get transaction output".into()).into());
+        }
+
+        let json = res.into_json()?;
+        let txout = json
+            .as_object()
+            .expect("response is object")
+            .get("result")
+            .expect("field exists");
+
+        if txout.is_null() {
+            return Ok(None);
+        }
+
+        let value = txout
+            .get("value")
+            .expect("field exists")
+            .as_f64()
+            .expect("value is number");
+        let script_pubkey = txout
+            .get("scriptPubKey")
+            .expect("field exists")
+            .get("hex")
+            .expect("field exists")
+            .as_str()
+            .expect("value is string");
+
+        let script_pubkey = hex::decode(script_pubkey)
+            .map_err(|e| BitcoinError::Node(format!("invalid script pubkey: {}", e)))?;
+
+        Ok(Some(TxOut {
+            value: (value * 100_000_000.0).round() as u64,
+            script_pubkey: Script::from(script_pubkey),
+        }))
+    }
+
+    /// The confirmations of the transaction output at `outpoint` if it is
+    /// unspent, zero while the transaction is in the mempool.
+    fn get_txout_confirmations(&self, outpoint: &OutPoint) -> crate::Result<Option<u64>> {
+        let res = ureq::post(&Client::node_url(self))
+        .send_json(ureq::json!({"jsonrpc": "1.0", "method": "gettxout", "params": [format!("{}", outpoint.txid), outpoint.vout] }));
+
+        if !res.ok() {
+            return Err(BitcoinError::Node("failed to get transaction output".into()).into());
+        }
+
+        let json = res.into_json()?;
+        let txout = &json["result"];
+
+        if txout.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            txout["confirmations"].as_u64().expect("value is number"),
+        ))
+    }
+
+    fn send_rawtransaction(&self, transaction: &Transaction) -> crate::Result<()> {
         let mut raw_tx = vec![];
         transaction
             .consensus_encode(&mut raw_tx)
@@ -47,7 +150,15 @@ pub trait Client {
 
             Ok(())
         } else {
-            Err(anyhow::anyhow!("failed to send raw transaction"))
+            let status = res.status();
+            // The node tells why in the error of the response
+            let reason = res
+                .into_json()
+                .ok()
+                .and_then(|json| json["error"]["message"].as_str().map(String::from))
+                .unwrap_or_else(|| format!("HTTP status {}", status));
+
+            Err(BitcoinError::rejected(transaction.txid().to_string(), reason).into())
         }
     }
 }
@@ -0,0 +1,117 @@
+//! Refunding the fund output of a swap before its expiry, with the help of the
+//! redeemer.
+//!
+//! The cooperative refund transaction is the refund transaction without its
+//! lock time. The redeemer signs it on their own and the funder completes it
+//! with their signature, so a single message is needed.
+
+use crate::{
+    bitcoin::{
+        action,
+        transaction::{cooperative_refund_transaction, fund_transaction},
+        Offer, PKs, SKs, Signature, WalletOutputs,
+    },
+    error::BitcoinError,
+    keypair::{self, PublicKey},
+};
+use ::bitcoin::{hashes::Hash, util::bip143::SighashComponents, Script, Transaction};
+use secp256k1zkp::Message;
+
+pub struct Funder {
+    offer: Offer,
+    wallet_outputs: WalletOutputs,
+    SKs_self: SKs,
+    PKs_other: PKs,
+}
+
+impl Funder {
+    pub fn new(offer: Offer, wallet_outputs: WalletOutputs, SKs_self: SKs, PKs_other: PKs) -> Self {
+        Self {
+            offer,
+            wallet_outputs,
+            SKs_self,
+            PKs_other,
+        }
+    }
+
+    pub fn receive(self, redeemer_sig: Signature) -> crate::Result<action::Refund> {
+        let (transaction, digest, fund_output_script) = cooperative_refund_digest(
+            &self.offer,
+            &self.wallet_outputs,
+            &self.PKs_other.X,
+            &self.SKs_self.x.public_key,
+        )?;
+
+        if !keypair::verify_ecdsa(&digest, &redeemer_sig, &self.PKs_other.X) {
+            return Err(BitcoinError::InvalidRedeemerSignature("cooperative refund").into());
+        }
+
+        let funder_sig = self.SKs_self.x.sign_ecdsa(&digest);
+
+        Ok(action::Refund::new(
+            transaction,
+            redeemer_sig,
+            funder_sig,
+            fund_output_script,
+            self.offer.fee,
+        ))
+    }
+}
+
+pub struct Redeemer {
+    offer: Offer,
+    wallet_outputs: WalletOutputs,
+    SKs_self: SKs,
+    PKs_other: PKs,
+}
+
+impl Redeemer {
+    pub fn new(offer: Offer, wallet_outputs: WalletOutputs, SKs_self: SKs, PKs_other: PKs) -> Self {
+        Self {
+            offer,
+            wallet_outputs,
+            SKs_self,
+            PKs_other,
+        }
+    }
+
+    /// Sign the cooperative refund for the funder.
+    ///
+    /// Once the signature is sent, the funder can take the fund output back at
+    /// any time. It must only be signed when the redeemer is certain not to
+    /// lose anything as a result, e.g. because they never funded the other
+    /// ledger or because their own refund there is already confirmed.
+    pub fn sign(&self) -> crate::Result<Signature> {
+        let (_, digest, _) = cooperative_refund_digest(
+            &self.offer,
+            &self.wallet_outputs,
+            &self.SKs_self.x.public_key,
+            &self.PKs_other.X,
+        )?;
+
+        Ok(self.SKs_self.x.sign_ecdsa(&digest))
+    }
+}
+
+fn cooperative_refund_digest(
+    offer: &Offer,
+    wallet_outputs: &WalletOutputs,
+    redeemer_key: &PublicKey,
+    funder_key: &PublicKey,
+) -> crate::Result<(Transaction, Message, Script)> {
+    let (fund_transaction, fund_output_script) =
+        fund_transaction(&offer, &wallet_outputs, redeemer_key, funder_key)?;
+
+    let transaction =
+        cooperative_refund_transaction(&offer, &wallet_outputs, fund_transaction.txid());
+
+    let digest = SighashComponents::new(&transaction).sighash_all(
+        &transaction.input[0],
+        &fund_output_script,
+        fund_transaction.output[0].value,
+    );
+    let digest =
+        Message::from_slice(&digest.into_inner()).expect("should not fail because it is a hash");
+
+    Ok((transaction, digest, fund_output_script))
+}
@@ -12,6 +12,7 @@ use ::bitcoin::{
 };
 use secp256k1zkp::Message;
 
+#[derive(Clone)]
 pub struct Redeem {
     // To identify the redeem transaction on Bitcoin
     pub txid: sha256d::Hash,
@@ -20,13 +21,19 @@ pub struct Redeem {
     pub message_hash: Message,
 }
 
+impl_wire!(Redeem {
+    txid,
+    funder_pk,
+    message_hash,
+});
+
 impl Redeem {
     pub fn new(
         offer: &Offer,
         wallet_outputs: &WalletOutputs,
         redeemer_PKs: &PKs,
         funder_PKs: &PKs,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let (fund_transaction, fund_output_script) =
             fund_transaction(&offer, &wallet_outputs, &redeemer_PKs.X, &funder_PKs.X)?;
         let redeem_transaction =
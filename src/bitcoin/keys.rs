@@ -9,6 +9,8 @@ pub struct SKs {
     pub x: KeyPair,
 }
 
+impl_wire!(SKs { x });
+
 impl Into<PKs> for SKs {
     fn into(self) -> PKs {
         PKs {
@@ -22,6 +24,8 @@ pub struct PKs {
     pub X: PublicKey,
 }
 
+impl_wire!(PKs { X });
+
 impl Hash for PKs {
     fn hash(&self) -> [u8; 64] {
         let mut hasher = Blake2b::new();
@@ -1,14 +1,17 @@
-use crate::bitcoin::sign::FunderActions;
+use crate::{bitcoin::sign::FunderActions, transcript::Transcript};
 
 pub mod action;
 pub mod alice;
 pub mod bob;
+pub mod chain;
 pub mod client;
+pub mod cooperative;
 pub mod event;
 pub mod keygen;
 pub mod keys;
 pub mod node;
 pub mod offer;
+pub mod recovery;
 pub mod sign;
 pub mod transaction;
 pub mod wallet;
@@ -18,10 +21,11 @@ pub use crate::{
     bitcoin::{
         alice::*,
         bob::*,
+        chain::ChainParams,
         keygen::keygen,
         keys::{PKs, SKs},
         offer::Offer,
-        wallet_outputs::WalletOutputs,
+        wallet_outputs::{FundInput, WalletOutputs},
     },
     ecdsa::EncryptedSignature,
     PublicKey,
@@ -52,12 +56,13 @@ impl Funder0 {
         }
     }
 
-    pub fn transition(self, PKs_other: PKs) -> Funder1 {
+    pub fn transition(self, PKs_other: PKs, transcript: &Transcript) -> Funder1 {
         Funder1 {
             offer: self.offer,
             wallet_outputs: self.wallet_outputs,
             SKs_self: self.SKs_self,
             PKs_other,
+            transcript: transcript.clone(),
         }
     }
 }
@@ -68,6 +73,7 @@ pub struct Funder1 {
     pub wallet_outputs: WalletOutputs,
     pub SKs_self: SKs,
     pub PKs_other: PKs,
+    pub transcript: Transcript,
 }
 
 impl Funder1 {
@@ -75,7 +81,7 @@ impl Funder1 {
         self,
         Y: &PublicKey,
         redeemer_refund_sig: Signature,
-    ) -> anyhow::Result<(FunderActions, EncryptedSignature)> {
+    ) -> crate::Result<(FunderActions, EncryptedSignature)> {
         let (funder_actions, redeem_encsig) = sign::funder(
             &self.offer,
             &self.wallet_outputs,
@@ -83,6 +89,7 @@ impl Funder1 {
             &self.PKs_other,
             &Y,
             &redeemer_refund_sig,
+            &self.transcript,
         )?;
 
         Ok((funder_actions, redeem_encsig))
@@ -112,7 +119,7 @@ impl Redeemer0 {
         }
     }
 
-    pub fn transition(self, PKs_other: PKs) -> anyhow::Result<(Redeemer1, Signature)> {
+    pub fn transition(self, PKs_other: PKs) -> crate::Result<(Redeemer1, Signature)> {
         let redeemer_refund_sig = sign::redeemer(
             &self.offer,
             &self.wallet_outputs,
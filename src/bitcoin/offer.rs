@@ -1,21 +1,54 @@
+use crate::{
+    bitcoin::ChainParams,
+    error::{ProtocolError, WalletError},
+    expiry::RefundLock,
+    ledger::LedgerOffer,
+    wire::{Decode, Encode},
+    Hash,
+};
+use blake2::{Blake2b, Digest};
+
 #[derive(Debug, Clone)]
 pub struct Offer {
     pub asset: u64,
     pub fee: u64,
-    pub expiry: u32, // absolute timestamp
+    pub expiry: u32, // absolute timestamp, or block height below LOCK_TIME_THRESHOLD
+    pub chain: ChainParams,
 }
 
+// Lock times below this value are interpreted as block heights
+pub const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
 impl Offer {
+    /// Ensure none of the outputs of the swap are dust on the chain of the
+    /// offer, which would prevent the transactions from being relayed
+    pub fn check_dust(&self, input: u64) -> crate::Result<()> {
+        let change = self.change_output_amount(input)?;
+
+        if self.asset < self.chain.dust_limit || change < self.chain.dust_limit {
+            return Err(ProtocolError::InvalidOffer(format!(
+                "{} outputs must not be below the dust limit of {}",
+                self.chain.name, self.chain.dust_limit
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
     // funder pays for fee of redeem/refund tx
     pub fn fund_output_amount(&self) -> u64 {
         self.asset + self.fee
     }
 
-    pub fn change_output_amount(&self, input: u64) -> anyhow::Result<u64> {
+    pub fn change_output_amount(&self, input: u64) -> crate::Result<u64> {
         input
             .checked_sub(self.asset + (2 * self.fee))
             .ok_or_else(|| {
-                anyhow::anyhow!("Bitcoin input amount does not cover fund output amount plus fees")
+                WalletError::InsufficientFunds(
+                    "Bitcoin input amount does not cover fund output amount plus fees".into(),
+                )
+                .into()
             })
     }
 
@@ -27,3 +60,65 @@ impl Offer {
         self.asset
     }
 }
+
+impl LedgerOffer for Offer {
+    fn asset(&self) -> u64 {
+        self.asset
+    }
+
+    fn with_asset(self, asset: u64) -> Self {
+        Self { asset, ..self }
+    }
+
+    // Same interpretation as the lock time of the refund transaction
+    fn refund_lock(&self) -> RefundLock {
+        if self.expiry < LOCK_TIME_THRESHOLD {
+            RefundLock::Height(u64::from(self.expiry))
+        } else {
+            RefundLock::Timestamp(u64::from(self.expiry))
+        }
+    }
+}
+
+impl Hash for Offer {
+    fn hash(&self) -> [u8; 64] {
+        let mut hasher = Blake2b::new();
+
+        hasher.input(self.chain.name.as_bytes());
+        hasher.input(self.asset.to_be_bytes());
+        hasher.input(self.fee.to_be_bytes());
+        hasher.input(self.expiry.to_be_bytes());
+
+        let mut hash = [0u8; 64];
+        hash.copy_from_slice(&hasher.result());
+        hash
+    }
+}
+
+// The chain is identified by its name, which is also what the hash commits to
+impl Encode for Offer {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.asset.encode(buf);
+        self.fee.encode(buf);
+        self.expiry.encode(buf);
+        self.chain.name.encode(buf);
+    }
+}
+
+impl Decode for Offer {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        let asset = u64::decode(bytes)?;
+        let fee = u64::decode(bytes)?;
+        let expiry = u32::decode(bytes)?;
+        let name = String::decode(bytes)?;
+        let chain = ChainParams::from_name(&name)
+            .ok_or_else(|| ProtocolError::InvalidOffer(format!("unsupported chain {}", name)))?;
+
+        Ok(Self {
+            asset,
+            fee,
+            expiry,
+            chain,
+        })
+    }
+}
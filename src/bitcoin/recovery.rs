@@ -0,0 +1,88 @@
+use crate::{
+    bitcoin::{
+        action, sign, transaction::fund_transaction, wallet::FunderWallet, Client, Funder1, Offer,
+        OutPoint, PKs, SKs, Signature, TxOut, WalletOutputs,
+    },
+    error::RequestError,
+    Execute, Receipt,
+};
+
+/// The secrets and public parameters the funder needs to get their Bitcoin
+/// back from the fund output, even if the refund action was lost.
+///
+/// It should be exported by the caller as soon as the redeemer's refund
+/// signature is received, and kept until the fund output is spent. The wire
+/// encoding is the export format.
+#[derive(Clone)]
+pub struct RecoveryData {
+    pub offer: Offer,
+    pub wallet_outputs: WalletOutputs,
+    pub SKs_self: SKs,
+    pub PKs_other: PKs,
+    pub redeemer_refund_sig: Signature,
+}
+
+impl_wire!(RecoveryData {
+    offer,
+    wallet_outputs,
+    SKs_self,
+    PKs_other,
+    redeemer_refund_sig,
+});
+
+impl Funder1 {
+    pub fn recovery_data(&self, redeemer_refund_sig: Signature) -> RecoveryData {
+        RecoveryData {
+            offer: self.offer.clone(),
+            wallet_outputs: self.wallet_outputs.clone(),
+            SKs_self: self.SKs_self.clone(),
+            PKs_other: self.PKs_other.clone(),
+            redeemer_refund_sig,
+        }
+    }
+}
+
+impl RecoveryData {
+    pub fn fund_outpoint(&self) -> crate::Result<OutPoint> {
+        let (fund_transaction, _) = fund_transaction(
+            &self.offer,
+            &self.wallet_outputs,
+            &self.PKs_other.X,
+            &self.SKs_self.x.public_key,
+        )?;
+
+        Ok(OutPoint {
+            txid: fund_transaction.txid(),
+            vout: 0,
+        })
+    }
+
+    /// Look for the fund output of the swap in the UTXO set.
+    pub fn scan(&self, client: &impl Client) -> crate::Result<Option<TxOut>> {
+        client.get_txout(&self.fund_outpoint()?)
+    }
+
+    pub fn refund_action(&self) -> crate::Result<action::Refund> {
+        sign::refund_action(
+            &self.offer,
+            &self.wallet_outputs,
+            &self.SKs_self,
+            &self.PKs_other,
+            &self.redeemer_refund_sig,
+        )
+    }
+
+    /// Spend the fund output back into the refund address of the funder.
+    ///
+    /// The refund transaction is timelocked, so this will fail if called
+    /// before the expiry of the offer.
+    pub fn sweep(&self, wallet: &FunderWallet) -> crate::Result<Receipt> {
+        if self.scan(wallet)?.is_none() {
+            return Err(
+                RequestError::Refused("no unspent Bitcoin fund output to recover".into()).into(),
+            );
+        }
+
+        self.refund_action()?.execute(wallet)
+    }
+}
@@ -5,7 +5,9 @@ use crate::{
         Offer, PKs, SKs, WalletOutputs,
     },
     ecdsa,
+    error::BitcoinError,
     keypair::{self, PublicKey},
+    transcript::Transcript,
 };
 use ::bitcoin::{hashes::Hash, util::bip143::SighashComponents};
 use secp256k1zkp::{self, Message};
@@ -17,7 +19,7 @@ pub fn redeemer(
     wallet_outputs: &WalletOutputs,
     redeemer_SKs: &SKs,
     funder_PKs: &PKs,
-) -> anyhow::Result<secp256k1zkp::Signature> {
+) -> crate::Result<secp256k1zkp::Signature> {
     let (fund_transaction, fund_output_script) = fund_transaction(
         &offer,
         &wallet_outputs,
@@ -51,7 +53,8 @@ pub fn funder(
     redeemer_PKs: &PKs,
     Y: &PublicKey,
     redeemer_refund_signature: &secp256k1zkp::Signature,
-) -> anyhow::Result<(FunderActions, ecdsa::EncryptedSignature)> {
+    transcript: &Transcript,
+) -> crate::Result<(FunderActions, ecdsa::EncryptedSignature)> {
     let (fund_transaction, fund_output_script) = fund_transaction(
         &offer,
         &wallet_outputs,
@@ -63,33 +66,13 @@ pub fn funder(
         transaction: fund_transaction.clone(),
     };
 
-    let refund = {
-        let refund_transaction =
-            refund_transaction(&offer, &wallet_outputs, fund_transaction.txid());
-
-        let refund_digest = SighashComponents::new(&refund_transaction).sighash_all(
-            &refund_transaction.input[0],
-            &fund_output_script,
-            fund_transaction.output[0].value,
-        );
-        let refund_digest = Message::from_slice(&refund_digest.into_inner())
-            .expect("Should not fail because it is a hash");
-
-        if !keypair::verify_ecdsa(&refund_digest, &redeemer_refund_signature, &redeemer_PKs.X) {
-            return Err(anyhow::anyhow!(
-                "failed to verify redeemer's Bitcoin refund signature"
-            ));
-        }
-
-        let funder_refund_signature = funder_SKs.x.sign_ecdsa(&refund_digest);
-
-        action::Refund::new(
-            refund_transaction,
-            *redeemer_refund_signature,
-            funder_refund_signature,
-            fund_output_script.clone(),
-        )
-    };
+    let refund = refund_action(
+        &offer,
+        &wallet_outputs,
+        &funder_SKs,
+        &redeemer_PKs,
+        &redeemer_refund_signature,
+    )?;
 
     let encrypted_redeem_signature = {
         let redeem_transaction =
@@ -100,8 +83,51 @@ pub fn funder(
             fund_transaction.output[0].value,
         );
 
-        ecdsa::encsign(&funder_SKs.x, &Y, &redeem_digest)
+        ecdsa::encsign(&funder_SKs.x, &Y, &redeem_digest, transcript)
     };
 
     Ok((FunderActions { fund, refund }, encrypted_redeem_signature))
 }
+
+/// Build the funder's refund action, which only requires the redeemer's
+/// signature on the refund transaction on top of the parameters of the swap.
+///
+/// This allows the refund action to be rebuilt if it is ever lost.
+pub fn refund_action(
+    offer: &Offer,
+    wallet_outputs: &WalletOutputs,
+    funder_SKs: &SKs,
+    redeemer_PKs: &PKs,
+    redeemer_refund_signature: &secp256k1zkp::Signature,
+) -> crate::Result<action::Refund> {
+    let (fund_transaction, fund_output_script) = fund_transaction(
+        &offer,
+        &wallet_outputs,
+        &redeemer_PKs.X,
+        &funder_SKs.x.public_key,
+    )?;
+
+    let refund_transaction = refund_transaction(&offer, &wallet_outputs, fund_transaction.txid());
+
+    let refund_digest = SighashComponents::new(&refund_transaction).sighash_all(
+        &refund_transaction.input[0],
+        &fund_output_script,
+        fund_transaction.output[0].value,
+    );
+    let refund_digest = Message::from_slice(&refund_digest.into_inner())
+        .expect("Should not fail because it is a hash");
+
+    if !keypair::verify_ecdsa(&refund_digest, &redeemer_refund_signature, &redeemer_PKs.X) {
+        return Err(BitcoinError::InvalidRedeemerSignature("refund").into());
+    }
+
+    let funder_refund_signature = funder_SKs.x.sign_ecdsa(&refund_digest);
+
+    Ok(action::Refund::new(
+        refund_transaction,
+        *redeemer_refund_signature,
+        funder_refund_signature,
+        fund_output_script,
+        offer.fee,
+    ))
+}
@@ -12,7 +12,9 @@ pub fn fund_transaction(
     wallet_outputs: &WalletOutputs,
     redeemer_key: &PublicKey,
     funder_key: &PublicKey,
-) -> anyhow::Result<(Transaction, Script)> {
+) -> crate::Result<(Transaction, Script)> {
+    offer.check_dust(wallet_outputs.fund_input.txout.value)?;
+
     let fund_output_script = script::Builder::new()
         .push_int(2)
         .push_key(&::bitcoin::util::key::PublicKey {
@@ -27,6 +29,7 @@ pub fn fund_transaction(
         .push_opcode(opcodes::all::OP_CHECKMULTISIG)
         .into_script();
 
+    // The network is irrelevant here, since only the script pubkey is used
     let fund_output_addr = Address::p2wsh(&fund_output_script, Network::Regtest);
     let transaction = Transaction {
         input: vec![TxIn {
@@ -63,7 +66,8 @@ pub fn refund_transaction(
                 txid: fund_transaction_id,
                 vout: 0,
             },
-            sequence: 0xffff_ffff,
+            // The lock time is ignored if every input has the final sequence
+            sequence: 0xffff_fffe,
             witness: Vec::new(),
             script_sig: Script::new(),
         }],
@@ -76,6 +80,19 @@ pub fn refund_transaction(
     }
 }
 
+/// The refund transaction without its lock time, which can only be signed with
+/// the cooperation of the redeemer.
+pub fn cooperative_refund_transaction(
+    offer: &Offer,
+    wallet_outputs: &WalletOutputs,
+    fund_transaction_id: Hash,
+) -> Transaction {
+    Transaction {
+        lock_time: 0,
+        ..refund_transaction(offer, wallet_outputs, fund_transaction_id)
+    }
+}
+
 pub fn redeem_transaction(
     offer: &Offer,
     wallet_outputs: &WalletOutputs,
@@ -99,3 +116,72 @@ pub fn redeem_transaction(
         version: 2,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        bitcoin::{offer::LOCK_TIME_THRESHOLD, wallet::Output, ChainParams},
+        KeyPair,
+    };
+
+    // The finality rule of Bitcoin Core, which nodes apply before accepting a
+    // transaction into the mempool or a block
+    fn is_final(transaction: &Transaction, next_height: u64, median_time_past: u64) -> bool {
+        let lock_time = u64::from(transaction.lock_time);
+        let reached = if transaction.lock_time < LOCK_TIME_THRESHOLD {
+            next_height
+        } else {
+            median_time_past
+        };
+
+        lock_time == 0
+            || lock_time < reached
+            || transaction
+                .input
+                .iter()
+                .all(|input| input.sequence == 0xffff_ffff)
+    }
+
+    fn refund(expiry: u32) -> (Transaction, Transaction) {
+        let offer = Offer {
+            asset: 100_000_000,
+            fee: 1_000,
+            expiry,
+            chain: ChainParams::bitcoin_regtest(),
+        };
+        let keypair = KeyPair::new_random();
+        let fund_input = Output::new(keypair.clone(), OutPoint::null(), TxOut {
+            value: 200_000_000,
+            script_pubkey: keypair.to_bitcoin_address().script_pubkey(),
+        });
+        let wallet_outputs = WalletOutputs {
+            fund_input: fund_input.into(),
+            fund_change_address: KeyPair::new_random().to_bitcoin_address(),
+            redeem_address: KeyPair::new_random().to_bitcoin_address(),
+            refund_address: KeyPair::new_random().to_bitcoin_address(),
+        };
+
+        (
+            refund_transaction(&offer, &wallet_outputs, OutPoint::null().txid),
+            cooperative_refund_transaction(&offer, &wallet_outputs, OutPoint::null().txid),
+        )
+    }
+
+    #[test]
+    fn refund_is_rejected_before_expiry_height() {
+        let (refund, cooperative_refund) = refund(1_000);
+
+        assert!(!is_final(&refund, 1_000, 0));
+        assert!(is_final(&refund, 1_001, 0));
+        assert!(is_final(&cooperative_refund, 100, 0));
+    }
+
+    #[test]
+    fn refund_is_rejected_before_expiry_time() {
+        let (refund, _) = refund(1_600_000_000);
+
+        assert!(!is_final(&refund, 100, 1_600_000_000));
+        assert!(is_final(&refund, 100, 1_600_000_001));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     bitcoin::{event, Address, Client, OutPoint, Script, Signature, Transaction, TxOut},
+    error::{BitcoinError, WalletError},
     keypair::{verify_ecdsa, KeyPair, PublicKey, SECP},
     LookFor,
 };
@@ -13,16 +14,16 @@ use secp256k1zkp::Message;
 
 pub struct FunderWallet {
     url: String,
-    fund_input: Output,
+    fund_inputs: Vec<Output>,
     change_output_keypair: KeyPair,
     refund_output_keypair: KeyPair,
 }
 
 impl FunderWallet {
-    pub fn new(url: String, fund_input: Output) -> anyhow::Result<Self> {
+    pub fn new(url: String, fund_input: Output) -> crate::Result<Self> {
         Ok(Self {
             url,
-            fund_input,
+            fund_inputs: vec![fund_input],
             change_output_keypair: KeyPair::new_random(),
             refund_output_keypair: KeyPair::new_random(),
         })
@@ -36,26 +37,35 @@ impl FunderWallet {
         self.refund_output_keypair.to_bitcoin_address()
     }
 
+    /// The first output of the wallet, for processes running a single swap.
     pub fn fund_input(&self) -> Output {
-        self.fund_input.clone()
+        self.fund_inputs[0].clone()
     }
 
-    pub fn sign_input(&self, transaction: Transaction) -> anyhow::Result<Transaction> {
+    /// All the outputs the wallet can fund swaps with. Concurrent swaps must
+    /// each use a different one, see `manager::Manager`.
+    pub fn fund_inputs(&self) -> &[Output] {
+        &self.fund_inputs
+    }
+
+    pub fn add_fund_input(&mut self, fund_input: Output) {
+        self.fund_inputs.push(fund_input);
+    }
+
+    pub fn owned_output(&self, outpoint: &OutPoint) -> crate::Result<&Output> {
+        self.fund_inputs
+            .iter()
+            .find(|output| &output.outpoint == outpoint)
+            .ok_or_else(|| WalletError::NoFundingOutput.into())
+    }
+
+    pub fn sign_input(&self, transaction: Transaction) -> crate::Result<Transaction> {
         let mut completed_tx = transaction;
         let sighash_components = SighashComponents::new(&completed_tx);
 
         #[allow(clippy::toplevel_ref_arg)]
         for ref mut input in &mut completed_tx.input {
-            let wallet_input = self.fund_input();
-
-            let owned_output = if input.previous_output == wallet_input.outpoint {
-                &wallet_input
-            } else {
-                return Err(anyhow::anyhow!(
-                    "transaction input {:?} not owned by wallet",
-                    input
-                ));
-            };
+            let owned_output = self.owned_output(&input.previous_output)?;
 
             let fund_digest = {
                 let digest = sighash_components.sighash_all(
@@ -89,7 +99,7 @@ impl FunderWallet {
         &self,
         txid: sha256d::Hash,
         amount: u64,
-    ) -> anyhow::Result<bool> {
+    ) -> crate::Result<bool> {
         let transaction = self.get_rawtransaction(&txid)?;
 
         Ok(transaction.output[0].value == amount)
@@ -117,7 +127,7 @@ impl RedeemerWallet {
         &self,
         txid: sha256d::Hash,
         amount: u64,
-    ) -> anyhow::Result<bool> {
+    ) -> crate::Result<bool> {
         let transaction = self.get_rawtransaction(&txid)?;
 
         Ok(transaction.output[0].value == amount)
@@ -136,11 +146,10 @@ impl Client for RedeemerWallet {
     }
 }
 
-impl LookFor for FunderWallet {
-    type Event = event::Redeem;
+impl LookFor<event::Redeem> for FunderWallet {
     type Extract = crate::ecdsa::Signature;
 
-    fn look_for(&self, event: Self::Event) -> anyhow::Result<Self::Extract> {
+    fn look_for(&self, event: event::Redeem) -> crate::Result<Self::Extract> {
         let transaction = self.get_rawtransaction(&event.txid)?;
 
         // the redeem transaction contains 1 input
@@ -165,7 +174,11 @@ impl LookFor for FunderWallet {
                 }
             })
             .ok_or_else(|| {
-                anyhow::anyhow!("failed to find signature corresponding to redeemer's public key")
+                BitcoinError::Node(format!(
+                    "no signature of the funder in redeem transaction {}",
+                    event.txid
+                ))
+                .into()
             })
     }
 }
@@ -1,9 +1,35 @@
-use crate::bitcoin::{wallet, Address};
+use crate::bitcoin::{wallet, Address, OutPoint, TxOut};
 
 #[derive(Clone)]
 pub struct WalletOutputs {
-    pub fund_input: wallet::Output,
+    pub fund_input: FundInput,
     pub fund_change_address: Address,
     pub redeem_address: Address,
     pub refund_address: Address,
 }
+
+impl_wire!(WalletOutputs {
+    fund_input,
+    fund_change_address,
+    redeem_address,
+    refund_address,
+});
+
+/// The output the funder spends into the fund transaction. Only the funder's
+/// wallet holds its key, which is needed when executing the fund action.
+#[derive(Clone, Debug)]
+pub struct FundInput {
+    pub outpoint: OutPoint,
+    pub txout: TxOut,
+}
+
+impl_wire!(FundInput { outpoint, txout });
+
+impl From<wallet::Output> for FundInput {
+    fn from(from: wallet::Output) -> Self {
+        Self {
+            outpoint: from.outpoint,
+            txout: from.txout,
+        }
+    }
+}
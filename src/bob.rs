@@ -1,193 +1,413 @@
 use crate::{
-    bitcoin,
-    commit::Commitment,
-    grin::{self, bulletproof},
+    commit::{CoinTossingKeys, Commitment, Opening},
+    error::ProtocolError,
+    expiry::ExpiryValidator,
     keypair,
-    messages::{Message0, Message1, Message2, Message3, Message4},
+    ledger::{BobFunder0, BobFunder1, BobRedeemer0, BobRedeemer1},
+    messages::{
+        bob_initiates, Abort, Message0, Message1, Message2, Message3, Message4, Phase, ReasonCode,
+        SwapId,
+    },
+    negotiation::OfferPair,
+    transcript::Transcript,
 };
 use std::convert::TryInto;
 
-pub struct Bob0<AL, BL> {
+pub struct Bob0<AL, BL>
+where
+    AL: BobRedeemer0,
+    BL: BobFunder0,
+{
+    swap_id: SwapId,
     alpha_state: AL,
     beta_state: BL,
     alice_commitment: Commitment,
+    alice_bulletproof_round_1: (AL::BulletproofRound1, BL::BulletproofRound1),
+    transcript: Transcript,
 }
 
-impl Bob0<grin::BobRedeemer0, bitcoin::BobFunder0> {
+impl<AL, BL> Bob0<AL, BL>
+where
+    AL: BobRedeemer0,
+    BL: BobFunder0,
+    CoinTossingKeys: TryInto<AL::PKs, Error = crate::Error>,
+    CoinTossingKeys: TryInto<BL::PKs, Error = crate::Error>,
+{
     pub fn new(
-        offer_grin: grin::Offer,
-        outputs_grin: grin::SpecialOutputs,
-        output_keypairs_grin_redeemer: grin::SpecialOutputKeyPairsRedeemer,
-        offer_bitcoin: bitcoin::Offer,
-        outputs_bitcoin: bitcoin::WalletOutputs,
-        message: Message0,
-    ) -> anyhow::Result<(Self, Message1<grin::PKs, bitcoin::PKs>)> {
-        let grin_state = grin::bob::BobRedeemer0::new(
-            offer_grin,
-            outputs_grin,
-            output_keypairs_grin_redeemer,
-            message.bulletproof_round_1_alice,
-        )?;
-        let bitcoin_state = bitcoin::bob::BobFunder0::new(offer_bitcoin, outputs_bitcoin);
-
-        Ok((
-            Bob0 {
-                alpha_state: grin_state.clone(),
-                beta_state: bitcoin_state.clone(),
-                alice_commitment: message.commitment,
-            },
-            Message1 {
-                PKs_alpha: grin_state.clone().into(),
-                PKs_beta: bitcoin_state.into(),
-                bulletproof_round_1_bob: grin_state.bulletproof_round_1_self,
-            },
-        ))
+        swap_id: SwapId,
+        alpha_state: AL,
+        beta_state: BL,
+        expiry_validator: &ExpiryValidator,
+        message: Message0<AL::BulletproofRound1, BL::BulletproofRound1>,
+    ) -> crate::Result<(
+        Self,
+        Message1<AL::PKs, BL::PKs, AL::BulletproofRound1, BL::BulletproofRound1>,
+    )> {
+        swap_id.check(message.swap_id)?;
+        expiry_validator.validate(alpha_state.offer(), beta_state.offer())?;
+
+        let offers = OfferPair {
+            alpha: alpha_state.offer().clone(),
+            beta: beta_state.offer().clone(),
+        };
+
+        let mut transcript = Transcript::for_swap(&swap_id, &offers);
+        message.append_to(&mut transcript);
+
+        let message1 = Message1 {
+            swap_id,
+            PKs_alpha: alpha_state.public_keys(),
+            PKs_beta: beta_state.public_keys(),
+            bulletproof_round_1_bob: (
+                alpha_state.bulletproof_round_1(),
+                beta_state.bulletproof_round_1(),
+            ),
+        };
+
+        message1.append_to(&mut transcript);
+
+        let state = Bob0 {
+            swap_id,
+            alpha_state,
+            beta_state,
+            alice_commitment: message.commitment,
+            alice_bulletproof_round_1: message.bulletproof_round_1_alice,
+            transcript,
+        };
+
+        Ok((state, message1))
     }
 
     #[allow(clippy::type_complexity)]
     pub fn receive(
-        self,
-        Message2 {
-            opening,
-            beta_redeemer_sigs: alice_bitcoin_refund_signature,
-            ..
-        }: Message2<bitcoin::Signature>,
-    ) -> anyhow::Result<(
-        Bob1<grin::BobRedeemer1, bitcoin::BobFunder1>,
-        Message3<(grin::RedeemerSigs, grin::bulletproof::Round2), bitcoin::EncryptedSignature>,
+        mut self,
+        message: Message2<BL::RedeemerSigs>,
+    ) -> crate::Result<(
+        Bob1<AL::Next, BL::Next>,
+        Message3<AL::RedeemerSigs, BL::EncryptedSignature>,
     )> {
-        let (alice_PKs_grin, alice_PKs_bitcoin, mut Y) = opening.open(self.alice_commitment)?;
+        self.swap_id.check(message.swap_id)?;
 
-        let (grin_state, grin_redeemer_sigs, bulletproof_round_2_self) = self
-            .alpha_state
-            .transition(alice_PKs_grin.try_into()?, &mut Y)?;
-        let (bitcoin_state, bitcoin_redeem_encsig) = self.beta_state.transition(
-            alice_PKs_bitcoin.try_into()?,
-            alice_bitcoin_refund_signature,
+        let offers = OfferPair {
+            alpha: self.alpha_state.offer().clone(),
+            beta: self.beta_state.offer().clone(),
+        };
+
+        message.opening.append_to(&mut self.transcript);
+
+        let (alice_PKs_alpha, alice_PKs_beta, Y) = message.opening.open(
+            self.alice_commitment,
+            &Transcript::for_swap(&self.swap_id, &offers),
+        )?;
+        let mut Y =
+            Y.ok_or_else(|| ProtocolError::Malformed("Alice did not commit to Y".into()))?;
+
+        let (alice_alpha_round_1, alice_beta_round_1) = self.alice_bulletproof_round_1;
+        let (alpha_state, alpha_redeemer_sigs) = self.alpha_state.transition(
+            alice_PKs_alpha.try_into()?,
+            &mut Y,
+            alice_alpha_round_1,
+            &self.transcript,
+        )?;
+        let (beta_state, beta_redeem_encsig) = self.beta_state.transition(
+            alice_PKs_beta.try_into()?,
+            message.beta_redeemer_sigs,
             &Y,
+            alice_beta_round_1,
+            &self.transcript,
         )?;
 
         let state = Bob1 {
-            alpha_state: grin_state,
-            beta_state: bitcoin_state,
+            swap_id: self.swap_id,
+            alpha_state,
+            beta_state,
             Y,
         };
 
         let message = Message3 {
-            alpha_redeemer_sigs: (grin_redeemer_sigs, bulletproof_round_2_self),
-            beta_redeem_encsig: bitcoin_redeem_encsig,
+            swap_id: self.swap_id,
+            alpha_redeemer_sigs,
+            beta_redeem_encsig,
         };
 
         Ok((state, message))
     }
-}
 
-impl Bob0<bitcoin::BobRedeemer0, grin::BobFunder0> {
-    pub fn new(
-        offer_bitcoin: bitcoin::Offer,
-        outputs_bitcoin: bitcoin::WalletOutputs,
-        offer_grin: grin::Offer,
-        outputs_grin: grin::SpecialOutputs,
-        output_keypairs_grin_funder: grin::SpecialOutputKeyPairsFunder,
-        message: Message0,
-    ) -> anyhow::Result<(Self, Message1<bitcoin::PKs, grin::PKs>)> {
-        let bitcoin_state = bitcoin::bob::BobRedeemer0::new(offer_bitcoin, outputs_bitcoin);
-        let grin_state = grin::bob::BobFunder0::new(
-            offer_grin,
-            outputs_grin,
-            output_keypairs_grin_funder,
-            message.bulletproof_round_1_alice,
-        )?;
+    pub fn swap_id(&self) -> SwapId {
+        self.swap_id
+    }
 
-        Ok((
-            Bob0 {
-                alpha_state: bitcoin_state.clone(),
-                beta_state: grin_state.clone(),
-                alice_commitment: message.commitment,
-            },
-            Message1 {
-                PKs_alpha: bitcoin_state.into(),
-                PKs_beta: grin_state.clone().into(),
-                bulletproof_round_1_bob: grin_state.bulletproof_round_1_self,
-            },
-        ))
+    /// Give up on the swap, typically because the opening or Alice's
+    /// signatures failed to verify.
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::KeyExchange,
+        }
     }
 
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
+}
+
+pub struct Bob1<AL, BL> {
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    Y: keypair::PublicKey,
+}
+
+impl<AL, BL> Bob1<AL, BL>
+where
+    AL: BobRedeemer1,
+    BL: BobFunder1,
+{
     pub fn receive(
         self,
-        Message2 {
+        message: Message4<AL::EncryptedSignature>,
+    ) -> crate::Result<Bob2<AL::Next, BL::Next>> {
+        self.swap_id.check(message.swap_id)?;
+
+        let alpha_state = self
+            .alpha_state
+            .transition(&self.Y, message.alpha_redeem_encsig)?;
+        let beta_state = self.beta_state.transition()?;
+
+        Ok(Bob2 {
+            swap_id: self.swap_id,
+            alpha_state,
+            beta_state,
+        })
+    }
+
+    /// Give up on the swap. The state holds Bob's beta fund action, which is
+    /// dropped here so that he cannot fund after the swap is cancelled.
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::Signing,
+        }
+    }
+
+    /// Alice is not going to fund the alpha ledger, so Bob must not fund the
+    /// beta ledger either. Consuming the state drops his fund action.
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
+}
+
+pub struct Bob2<AL, BL> {
+    pub swap_id: SwapId,
+    pub alpha_state: AL,
+    pub beta_state: BL,
+}
+
+impl<AL, BL> Bob2<AL, BL> {
+    /// Decide not to fund the beta ledger, e.g. because Alice's fund
+    /// transaction did not confirm in time. Alice gets her alpha asset back
+    /// once her refund transaction becomes valid. Must not be called once
+    /// the beta fund action has been executed.
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::Funding,
+        }
+    }
+}
+
+/// Bob's side of the protocol when he sends the first message.
+pub struct BobInitiator0<AL, BL> {
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    opening: Opening,
+    transcript: Transcript,
+}
+
+impl<AL, BL> BobInitiator0<AL, BL>
+where
+    AL: BobRedeemer0,
+    BL: BobFunder0,
+    AL::PKs: Into<CoinTossingKeys>,
+    BL::PKs: Into<CoinTossingKeys>,
+    CoinTossingKeys: TryInto<AL::PKs, Error = crate::Error>,
+{
+    pub fn new(
+        swap_id: SwapId,
+        alpha_state: AL,
+        beta_state: BL,
+        expiry_validator: &ExpiryValidator,
+    ) -> crate::Result<(
+        Self,
+        bob_initiates::Message0<AL::BulletproofRound1, BL::BulletproofRound1>,
+    )> {
+        expiry_validator.validate(alpha_state.offer(), beta_state.offer())?;
+
+        let offers = OfferPair {
+            alpha: alpha_state.offer().clone(),
+            beta: beta_state.offer().clone(),
+        };
+
+        let PKs_alpha = alpha_state.public_keys().into();
+        let PKs_beta = beta_state.public_keys().into();
+
+        // The opening is kept as it is now, because Bob's keys may change when
+        // transitioning the redeemer's state
+        let mut transcript = Transcript::for_swap(&swap_id, &offers);
+        let commitment = Commitment::commit(&transcript, PKs_alpha.clone(), PKs_beta.clone(), None);
+        let opening = Opening::new(PKs_alpha, PKs_beta, None);
+
+        let message = bob_initiates::Message0 {
+            swap_id,
+            commitment,
+            bulletproof_round_1_bob: (
+                alpha_state.bulletproof_round_1(),
+                beta_state.bulletproof_round_1(),
+            ),
+        };
+        message.append_to(&mut transcript);
+
+        let state = BobInitiator0 {
+            swap_id,
+            alpha_state,
+            beta_state,
             opening,
-            beta_redeemer_sigs: alice_grin_redeemer_sigs,
-        }: Message2<(grin::RedeemerSigs, bulletproof::Round2)>,
-    ) -> anyhow::Result<(
-        Bob1<bitcoin::BobRedeemer1, grin::BobFunder1>,
-        Message3<bitcoin::Signature, grin::EncryptedSignature>,
+            transcript,
+        };
+
+        Ok((state, message))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn receive(
+        mut self,
+        message: bob_initiates::Message1<AL::BulletproofRound1, BL::BulletproofRound1>,
+    ) -> crate::Result<(
+        BobInitiator1<AL::Next, BL>,
+        bob_initiates::Message2<AL::RedeemerSigs>,
     )> {
-        let (alice_PKs_bitcoin, alice_PKs_grin, Y) = opening.open(self.alice_commitment)?;
+        self.swap_id.check(message.swap_id)?;
 
-        let (bitcoin_state, bitcoin_redeemer_refund_sig) =
-            self.alpha_state.transition(alice_PKs_bitcoin.try_into()?)?;
-        let (grin_state, grin_redeem_encsig) = self.beta_state.transition(
-            alice_PKs_grin.try_into()?,
-            alice_grin_redeemer_sigs.0,
-            &Y,
-            alice_grin_redeemer_sigs.1,
+        message.append_to(&mut self.transcript);
+        self.opening.append_to(&mut self.transcript);
+
+        let mut Y = message.Y;
+
+        let (alice_alpha_round_1, alice_beta_round_1) = message.bulletproof_round_1_alice;
+        let (alpha_state, alpha_redeemer_sigs) = self.alpha_state.transition(
+            message.PKs_alpha.try_into()?,
+            &mut Y,
+            alice_alpha_round_1,
+            &self.transcript,
         )?;
 
-        let state = Bob1 {
-            alpha_state: bitcoin_state,
-            beta_state: grin_state,
+        let state = BobInitiator1 {
+            swap_id: self.swap_id,
+            alpha_state,
+            beta_state: self.beta_state,
             Y,
+            alice_PKs_beta: message.PKs_beta,
+            alice_beta_bulletproof_round_1: alice_beta_round_1,
+            transcript: self.transcript,
         };
 
-        let message = Message3 {
-            alpha_redeemer_sigs: bitcoin_redeemer_refund_sig,
-            beta_redeem_encsig: grin_redeem_encsig,
+        let message = bob_initiates::Message2 {
+            swap_id: self.swap_id,
+            opening: self.opening,
+            alpha_redeemer_sigs,
         };
 
         Ok((state, message))
     }
+
+    pub fn swap_id(&self) -> SwapId {
+        self.swap_id
+    }
+
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::KeyExchange,
+        }
+    }
+
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
 }
 
-pub struct Bob1<AL, BL> {
+pub struct BobInitiator1<AL, BL>
+where
+    BL: BobFunder0,
+{
+    swap_id: SwapId,
     alpha_state: AL,
     beta_state: BL,
     Y: keypair::PublicKey,
+    alice_PKs_beta: CoinTossingKeys,
+    alice_beta_bulletproof_round_1: BL::BulletproofRound1,
+    transcript: Transcript,
 }
 
-impl Bob1<grin::BobRedeemer1, bitcoin::BobFunder1> {
+impl<AL, BL> BobInitiator1<AL, BL>
+where
+    AL: BobRedeemer1,
+    BL: BobFunder0,
+    CoinTossingKeys: TryInto<BL::PKs, Error = crate::Error>,
+{
+    #[allow(clippy::type_complexity)]
     pub fn receive(
         self,
-        message: Message4<grin::EncryptedSignature>,
-    ) -> anyhow::Result<Bob2<grin::BobRedeemer2, bitcoin::BobFunder2>> {
-        let grin_state = self
+        message: bob_initiates::Message3<AL::EncryptedSignature, BL::RedeemerSigs>,
+    ) -> crate::Result<(
+        Bob2<AL::Next, <BL::Next as BobFunder1>::Next>,
+        bob_initiates::Message4<BL::EncryptedSignature>,
+    )> {
+        self.swap_id.check(message.swap_id)?;
+
+        let alpha_state = self
             .alpha_state
-            .transition(self.Y, message.alpha_redeem_encsig)?;
-        let bitcoin_state = self.beta_state.transition()?;
+            .transition(&self.Y, message.alpha_redeem_encsig)?;
 
-        Ok(Bob2 {
-            alpha_state: grin_state,
-            beta_state: bitcoin_state,
-        })
-    }
-}
+        let (beta_state, beta_redeem_encsig) = self.beta_state.transition(
+            self.alice_PKs_beta.try_into()?,
+            message.beta_redeemer_sigs,
+            &self.Y,
+            self.alice_beta_bulletproof_round_1,
+            &self.transcript,
+        )?;
+        let beta_state = beta_state.transition()?;
 
-impl Bob1<bitcoin::BobRedeemer1, grin::BobFunder1> {
-    pub fn receive(
-        self,
-        message: Message4<bitcoin::EncryptedSignature>,
-    ) -> anyhow::Result<Bob2<bitcoin::BobRedeemer2, grin::BobFunder2>> {
-        let bitcoin_state = self.alpha_state.transition(message.alpha_redeem_encsig)?;
-        let grin_state = self.beta_state.transition()?;
+        let state = Bob2 {
+            swap_id: self.swap_id,
+            alpha_state,
+            beta_state,
+        };
 
-        Ok(Bob2 {
-            alpha_state: bitcoin_state,
-            beta_state: grin_state,
-        })
+        let message = bob_initiates::Message4 {
+            swap_id: self.swap_id,
+            beta_redeem_encsig,
+        };
+
+        Ok((state, message))
     }
-}
 
-pub struct Bob2<AL, BL> {
-    pub alpha_state: AL,
-    pub beta_state: BL,
+    /// Give up on the swap. Bob has not handed out his encrypted signature on
+    /// the beta ledger yet, so Alice will not fund.
+    pub fn abort(self, reason_code: ReasonCode) -> Abort {
+        Abort {
+            swap_id: self.swap_id,
+            reason_code,
+            phase: Phase::Signing,
+        }
+    }
+
+    pub fn receive_abort(self, abort: Abort) -> crate::Error {
+        abort.into()
+    }
 }
@@ -0,0 +1,462 @@
+//! An encrypted and authenticated channel between the two parties of a swap.
+//!
+//! The handshake follows the Noise XX pattern, with secp256k1 for the
+//! Diffie-Hellman operations, ChaCha20-Poly1305 as cipher and SHA-256 as hash:
+//!
+//! ```text
+//! -> e
+//! <- e, ee, s, es
+//! -> s, se
+//! ```
+//!
+//! Both parties learn each other's long-term identity key, which they must
+//! check against the one they expect, e.g. the maker's key in the quote. The
+//! protocol messages are then sent encrypted under the keys of the session.
+//!
+//! On top of that, the swap is bound to the session through its `SwapId`,
+//! which is derived from the handshake hash and included in the transcript of
+//! the swap. A man-in-the-middle relaying messages between two sessions ends up
+//! with different identifiers on each side and the opening of the commitment
+//! fails, so they cannot substitute their own keys for those of a party.
+
+use crate::{
+    error::{CryptoError, ProtocolError},
+    keypair::{KeyPair, PublicKey, SECP},
+    messages::SwapId,
+    wire::malformed,
+};
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+
+const PROTOCOL_NAME: &[u8] = b"Noise_XX_secp256k1_ChaChaPoly_SHA256";
+
+// Compressed secp256k1 public key
+const PUBLIC_KEY_LEN: usize = 33;
+const TAG_LEN: usize = 16;
+
+// Frames longer than this are rejected before allocating any memory for them
+const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// The party opening the connection, usually the taker.
+pub struct Initiator0 {
+    identity: KeyPair,
+    e: KeyPair,
+    symmetric: SymmetricState,
+}
+
+impl Initiator0 {
+    pub fn new(identity: KeyPair) -> (Self, Vec<u8>) {
+        let mut symmetric = SymmetricState::new();
+        let e = KeyPair::new_random();
+
+        let message = serialize(&e.public_key);
+        symmetric.mix_hash(&message);
+        // Empty payload, sent in the clear since there is no key yet
+        symmetric.mix_hash(&[]);
+
+        let state = Initiator0 {
+            identity,
+            e,
+            symmetric,
+        };
+
+        (state, message)
+    }
+
+    pub fn receive(mut self, message: &[u8]) -> crate::Result<(Session, Vec<u8>)> {
+        if message.len() != PUBLIC_KEY_LEN + PUBLIC_KEY_LEN + TAG_LEN + TAG_LEN {
+            return Err(malformed("handshake message has the wrong length"));
+        }
+        let (re, rest) = message.split_at(PUBLIC_KEY_LEN);
+        let (encrypted_rs, encrypted_payload) = rest.split_at(PUBLIC_KEY_LEN + TAG_LEN);
+
+        let re = deserialize(re)?;
+        self.symmetric.mix_hash(&serialize(&re));
+        self.symmetric.mix_key(&dh(&self.e, &re)?);
+
+        let rs = deserialize(&self.symmetric.decrypt_and_hash(encrypted_rs)?)?;
+        self.symmetric.mix_key(&dh(&self.e, &rs)?);
+        self.symmetric.decrypt_and_hash(encrypted_payload)?;
+
+        let mut message = self
+            .symmetric
+            .encrypt_and_hash(&serialize(&self.identity.public_key))?;
+        self.symmetric.mix_key(&dh(&self.identity, &re)?);
+        message.extend(self.symmetric.encrypt_and_hash(&[])?);
+
+        let (send_key, receive_key) = self.symmetric.split();
+        let session = Session::new(rs, self.symmetric.h, send_key, receive_key);
+
+        Ok((session, message))
+    }
+}
+
+/// The party accepting the connection, usually the maker.
+pub struct Responder0 {
+    e: KeyPair,
+    symmetric: SymmetricState,
+}
+
+impl Responder0 {
+    pub fn new(identity: KeyPair, message: &[u8]) -> crate::Result<(Self, Vec<u8>)> {
+        if message.len() != PUBLIC_KEY_LEN {
+            return Err(malformed("handshake message has the wrong length"));
+        }
+
+        let mut symmetric = SymmetricState::new();
+        let re = deserialize(message)?;
+        symmetric.mix_hash(&serialize(&re));
+        symmetric.mix_hash(&[]);
+
+        let e = KeyPair::new_random();
+        let mut response = serialize(&e.public_key);
+        symmetric.mix_hash(&response);
+        symmetric.mix_key(&dh(&e, &re)?);
+
+        response.extend(symmetric.encrypt_and_hash(&serialize(&identity.public_key))?);
+        symmetric.mix_key(&dh(&identity, &re)?);
+        response.extend(symmetric.encrypt_and_hash(&[])?);
+
+        let state = Responder0 { e, symmetric };
+
+        Ok((state, response))
+    }
+
+    pub fn receive(mut self, message: &[u8]) -> crate::Result<Session> {
+        if message.len() != PUBLIC_KEY_LEN + TAG_LEN + TAG_LEN {
+            return Err(malformed("handshake message has the wrong length"));
+        }
+        let (encrypted_rs, encrypted_payload) = message.split_at(PUBLIC_KEY_LEN + TAG_LEN);
+
+        let rs = deserialize(&self.symmetric.decrypt_and_hash(encrypted_rs)?)?;
+        self.symmetric.mix_key(&dh(&self.e, &rs)?);
+        self.symmetric.decrypt_and_hash(encrypted_payload)?;
+
+        let (initiator_key, responder_key) = self.symmetric.split();
+
+        Ok(Session::new(
+            rs,
+            self.symmetric.h,
+            responder_key,
+            initiator_key,
+        ))
+    }
+}
+
+/// The result of a successful handshake.
+pub struct Session {
+    remote_identity: PublicKey,
+    handshake_hash: [u8; 32],
+    send: CipherState,
+    receive: CipherState,
+}
+
+impl Session {
+    fn new(
+        remote_identity: PublicKey,
+        handshake_hash: [u8; 32],
+        send_key: [u8; 32],
+        receive_key: [u8; 32],
+    ) -> Self {
+        Self {
+            remote_identity,
+            handshake_hash,
+            send: CipherState::new(send_key),
+            receive: CipherState::new(receive_key),
+        }
+    }
+
+    /// The long-term identity key the peer proved ownership of.
+    pub fn remote_identity(&self) -> &PublicKey {
+        &self.remote_identity
+    }
+
+    pub fn expect_remote_identity(&self, identity: &PublicKey) -> crate::Result<()> {
+        if &self.remote_identity == identity {
+            Ok(())
+        } else {
+            Err(ProtocolError::UnexpectedIdentity.into())
+        }
+    }
+
+    pub fn handshake_hash(&self) -> [u8; 32] {
+        self.handshake_hash
+    }
+
+    /// The identifier of the swap run over this session. Both parties compute
+    /// the same one, so it does not need to be sent.
+    pub fn swap_id(&self) -> SwapId {
+        let mut hasher = Sha256::default();
+        hasher.input(b"swap_id");
+        hasher.input(&self.handshake_hash);
+
+        let mut swap_id = [0u8; 16];
+        swap_id.copy_from_slice(&hasher.result()[..16]);
+        SwapId(swap_id)
+    }
+
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> crate::Result<Vec<u8>> {
+        self.send.encrypt(&[], plaintext)
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> crate::Result<Vec<u8>> {
+        self.receive.decrypt(&[], ciphertext)
+    }
+
+    pub fn send<W: Write>(&mut self, writer: &mut W, plaintext: &[u8]) -> crate::Result<()> {
+        let ciphertext = self.encrypt(plaintext)?;
+        write_frame(writer, &ciphertext)
+    }
+
+    pub fn receive<R: Read>(&mut self, reader: &mut R) -> crate::Result<Vec<u8>> {
+        let ciphertext = read_frame(reader)?;
+        self.decrypt(&ciphertext)
+    }
+}
+
+/// Run the initiator's side of the handshake over a stream.
+pub fn connect<S: Read + Write>(stream: &mut S, identity: KeyPair) -> crate::Result<Session> {
+    let (state, message) = Initiator0::new(identity);
+    write_frame(stream, &message)?;
+
+    let response = read_frame(stream)?;
+    let (session, message) = state.receive(&response)?;
+    write_frame(stream, &message)?;
+
+    Ok(session)
+}
+
+/// Run the responder's side of the handshake over a stream.
+pub fn accept<S: Read + Write>(stream: &mut S, identity: KeyPair) -> crate::Result<Session> {
+    let message = read_frame(stream)?;
+    let (state, response) = Responder0::new(identity, &message)?;
+    write_frame(stream, &response)?;
+
+    let message = read_frame(stream)?;
+    state.receive(&message)
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, frame: &[u8]) -> crate::Result<()> {
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(malformed(format!(
+            "frame of {} bytes is too long",
+            frame.len()
+        )));
+    }
+
+    writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn read_frame<R: Read>(reader: &mut R) -> crate::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(malformed(format!("frame of {} bytes is too long", len)));
+    }
+
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame)?;
+
+    Ok(frame)
+}
+
+struct SymmetricState {
+    ck: [u8; 32],
+    h: [u8; 32],
+    cipher: Option<CipherState>,
+}
+
+impl SymmetricState {
+    fn new() -> Self {
+        let h = sha256(&[PROTOCOL_NAME]);
+
+        Self {
+            ck: h,
+            h,
+            cipher: None,
+        }
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.h = sha256(&[&self.h, data]);
+    }
+
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (ck, k) = hkdf(&self.ck, input_key_material);
+        self.ck = ck;
+        self.cipher = Some(CipherState::new(k));
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> crate::Result<Vec<u8>> {
+        let ciphertext = match &mut self.cipher {
+            Some(cipher) => cipher.encrypt(&self.h, plaintext)?,
+            None => plaintext.to_vec(),
+        };
+        self.mix_hash(&ciphertext);
+
+        Ok(ciphertext)
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> crate::Result<Vec<u8>> {
+        let plaintext = match &mut self.cipher {
+            Some(cipher) => cipher.decrypt(&self.h, ciphertext)?,
+            None => ciphertext.to_vec(),
+        };
+        self.mix_hash(ciphertext);
+
+        Ok(plaintext)
+    }
+
+    fn split(&self) -> ([u8; 32], [u8; 32]) {
+        hkdf(&self.ck, &[])
+    }
+}
+
+struct CipherState {
+    key: [u8; 32],
+    nonce: u64,
+}
+
+impl CipherState {
+    fn new(key: [u8; 32]) -> Self {
+        Self { key, nonce: 0 }
+    }
+
+    fn encrypt(&mut self, ad: &[u8], plaintext: &[u8]) -> crate::Result<Vec<u8>> {
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .encrypt(&self.next_nonce()?, Payload {
+                msg: plaintext,
+                aad: ad,
+            })
+            .map_err(|_| CryptoError::Calculate("ciphertext"))?;
+
+        Ok(ciphertext)
+    }
+
+    fn decrypt(&mut self, ad: &[u8], ciphertext: &[u8]) -> crate::Result<Vec<u8>> {
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .decrypt(&self.next_nonce()?, Payload {
+                msg: ciphertext,
+                aad: ad,
+            })
+            .map_err(|_| malformed("failed to decrypt message"))?;
+
+        Ok(plaintext)
+    }
+
+    // 32 bits of zeros followed by the little-endian counter
+    fn next_nonce(&mut self) -> crate::Result<Nonce> {
+        if self.nonce == u64::max_value() {
+            return Err(
+                CryptoError::Calculate("nonce, the session has sent too many messages").into(),
+            );
+        }
+
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce += 1;
+
+        Ok(*Nonce::from_slice(&nonce))
+    }
+}
+
+fn dh(keypair: &KeyPair, pk: &PublicKey) -> crate::Result<[u8; 32]> {
+    let mut shared = *pk;
+    shared.mul_assign(&*SECP, keypair.expose_secret_key())?;
+
+    Ok(sha256(&[&serialize(&shared)]))
+}
+
+fn hkdf(chaining_key: &[u8; 32], input_key_material: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut okm = [0u8; 64];
+    Hkdf::<Sha256>::new(Some(&chaining_key[..]), input_key_material)
+        .expand(&[], &mut okm)
+        .expect("64 bytes is a valid output length for SHA-256");
+
+    let mut first = [0u8; 32];
+    let mut second = [0u8; 32];
+    first.copy_from_slice(&okm[..32]);
+    second.copy_from_slice(&okm[32..]);
+
+    (first, second)
+}
+
+fn sha256(data: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    for item in data {
+        hasher.input(item);
+    }
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hasher.result());
+    hash
+}
+
+fn serialize(pk: &PublicKey) -> Vec<u8> {
+    pk.serialize_vec(&*SECP, true).to_vec()
+}
+
+fn deserialize(bytes: &[u8]) -> crate::Result<PublicKey> {
+    PublicKey::from_slice(&*SECP, bytes).map_err(malformed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn handshake(
+        initiator_identity: &KeyPair,
+        responder_identity: &KeyPair,
+    ) -> crate::Result<(Session, Session)> {
+        let (initiator, message0) = Initiator0::new(initiator_identity.clone());
+        let (responder, message1) = Responder0::new(responder_identity.clone(), &message0)?;
+        let (initiator_session, message2) = initiator.receive(&message1)?;
+        let responder_session = responder.receive(&message2)?;
+
+        Ok((initiator_session, responder_session))
+    }
+
+    #[test]
+    fn handshake_authenticates_both_parties() -> crate::Result<()> {
+        let initiator_identity = KeyPair::new_random();
+        let responder_identity = KeyPair::new_random();
+
+        let (mut initiator, mut responder) = handshake(&initiator_identity, &responder_identity)?;
+
+        initiator.expect_remote_identity(&responder_identity.public_key)?;
+        responder.expect_remote_identity(&initiator_identity.public_key)?;
+        assert_eq!(initiator.swap_id(), responder.swap_id());
+
+        let ciphertext = initiator.encrypt(b"message0")?;
+        assert_eq!(responder.decrypt(&ciphertext)?, b"message0".to_vec());
+
+        let ciphertext = responder.encrypt(b"message1")?;
+        assert_eq!(initiator.decrypt(&ciphertext)?, b"message1".to_vec());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_message_is_rejected() -> crate::Result<()> {
+        let (mut initiator, mut responder) =
+            handshake(&KeyPair::new_random(), &KeyPair::new_random())?;
+
+        let mut ciphertext = initiator.encrypt(b"message0")?;
+        ciphertext[0] ^= 1;
+
+        assert!(responder.decrypt(&ciphertext).is_err());
+
+        Ok(())
+    }
+}
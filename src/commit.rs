@@ -1,43 +1,84 @@
-use crate::PublicKey;
-use blake2::{Blake2b, Digest};
+use crate::{
+    error::ProtocolError,
+    transcript::Transcript,
+    wire::{take, Decode, Encode},
+    Hash, PublicKey,
+};
 
 pub struct Commitment([u8; 64]);
 
 pub struct Opening {
     PKs_alpha: Vec<PublicKey>,
     PKs_beta: Vec<PublicKey>,
-    Y: PublicKey,
+    Y: Option<PublicKey>,
 }
 
+impl_wire!(Opening {
+    PKs_alpha,
+    PKs_beta,
+    Y
+});
+
 pub type CoinTossingKeys = Vec<PublicKey>;
 
 impl Commitment {
+    /// Commit to the keys on top of the transcript, which includes the offers,
+    /// so that the opening fails if both parties did not agree on the same
+    /// offers. `Y` is only committed to if the committing party is Alice
     pub fn commit(
+        transcript: &Transcript,
         PKs_alpha: CoinTossingKeys,
         PKs_beta: CoinTossingKeys,
-        Y: &PublicKey,
+        Y: Option<&PublicKey>,
     ) -> Commitment {
-        let mut hasher = Blake2b::new();
+        let mut transcript = transcript.clone();
+        append_keys(&mut transcript, &PKs_alpha, &PKs_beta, Y);
 
-        for pk in PKs_alpha.iter() {
-            hasher.input(pk.0);
-        }
+        Commitment(transcript.challenge(b"commitment"))
+    }
+}
 
-        for pk in PKs_beta.iter() {
-            hasher.input(pk.0);
-        }
+impl Hash for Commitment {
+    fn hash(&self) -> [u8; 64] {
+        self.0
+    }
+}
 
-        hasher.input(Y.0);
+impl Encode for Commitment {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+}
 
+impl Decode for Commitment {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
         let mut commitment = [0u8; 64];
-        commitment.copy_from_slice(&hasher.result());
+        commitment.copy_from_slice(take(bytes, 64)?);
 
-        Commitment(commitment)
+        Ok(Commitment(commitment))
+    }
+}
+
+fn append_keys(
+    transcript: &mut Transcript,
+    PKs_alpha: &[PublicKey],
+    PKs_beta: &[PublicKey],
+    Y: Option<&PublicKey>,
+) {
+    transcript.append_public_keys(b"committed_PKs_alpha", PKs_alpha);
+    transcript.append_public_keys(b"committed_PKs_beta", PKs_beta);
+
+    if let Some(Y) = Y {
+        transcript.append_public_key(b"Y", Y);
     }
 }
 
 impl Opening {
-    pub fn new(PKs_alpha: CoinTossingKeys, PKs_beta: CoinTossingKeys, Y: PublicKey) -> Self {
+    pub fn new(
+        PKs_alpha: CoinTossingKeys,
+        PKs_beta: CoinTossingKeys,
+        Y: Option<PublicKey>,
+    ) -> Self {
         Opening {
             PKs_alpha,
             PKs_beta,
@@ -45,17 +86,28 @@ impl Opening {
         }
     }
 
+    /// Add the revealed keys to the transcript, in the same way as they were
+    /// committed to.
+    pub fn append_to(&self, transcript: &mut Transcript) {
+        append_keys(transcript, &self.PKs_alpha, &self.PKs_beta, self.Y.as_ref());
+    }
+
     pub fn open(
         self,
         commitment: Commitment,
-    ) -> anyhow::Result<(CoinTossingKeys, CoinTossingKeys, PublicKey)> {
-        let self_commitment =
-            Commitment::commit(self.PKs_alpha.clone(), self.PKs_beta.clone(), &self.Y);
+        transcript: &Transcript,
+    ) -> crate::Result<(CoinTossingKeys, CoinTossingKeys, Option<PublicKey>)> {
+        let self_commitment = Commitment::commit(
+            transcript,
+            self.PKs_alpha.clone(),
+            self.PKs_beta.clone(),
+            self.Y.as_ref(),
+        );
 
         if commitment.0[..] == self_commitment.0[..] {
             Ok((self.PKs_alpha, self.PKs_beta, self.Y))
         } else {
-            Err(anyhow::anyhow!("Opening does not match commitment"))
+            Err(ProtocolError::InvalidOpening.into())
         }
     }
 }
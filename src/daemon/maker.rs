@@ -0,0 +1,389 @@
+//! Quoting takers automatically.
+//!
+//! Every taker connecting to the maker gets a quote at the current price of
+//! the `PriceSource`, with the spread in favour of the maker. The quoted range
+//! of the alpha asset respects the per-swap bounds on both assets and what the
+//! daemon has left to fund with. The swap is then started in the daemon like
+//! one started through the API.
+
+use crate::{
+    bitcoin,
+    daemon::{
+        price::{FilePrice, FixedPrice, PriceSource},
+        Daemon, Event, SwapInfo, PEER_TIMEOUT,
+    },
+    error::{Recovery, RequestError, StorageError},
+    grin,
+    negotiation::{Party, Quote, Rate, Roles},
+    swap::{config::Config, setup, AnyQuote, Ledger, Peer},
+};
+use grin_core::consensus::GRIN_BASE;
+use serde::Deserialize;
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+// Basis points in a unit
+const BPS: u64 = 10_000;
+
+/// Amounts are in nanogrin and satoshis.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MakerConfig {
+    pub listen: String,
+    /// Ledger of the alpha asset of the quotes
+    pub alpha: Ledger,
+    pub alice: Party,
+    pub initiator: Party,
+    /// Satoshis per grin, if fixed
+    pub price: Option<u64>,
+    /// File to read the price from on every quote otherwise
+    pub price_file: Option<PathBuf>,
+    /// Added to the price when selling grin, taken off when buying it
+    pub spread_bps: u64,
+    #[serde(default = "default_alpha_expiry_hours")]
+    pub alpha_expiry_hours: u64,
+    #[serde(default = "default_beta_expiry_hours")]
+    pub beta_expiry_hours: u64,
+    // Bounds on the amounts of a single swap
+    pub min_grin: u64,
+    pub max_grin: u64,
+    pub min_bitcoin: u64,
+    pub max_bitcoin: u64,
+}
+
+fn default_alpha_expiry_hours() -> u64 {
+    48
+}
+
+fn default_beta_expiry_hours() -> u64 {
+    24
+}
+
+impl MakerConfig {
+    pub fn price_source(&self) -> crate::Result<Box<dyn PriceSource>> {
+        match (self.price, &self.price_file) {
+            (Some(price), None) => Ok(Box::new(FixedPrice(price))),
+            (None, Some(path)) => Ok(Box::new(FilePrice(path.clone()))),
+            _ => Err(StorageError::Config(
+                "exactly one of maker.price and maker.price_file must be set".into(),
+            )
+            .into()),
+        }
+    }
+
+    pub fn roles(&self) -> Roles {
+        Roles {
+            alice: self.alice,
+            initiator: self.initiator,
+        }
+    }
+
+    // Alice funds the alpha ledger and Bob the beta ledger
+    fn sells_grin(&self) -> bool {
+        (self.alpha == Ledger::Grin) == (self.alice == Party::Maker)
+    }
+
+    /// The rate of the quotes when one grin is worth `satoshis_per_grin`.
+    pub fn rate(&self, satoshis_per_grin: u64) -> crate::Result<Rate> {
+        if satoshis_per_grin == 0 {
+            return Err(StorageError::Config("price must not be zero".into()).into());
+        }
+        if self.spread_bps >= BPS {
+            return Err(StorageError::Config(format!("spread must be below {} bps", BPS)).into());
+        }
+
+        let factor = if self.sells_grin() {
+            BPS + self.spread_bps
+        } else {
+            BPS - self.spread_bps
+        };
+
+        // Both per BPS grin
+        let satoshis = u128::from(satoshis_per_grin) * u128::from(factor);
+        let nanogrin = u128::from(GRIN_BASE) * u128::from(BPS);
+
+        let (numerator, denominator) = match self.alpha {
+            Ledger::Grin => (satoshis, nanogrin),
+            Ledger::Bitcoin => (nanogrin, satoshis),
+        };
+
+        Ok(Rate {
+            numerator: u64::try_from(numerator)?,
+            denominator: u64::try_from(denominator)?,
+        })
+    }
+
+    /// The range of the alpha asset a taker may request at `rate`. `available`
+    /// is the most the maker can fund a swap with on its ledger, if known.
+    pub fn alpha_range(&self, rate: Rate, available: Option<u64>) -> crate::Result<(u64, u64)> {
+        let ((min_alpha, max_alpha), (min_beta, max_beta)) = match self.alpha {
+            Ledger::Grin => (
+                (self.min_grin, self.max_grin),
+                (self.min_bitcoin, self.max_bitcoin),
+            ),
+            Ledger::Bitcoin => (
+                (self.min_bitcoin, self.max_bitcoin),
+                (self.min_grin, self.max_grin),
+            ),
+        };
+
+        let min = min_alpha.max(min_alpha_for(rate, min_beta));
+        let mut max = max_alpha.min(max_alpha_for(rate, max_beta));
+        if let Some(available) = available {
+            max = max.min(if self.alice == Party::Maker {
+                available
+            } else {
+                max_alpha_for(rate, available)
+            });
+        }
+
+        if min > max {
+            return Err(RequestError::Refused(format!(
+                "cannot quote a swap of at least {} and at most {} of the alpha asset",
+                min, max
+            ))
+            .into());
+        }
+
+        Ok((min, max))
+    }
+}
+
+// The least alpha asset exchanged for at least `beta_asset`
+fn min_alpha_for(rate: Rate, beta_asset: u64) -> u64 {
+    if rate.numerator == 0 {
+        return if beta_asset == 0 { 0 } else { u64::max_value() };
+    }
+
+    let numerator = u128::from(rate.numerator);
+    let alpha = (u128::from(beta_asset) * u128::from(rate.denominator) + numerator - 1) / numerator;
+
+    u64::try_from(alpha).unwrap_or_else(|_| u64::max_value())
+}
+
+// The most alpha asset exchanged for at most `beta_asset`, as the rate rounds
+// down
+fn max_alpha_for(rate: Rate, beta_asset: u64) -> u64 {
+    if rate.numerator == 0 {
+        return u64::max_value();
+    }
+
+    let alpha = ((u128::from(beta_asset) + 1) * u128::from(rate.denominator)).saturating_sub(1)
+        / u128::from(rate.numerator);
+
+    u64::try_from(alpha).unwrap_or_else(|_| u64::max_value())
+}
+
+pub struct Maker {
+    daemon: Arc<Daemon>,
+    config: Config,
+    maker: MakerConfig,
+    price_source: Box<dyn PriceSource>,
+}
+
+impl Maker {
+    pub fn new(daemon: Arc<Daemon>, config: Config) -> crate::Result<Self> {
+        let maker = config
+            .maker
+            .clone()
+            .ok_or_else(|| StorageError::Config("no [maker] section".into()))?;
+        let price_source = maker.price_source()?;
+
+        Ok(Self {
+            daemon,
+            config,
+            maker,
+            price_source,
+        })
+    }
+
+    pub fn with_price_source(self, price_source: Box<dyn PriceSource>) -> Self {
+        Self {
+            price_source,
+            ..self
+        }
+    }
+
+    /// Quote every taker connecting to `listener` and run the swaps they take.
+    pub fn run(self, listener: TcpListener) -> crate::Result<()> {
+        let maker = Arc::new(self);
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let maker = maker.clone();
+
+            thread::spawn(move || {
+                // Takers which fail to connect are not worth reporting
+                let _ = maker.serve(stream);
+            });
+        }
+
+        Ok(())
+    }
+
+    fn serve(&self, stream: TcpStream) -> crate::Result<()> {
+        stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+        let mut peer = Peer::accept(stream, self.daemon.identity.clone())?
+            .with_history(self.daemon.history.clone());
+
+        if let Err(e) = self.make(&mut peer) {
+            self.daemon.notify(Event::Failed {
+                swap_id: peer.swap_id().to_string(),
+                error: e.to_string(),
+                recovery: Recovery::of(&e),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn make<S: Read + Write>(&self, peer: &mut Peer<S>) -> crate::Result<SwapInfo> {
+        let quote = self.quote()?;
+        let terms = setup::make(peer, &quote, &self.daemon.identity)?;
+
+        self.daemon.start(peer, &terms)
+    }
+
+    /// A quote at the current price and inventory.
+    pub fn quote(&self) -> crate::Result<AnyQuote> {
+        let rate = self.maker.rate(self.price_source.satoshis_per_grin()?)?;
+        let maker = self.daemon.identity.public_key;
+        let roles = self.maker.roles();
+
+        let quote = match self.maker.alpha {
+            Ledger::Grin => {
+                let alpha = self
+                    .config
+                    .grin_offer(&self.daemon.wallets, self.maker.alpha_expiry_hours)?;
+                let beta = self.config.bitcoin_offer(self.maker.beta_expiry_hours)?;
+                let (min_alpha_asset, max_alpha_asset) = self
+                    .maker
+                    .alpha_range(rate, self.available(&alpha, &beta))?;
+
+                AnyQuote::GrinBitcoin(Quote {
+                    alpha,
+                    beta,
+                    rate,
+                    min_alpha_asset,
+                    max_alpha_asset,
+                    maker,
+                    roles,
+                })
+            }
+            Ledger::Bitcoin => {
+                let alpha = self.config.bitcoin_offer(self.maker.alpha_expiry_hours)?;
+                let beta = self
+                    .config
+                    .grin_offer(&self.daemon.wallets, self.maker.beta_expiry_hours)?;
+                let (min_alpha_asset, max_alpha_asset) = self
+                    .maker
+                    .alpha_range(rate, self.available(&beta, &alpha))?;
+
+                AnyQuote::BitcoinGrin(Quote {
+                    alpha,
+                    beta,
+                    rate,
+                    min_alpha_asset,
+                    max_alpha_asset,
+                    maker,
+                    roles,
+                })
+            }
+        };
+
+        Ok(quote)
+    }
+
+    // The most the maker can swap on the ledger it funds. The offers do not
+    // have an asset yet, so what they require is only the fees.
+    fn available(&self, grin: &grin::Offer, bitcoin: &bitcoin::Offer) -> Option<u64> {
+        if self.maker.sells_grin() {
+            self.daemon
+                .grin_available()
+                .map(|available| available.saturating_sub(grin.fund_input_amount()))
+        } else {
+            Some(self.daemon.bitcoin_available().saturating_sub(bitcoin.fee))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(alpha: Ledger, alice: Party) -> MakerConfig {
+        MakerConfig {
+            listen: String::new(),
+            alpha,
+            alice,
+            initiator: Party::Maker,
+            price: Some(10_000),
+            price_file: None,
+            spread_bps: 100,
+            alpha_expiry_hours: 48,
+            beta_expiry_hours: 24,
+            min_grin: GRIN_BASE,
+            max_grin: 100 * GRIN_BASE,
+            min_bitcoin: 5_000,
+            max_bitcoin: 500_000,
+        }
+    }
+
+    #[test]
+    fn spread_is_in_favour_of_the_maker() -> crate::Result<()> {
+        // The maker funds grin on the alpha ledger
+        let selling = config(Ledger::Grin, Party::Maker).rate(10_000)?;
+        assert_eq!(selling.apply(GRIN_BASE)?, 10_100);
+
+        // The maker funds bitcoin on the beta ledger
+        let buying = config(Ledger::Grin, Party::Taker).rate(10_000)?;
+        assert_eq!(buying.apply(GRIN_BASE)?, 9_900);
+
+        // The maker funds bitcoin on the alpha ledger
+        let buying = config(Ledger::Bitcoin, Party::Maker).rate(10_000)?;
+        assert_eq!(buying.apply(9_900)?, GRIN_BASE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn alpha_range_respects_limits_of_both_assets() -> crate::Result<()> {
+        let config = config(Ledger::Grin, Party::Maker);
+        let rate = config.rate(10_000)?;
+
+        // 5_000 satoshis buy a bit less than half a grin, so the grin bound
+        // applies. 500_000 satoshis buy a bit less than 50 grin.
+        let (min, max) = config.alpha_range(rate, None)?;
+        assert_eq!(min, GRIN_BASE);
+        assert!(rate.apply(max)? <= 500_000);
+        assert!(rate.apply(max + 1)? > 500_000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn alpha_range_is_bounded_by_inventory() -> crate::Result<()> {
+        // The maker funds the alpha asset
+        let selling = config(Ledger::Grin, Party::Maker);
+        let rate = selling.rate(10_000)?;
+        assert_eq!(
+            selling.alpha_range(rate, Some(2 * GRIN_BASE))?,
+            (GRIN_BASE, 2 * GRIN_BASE)
+        );
+        assert!(selling.alpha_range(rate, Some(GRIN_BASE / 2)).is_err());
+
+        // The maker funds the beta asset
+        let buying = config(Ledger::Grin, Party::Taker);
+        let rate = buying.rate(10_000)?;
+        let (_, max) = buying.alpha_range(rate, Some(19_800))?;
+        assert_eq!(rate.apply(max)?, 19_800);
+        assert!(rate.apply(max + 1)? > 19_800);
+
+        Ok(())
+    }
+}
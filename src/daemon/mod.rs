@@ -0,0 +1,569 @@
+//! A long-running process owning the wallets and running many swaps at once.
+//!
+//! Swaps are started, watched and cancelled through the JSON-RPC API in `rpc`.
+//! Each swap is negotiated in the thread of the request which started it, and
+//! then executed by a thread of its own until it is over. Swaps which were not
+//! over when the daemon stopped are resumed when it starts again.
+//!
+//! With a `maker::Maker`, the daemon also quotes takers connecting to it and
+//! runs the swaps they take.
+//!
+//! The messages exchanged with peers and the steps of every swap are recorded
+//! in the history of the swap directory, which the API can query. With a
+//! watchtower, the refund of every running swap is deposited with it until the
+//! swap is over.
+//!
+//! The `Manager` keeps the execution of every running swap and reserves a
+//! different Bitcoin output for each swap funding on Bitcoin. The reservation
+//! ends with the swap, so outputs spent by a swap have to be removed from the
+//! configuration before the next restart. Grin is reserved against the
+//! configured inventory or the balance of the wallet, whichever is known and
+//! lower. If neither is known, the wallet rejects the invoices it cannot pay.
+
+use crate::{
+    bitcoin::wallet::Output,
+    error::{ProtocolError, Recovery, RequestError},
+    history::{self, Entry, History, Query, Record},
+    keypair::PublicKey,
+    manager::Manager,
+    messages::SwapId,
+    negotiation::{Party, Roles},
+    swap::{
+        config::parse_public_key, execution::Step, key_exchange, setup, Execution, Ledger, Offers,
+        Peer, Role, Status, SwapFile, Terms, Wallets,
+    },
+    watchtower, KeyPair,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+pub mod maker;
+pub mod price;
+pub mod rpc;
+
+// How long the peer may take to answer during the negotiation
+const PEER_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct Daemon {
+    identity: KeyPair,
+    wallets: Wallets,
+    swap_dir: PathBuf,
+    history: History,
+    poll_interval: Duration,
+    // Most grin committed to unfinished swaps at once
+    grin_inventory: Option<u64>,
+    manager: Manager<Running>,
+    watchtower: Option<watchtower::Client>,
+    subscribers: Mutex<Vec<Sender<Event>>>,
+}
+
+// A swap past its key exchange, saved in the swap directory
+struct Running {
+    file: SwapFile,
+    execution: Execution,
+}
+
+/// What the API tells about a swap. Unlike the `SwapFile` it leaves out the
+/// execution, which holds secret keys.
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapInfo {
+    pub swap_id: String,
+    pub role: Role,
+    pub alpha_ledger: Ledger,
+    pub alpha_asset: u64,
+    pub beta_ledger: Ledger,
+    pub beta_asset: u64,
+    pub peer: String,
+    pub status: Status,
+    pub updated_at: u64,
+}
+
+impl From<&SwapFile> for SwapInfo {
+    fn from(file: &SwapFile) -> Self {
+        Self {
+            swap_id: file.swap_id.clone(),
+            role: file.role,
+            alpha_ledger: file.alpha_ledger,
+            alpha_asset: file.alpha_asset,
+            beta_ledger: file.beta_ledger,
+            beta_asset: file.beta_asset,
+            peer: file.peer.clone(),
+            status: file.status,
+            updated_at: file.updated_at,
+        }
+    }
+}
+
+/// Take the quote of a maker.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StartSwap {
+    pub address: String,
+    /// Identity key of the maker, hex encoded
+    pub maker: String,
+    pub alpha_asset: u64,
+    /// The offers are declined unless they pay us at least `min_receive` on
+    /// the `receive` ledger.
+    pub receive: Ledger,
+    pub min_receive: u64,
+}
+
+/// Sent to the subscribers whenever a swap changes status, or fails to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    StatusChanged {
+        swap_id: String,
+        status: Status,
+    },
+    // A running swap is retried after the poll interval, one which was being
+    // negotiated is dropped. `recovery` is null if the cause is unknown
+    Failed {
+        swap_id: String,
+        error: String,
+        recovery: Option<Recovery>,
+    },
+}
+
+impl Daemon {
+    pub fn new(
+        identity: KeyPair,
+        wallets: Wallets,
+        swap_dir: PathBuf,
+        poll_interval: Duration,
+        grin_inventory: Option<u64>,
+        watchtower: Option<watchtower::Client>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            identity,
+            wallets,
+            history: history::in_swap_dir(&swap_dir),
+            swap_dir,
+            poll_interval,
+            grin_inventory,
+            manager: Manager::new(),
+            watchtower,
+            subscribers: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn identity(&self) -> &PublicKey {
+        &self.identity.public_key
+    }
+
+    /// Continue every saved swap which is not over yet.
+    pub fn resume(self: &Arc<Self>) -> crate::Result<()> {
+        for file in SwapFile::list(&self.swap_dir)? {
+            if file.status.is_final() {
+                continue;
+            }
+
+            let swap_id = file.swap_id.parse()?;
+            let execution = file.execution()?;
+            self.deposit_refund(swap_id, &execution);
+            self.manager.insert(swap_id, Running { file, execution })?;
+            self.spawn(swap_id);
+        }
+
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .expect("subscribers lock poisoned")
+            .push(sender);
+
+        receiver
+    }
+
+    pub fn list_swaps(&self) -> crate::Result<Vec<SwapInfo>> {
+        Ok(SwapFile::list(&self.swap_dir)?
+            .iter()
+            .map(SwapInfo::from)
+            .collect())
+    }
+
+    pub fn get_swap(&self, swap_id: &str) -> crate::Result<SwapInfo> {
+        let swap_id = swap_id.parse::<SwapId>()?;
+
+        Ok(SwapInfo::from(&SwapFile::load(
+            &self.swap_dir,
+            &swap_id.to_string(),
+        )?))
+    }
+
+    /// Everything recorded about a swap, oldest first.
+    pub fn get_history(&self, swap_id: &str) -> crate::Result<Vec<Record>> {
+        let swap_id = swap_id.parse::<SwapId>()?;
+
+        self.history.query(&Query {
+            swap_id: Some(swap_id.to_string()),
+            ..Query::default()
+        })
+    }
+
+    /// Negotiate a swap with a maker and execute it in the background once the
+    /// keys are exchanged.
+    pub fn start_swap(self: &Arc<Self>, request: StartSwap) -> crate::Result<SwapInfo> {
+        let maker = parse_public_key(&request.maker).map_err(|_| {
+            RequestError::Invalid(format!("maker {} is not a public key", request.maker))
+        })?;
+
+        let stream = TcpStream::connect(&request.address)?;
+        stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+        let mut peer =
+            Peer::connect(stream, self.identity.clone())?.with_history(self.history.clone());
+        if peer.remote_identity() != &maker {
+            return Err(ProtocolError::UnexpectedIdentity.into());
+        }
+
+        let terms = setup::take(
+            &mut peer,
+            request.alpha_asset,
+            &self.identity,
+            |offers, roles| check_receive(&request, offers, roles),
+        )?;
+
+        self.start(&mut peer, &terms)
+    }
+
+    /// Run the key exchange of a negotiated swap, and execute the swap in the
+    /// background if it succeeds.
+    fn start<S>(self: &Arc<Self>, peer: &mut Peer<S>, terms: &Terms) -> crate::Result<SwapInfo>
+    where
+        S: Read + Write,
+    {
+        let swap_id = peer.swap_id();
+        self.manager.insert_taken(swap_id)?;
+
+        let running = match self.exchange_keys(peer, terms) {
+            Ok(running) => running,
+            Err(e) => {
+                self.manager.remove(swap_id);
+                return Err(e);
+            }
+        };
+
+        let info = SwapInfo::from(&running.file);
+        self.deposit_refund(swap_id, &running.execution);
+        self.manager.put(swap_id, running)?;
+        self.notify(Event::StatusChanged {
+            swap_id: info.swap_id.clone(),
+            status: info.status,
+        });
+        self.spawn(swap_id);
+
+        Ok(info)
+    }
+
+    fn exchange_keys<S>(&self, peer: &mut Peer<S>, terms: &Terms) -> crate::Result<Running>
+    where
+        S: Read + Write,
+    {
+        let swap_id = peer.swap_id();
+
+        let bitcoin_fund_input = self.reserve(swap_id, terms)?;
+        let outputs = setup::exchange_outputs(peer, terms, &self.wallets, bitcoin_fund_input)?;
+        let execution = key_exchange::run(peer, terms, outputs, &self.wallets)?;
+
+        let file = SwapFile::new(swap_id, terms, peer.remote_identity(), &execution);
+        file.save(&self.swap_dir)?;
+        self.history.append(&file.swap_id, Entry::StatusChanged {
+            status: file.status,
+        })?;
+
+        Ok(Running { file, execution })
+    }
+
+    // Returns the Bitcoin output to fund from, if we fund on Bitcoin
+    fn reserve(&self, swap_id: SwapId, terms: &Terms) -> crate::Result<Option<Output>> {
+        if terms.funds_grin() {
+            if let Some(inventory) = self.grin_inventory() {
+                self.manager.reserve_grin_amount_within(
+                    swap_id,
+                    terms.offers.grin().fund_input_amount(),
+                    inventory,
+                )?;
+            }
+
+            return Ok(None);
+        }
+
+        let offer = terms.offers.bitcoin();
+        self.manager
+            .reserve_bitcoin_input(
+                swap_id,
+                self.wallets.bitcoin_funder()?,
+                offer.asset + offer.fee,
+            )
+            .map(Some)
+    }
+
+    fn grin_inventory(&self) -> Option<u64> {
+        let balance = self.wallets.grin.get_balance().ok();
+
+        match (self.grin_inventory, balance) {
+            (Some(inventory), Some(balance)) => Some(inventory.min(balance)),
+            (inventory, balance) => inventory.or(balance),
+        }
+    }
+
+    /// The grin which is not reserved by any swap, if the inventory is known.
+    pub fn grin_available(&self) -> Option<u64> {
+        self.grin_inventory()
+            .map(|inventory| inventory.saturating_sub(self.manager.reserved_grin_amount()))
+    }
+
+    /// The most a single swap can fund on Bitcoin, since each swap spends a
+    /// single output.
+    pub fn bitcoin_available(&self) -> u64 {
+        self.wallets
+            .bitcoin_funder
+            .as_ref()
+            .and_then(|wallet| {
+                self.manager
+                    .unreserved_bitcoin_inputs(wallet)
+                    .iter()
+                    .map(|output| output.txout.value)
+                    .max()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Give up on a swap which is not funded yet.
+    pub fn abort(&self, swap_id: &str) -> crate::Result<SwapInfo> {
+        self.with_running(swap_id, |running| {
+            if running.file.status != Status::KeysExchanged {
+                return Err(RequestError::Refused(format!(
+                    "swap is already {:?}, only a refund is possible",
+                    running.file.status
+                ))
+                .into());
+            }
+
+            Ok(Step {
+                status: Status::Aborted,
+                entries: Vec::new(),
+            })
+        })
+    }
+
+    /// Refund a swap without waiting for the peer any longer, once the refund
+    /// lock is reached. A swap which is not funded yet is aborted instead.
+    pub fn force_refund(&self, swap_id: &str) -> crate::Result<SwapInfo> {
+        self.with_running(swap_id, |running| {
+            running.execution.refund(running.file.status, &self.wallets)
+        })
+    }
+
+    // The step returned by `f` is saved, and the thread of the swap stops at
+    // its next step if the status is final
+    fn with_running(
+        &self,
+        swap_id: &str,
+        f: impl FnOnce(&Running) -> crate::Result<Step>,
+    ) -> crate::Result<SwapInfo> {
+        let swap_id = swap_id.parse::<SwapId>()?;
+        let mut running = self.manager.take(swap_id).map_err(|e| match e {
+            crate::Error::Request(RequestError::Refused(reason)) => {
+                RequestError::Refused(format!("{}, try again later", reason)).into()
+            }
+            e => e,
+        })?;
+
+        let result = f(&running).and_then(|step| self.set_status(&mut running, step));
+        let info = SwapInfo::from(&running.file);
+        self.manager.put(swap_id, running)?;
+
+        result.map(|_| info)
+    }
+
+    fn set_status(&self, running: &mut Running, step: Step) -> crate::Result<()> {
+        let status = step.status;
+        running.file.set_status(status);
+        self.notify(Event::StatusChanged {
+            swap_id: running.file.swap_id.clone(),
+            status,
+        });
+
+        // The status is what resuming depends on, so it is saved first
+        running.file.save(&self.swap_dir)?;
+        self.history
+            .append_all(&running.file.swap_id, step.entries)?;
+        self.history
+            .append(&running.file.swap_id, Entry::StatusChanged { status })
+    }
+
+    fn spawn(self: &Arc<Self>, swap_id: SwapId) {
+        let daemon = self.clone();
+        thread::spawn(move || daemon.execute(swap_id));
+    }
+
+    // Step through a swap until it is over. The execution is put back into the
+    // manager between steps so that the API can abort or refund it.
+    fn execute(&self, swap_id: SwapId) {
+        loop {
+            let mut running = match self.manager.take(swap_id) {
+                Ok(running) => running,
+                // Being aborted or refunded through the API
+                Err(_) => {
+                    thread::sleep(self.poll_interval);
+                    continue;
+                }
+            };
+
+            let status = running.file.status;
+            if status.is_final() {
+                self.manager.remove(swap_id);
+                self.cancel_refund(swap_id);
+                return;
+            }
+
+            let result = running
+                .execution
+                .step(status, &self.wallets)
+                .and_then(|step| {
+                    let next = step.status;
+                    if next != status {
+                        self.set_status(&mut running, step)?;
+                    }
+
+                    Ok(next)
+                });
+            self.manager
+                .put(swap_id, running)
+                .expect("the swap was taken above");
+
+            match result {
+                // Take the next step right away
+                Ok(next) if next != status => {}
+                Ok(_) => thread::sleep(self.poll_interval),
+                Err(e) => {
+                    self.notify(Event::Failed {
+                        swap_id: swap_id.to_string(),
+                        error: e.to_string(),
+                        recovery: Recovery::of(&e),
+                    });
+                    thread::sleep(self.poll_interval);
+                }
+            }
+        }
+    }
+
+    // The swap runs without the watchtower if it cannot be reached
+    fn deposit_refund(&self, swap_id: SwapId, execution: &Execution) {
+        if let Some(watchtower) = &self.watchtower {
+            if let Err(e) = watchtower.deposit(swap_id, execution.refund_package()) {
+                self.notify(Event::Failed {
+                    swap_id: swap_id.to_string(),
+                    error: format!("failed to deposit refund with watchtower: {}", e),
+                    recovery: Recovery::of(&e),
+                });
+            }
+        }
+    }
+
+    fn cancel_refund(&self, swap_id: SwapId) {
+        if let Some(watchtower) = &self.watchtower {
+            if let Err(e) = watchtower.cancel(swap_id) {
+                self.notify(Event::Failed {
+                    swap_id: swap_id.to_string(),
+                    error: format!("failed to cancel refund with watchtower: {}", e),
+                    recovery: Recovery::of(&e),
+                });
+            }
+        }
+    }
+
+    fn notify(&self, event: Event) {
+        self.subscribers
+            .lock()
+            .expect("subscribers lock poisoned")
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+// Alice receives the beta asset and Bob the alpha asset
+fn check_receive(request: &StartSwap, offers: &Offers, roles: Roles) -> crate::Result<()> {
+    let (ledger, amount) = if roles.is_alice(Party::Taker) {
+        offers.beta()
+    } else {
+        offers.alpha()
+    };
+
+    if ledger != request.receive || amount < request.min_receive {
+        return Err(ProtocolError::InvalidOffer(format!(
+            "pays {} on {:?} instead of at least {} on {:?}",
+            amount, ledger, request.min_receive, request.receive
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::swap::execution::test::{bob_funds_grin, wallets, GRIN_HEIGHT};
+    use std::fs;
+
+    // A daemon running a single swap at `status`, whose thread is not started
+    fn daemon_with_swap(status: Status) -> crate::Result<(Arc<Daemon>, String)> {
+        let (terms, execution) = bob_funds_grin()?;
+        let (wallets, _) = wallets(GRIN_HEIGHT);
+        let swap_dir = std::env::temp_dir().join(format!("daemon-{}", SwapId::random()));
+        let daemon = Daemon::new(
+            KeyPair::new_random(),
+            wallets,
+            swap_dir,
+            Duration::from_secs(60),
+            None,
+            None,
+        );
+
+        let swap_id = SwapId::random();
+        let peer = KeyPair::new_random().public_key;
+        let mut file = SwapFile::new(swap_id, &terms, &peer, &execution);
+        file.set_status(status);
+        file.save(&daemon.swap_dir)?;
+        daemon
+            .manager
+            .insert(swap_id, Running { file, execution })?;
+
+        Ok((daemon, swap_id.to_string()))
+    }
+
+    #[test]
+    fn abort_before_funding() -> crate::Result<()> {
+        let (daemon, swap_id) = daemon_with_swap(Status::KeysExchanged)?;
+
+        assert_eq!(daemon.abort(&swap_id)?.status, Status::Aborted);
+        assert_eq!(daemon.get_swap(&swap_id)?.status, Status::Aborted);
+
+        fs::remove_dir_all(&daemon.swap_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn abort_is_rejected_after_funding() -> crate::Result<()> {
+        let (daemon, swap_id) = daemon_with_swap(Status::Funded)?;
+
+        assert!(daemon.abort(&swap_id).is_err());
+        assert_eq!(daemon.get_swap(&swap_id)?.status, Status::Funded);
+
+        fs::remove_dir_all(&daemon.swap_dir)?;
+        Ok(())
+    }
+}
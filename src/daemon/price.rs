@@ -0,0 +1,32 @@
+use crate::error::{Context, StorageError};
+use std::{fs, path::PathBuf};
+
+/// Where the maker gets the BTC/GRIN price its quotes are based on.
+pub trait PriceSource: Send + Sync {
+    /// The price of one grin in satoshis.
+    fn satoshis_per_grin(&self) -> crate::Result<u64>;
+}
+
+pub struct FixedPrice(pub u64);
+
+impl PriceSource for FixedPrice {
+    fn satoshis_per_grin(&self) -> crate::Result<u64> {
+        Ok(self.0)
+    }
+}
+
+/// Reads the price from a file on every quote, so that it can be updated by
+/// another process. The file holds the price as a decimal integer.
+pub struct FilePrice(pub PathBuf);
+
+impl PriceSource for FilePrice {
+    fn satoshis_per_grin(&self) -> crate::Result<u64> {
+        let price = fs::read_to_string(&self.0)
+            .with_context(|| format!("failed to read price from {:?}", self.0))?;
+
+        price
+            .trim()
+            .parse()
+            .map_err(|e| StorageError::Config(format!("price in {:?}: {}", self.0, e)).into())
+    }
+}
@@ -0,0 +1,359 @@
+//! JSON-RPC 2.0 over TCP, one request or response per line.
+//!
+//! Methods:
+//! - `list_swaps`: all saved swaps, oldest update first
+//! - `get_swap {swap_id}`
+//! - `get_history {swap_id}`: every record of the swap in the history, oldest
+//!   first
+//! - `start_swap {address, maker, alpha_asset, receive, min_receive}`: take the
+//!   quote of a maker, returning once the keys are exchanged
+//! - `abort {swap_id}`: give up on a swap which is not funded yet
+//! - `force_refund {swap_id}`: refund without waiting for the peer any longer
+//! - `subscribe`: receive every `Event` of the daemon on this connection, as
+//!   `swap_event` notifications
+//!
+//! Failed calls carry `data.recovery`, one of `retry`, `abort_swap` or
+//! `blame_peer`, when the cause of the failure is known.
+//!
+//! The API can start and refund swaps with the funds of the daemon, so it must
+//! only be reachable by the operator. `bind` refuses addresses other than
+//! loopback unless remote access is explicitly allowed, for a private network.
+
+use crate::{
+    daemon::{Daemon, Event},
+    error::{Recovery, StorageError},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Reserved for implementation-defined server errors
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    // Absent for notifications, which are not answered
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+}
+
+#[derive(Serialize)]
+struct Notification<'a> {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: &'a Event,
+}
+
+#[derive(Serialize)]
+struct Error {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<ErrorData>,
+}
+
+// Tells the client whether to retry the request
+#[derive(Serialize)]
+struct ErrorData {
+    recovery: Recovery,
+}
+
+impl From<crate::Error> for Error {
+    fn from(error: crate::Error) -> Self {
+        Self {
+            code: SERVER_ERROR,
+            message: error.to_string(),
+            data: Recovery::of(&error).map(|recovery| ErrorData { recovery }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SwapIdParams {
+    swap_id: String,
+}
+
+/// Listen for API connections on `address`, which must be a loopback address
+/// unless `allow_remote` is set.
+pub fn bind(address: &str, allow_remote: bool) -> crate::Result<TcpListener> {
+    if !allow_remote {
+        for socket_address in address.to_socket_addrs()? {
+            if !socket_address.ip().is_loopback() {
+                return Err(StorageError::Config(format!(
+                    "refusing to serve the API on {}, which is not a loopback address, \
+                     without rpc_allow_remote",
+                    socket_address
+                ))
+                .into());
+            }
+        }
+    }
+
+    Ok(TcpListener::bind(address)?)
+}
+
+/// Answer requests on `listener` until it fails, one thread per connection.
+pub fn serve(daemon: Arc<Daemon>, listener: TcpListener) -> crate::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let daemon = daemon.clone();
+
+        thread::spawn(move || {
+            // The connection is dropped, nobody is left to tell
+            let _ = Connection::new(daemon, stream).and_then(Connection::run);
+        });
+    }
+
+    Ok(())
+}
+
+struct Connection {
+    daemon: Arc<Daemon>,
+    reader: BufReader<TcpStream>,
+    // Shared with the thread forwarding events once subscribed
+    writer: Arc<Mutex<TcpStream>>,
+    subscribed: bool,
+}
+
+impl Connection {
+    fn new(daemon: Arc<Daemon>, stream: TcpStream) -> crate::Result<Self> {
+        Ok(Self {
+            daemon,
+            writer: Arc::new(Mutex::new(stream.try_clone()?)),
+            reader: BufReader::new(stream),
+            subscribed: false,
+        })
+    }
+
+    fn run(mut self) -> crate::Result<()> {
+        let mut line = String::new();
+
+        while self.reader.read_line(&mut line)? > 0 {
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => {
+                    let result = self.call(&request.method, request.params);
+                    request.id.map(|id| Response::new(id, result))
+                }
+                Err(e) => Some(Response::new(
+                    Value::Null,
+                    Err(Error {
+                        code: PARSE_ERROR,
+                        message: e.to_string(),
+                        data: None,
+                    }),
+                )),
+            };
+
+            if let Some(response) = response {
+                write_line(&self.writer, &response)?;
+            }
+            line.clear();
+        }
+
+        Ok(())
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, Error> {
+        let result = match method {
+            "list_swaps" => to_value(self.daemon.list_swaps()?),
+            "get_swap" => {
+                let params = parse_params::<SwapIdParams>(params)?;
+                to_value(self.daemon.get_swap(&params.swap_id)?)
+            }
+            "get_history" => {
+                let params = parse_params::<SwapIdParams>(params)?;
+                to_value(self.daemon.get_history(&params.swap_id)?)
+            }
+            "start_swap" => to_value(self.daemon.start_swap(parse_params(params)?)?),
+            "abort" => {
+                let params = parse_params::<SwapIdParams>(params)?;
+                to_value(self.daemon.abort(&params.swap_id)?)
+            }
+            "force_refund" => {
+                let params = parse_params::<SwapIdParams>(params)?;
+                to_value(self.daemon.force_refund(&params.swap_id)?)
+            }
+            "subscribe" => {
+                self.subscribe();
+                Value::Bool(true)
+            }
+            _ => {
+                return Err(Error {
+                    code: METHOD_NOT_FOUND,
+                    message: format!("unknown method {}", method),
+                    data: None,
+                })
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn subscribe(&mut self) {
+        if self.subscribed {
+            return;
+        }
+        self.subscribed = true;
+
+        let events = self.daemon.subscribe();
+        let writer = self.writer.clone();
+        thread::spawn(move || {
+            for event in events {
+                let notification = Notification {
+                    jsonrpc: "2.0",
+                    method: "swap_event",
+                    params: &event,
+                };
+
+                // Stop forwarding once the connection is closed
+                if write_line(&writer, &notification).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+impl Response {
+    fn new(id: Value, result: Result<Value, Error>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, Error> {
+    serde_json::from_value(params).map_err(|e| Error {
+        code: INVALID_PARAMS,
+        message: e.to_string(),
+        data: None,
+    })
+}
+
+fn to_value<T: Serialize>(value: T) -> Value {
+    serde_json::to_value(value).expect("API types serialize to JSON")
+}
+
+fn write_line<T: Serialize>(writer: &Mutex<TcpStream>, message: &T) -> crate::Result<()> {
+    let mut line = serde_json::to_vec(message).expect("API types serialize to JSON");
+    line.push(b'\n');
+
+    writer
+        .lock()
+        .expect("connection lock poisoned")
+        .write_all(&line)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        messages::SwapId,
+        swap::execution::test::{wallets, GRIN_HEIGHT},
+        KeyPair,
+    };
+    use std::time::Duration;
+
+    // A connection to the API of a daemon without any swap
+    fn connect() -> crate::Result<(TcpStream, BufReader<TcpStream>)> {
+        let (wallets, _) = wallets(GRIN_HEIGHT);
+        let daemon = Daemon::new(
+            KeyPair::new_random(),
+            wallets,
+            std::env::temp_dir().join(format!("rpc-{}", SwapId::random())),
+            Duration::from_secs(60),
+            None,
+            None,
+        );
+
+        let listener = bind("127.0.0.1:0", false)?;
+        let address = listener.local_addr()?;
+        thread::spawn(move || serve(daemon, listener));
+
+        let stream = TcpStream::connect(address)?;
+        let reader = BufReader::new(stream.try_clone()?);
+
+        Ok((stream, reader))
+    }
+
+    fn call(request: &str) -> crate::Result<Value> {
+        let (mut stream, mut reader) = connect()?;
+        writeln!(stream, "{}", request)?;
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        Ok(serde_json::from_str(&line).expect("responses are JSON"))
+    }
+
+    #[test]
+    fn malformed_request_is_a_parse_error() -> crate::Result<()> {
+        let response = call(r#"{"jsonrpc": "2.0", "id": 1, "method""#)?;
+
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert!(response.get("result").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_method_is_not_found() -> crate::Result<()> {
+        let response = call(r#"{"jsonrpc": "2.0", "id": 2, "method": "withdraw"}"#)?;
+
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert!(response.get("result").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_params_are_invalid() -> crate::Result<()> {
+        let response =
+            call(r#"{"jsonrpc": "2.0", "id": 3, "method": "get_swap", "params": {"id": "1"}}"#)?;
+
+        assert_eq!(response["id"], 3);
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert!(response.get("result").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_address_needs_opt_in() {
+        assert!(bind("0.0.0.0:0", false).is_err());
+        assert!(bind("0.0.0.0:0", true).is_ok());
+        assert!(bind("localhost:0", false).is_ok());
+    }
+}
@@ -1,13 +1,24 @@
-use crate::keypair::{KeyPair, PublicKey, SecretKey, SECP};
-use sha2::{Digest, Sha256};
+use crate::{
+    keypair::{KeyPair, PublicKey, SecretKey, SECP},
+    transcript::Transcript,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Proof {
     s: SecretKey,
     c: SecretKey,
 }
 
-pub fn prove(G: &PublicKey, Gx: &PublicKey, H: &PublicKey, Hx: &PublicKey, x: &SecretKey) -> Proof {
+impl_wire!(Proof { s, c });
+
+pub fn prove(
+    G: &PublicKey,
+    Gx: &PublicKey,
+    H: &PublicKey,
+    Hx: &PublicKey,
+    x: &SecretKey,
+    transcript: &Transcript,
+) -> Proof {
     // NOTE: using thread_rng for PoC and even early stage production but there
     // are more robust ways of doing this which include hashing secret
     // information along with randomness (see https://github.com/bitcoin/bips/pull/893/).
@@ -15,26 +26,19 @@ pub fn prove(G: &PublicKey, Gx: &PublicKey, H: &PublicKey, Hx: &PublicKey, x: &S
 
     // Gr
     let mut Gr = *G;
-    Gr.mul_assign(&*SECP, &r.secret_key).unwrap();
+    Gr.mul_assign(&*SECP, r.expose_secret_key()).unwrap();
 
     // Hr
     let mut Hr = *H;
-    Hr.mul_assign(&*SECP, &r.secret_key).unwrap();
-
-    // c = H(G | Gx | H | Hx | Gr | Hr)
-    let mut hasher = Sha256::default();
-    hasher.input(&G.serialize_vec(&*SECP, true));
-    hasher.input(&Gx.serialize_vec(&*SECP, true));
-    hasher.input(&H.serialize_vec(&*SECP, true));
-    hasher.input(&Hx.serialize_vec(&*SECP, true));
-    hasher.input(&Gr.serialize_vec(&*SECP, true));
-    hasher.input(&Hr.serialize_vec(&*SECP, true));
-    let c = SecretKey::from_slice(&*SECP, &hasher.result()[..]).unwrap();
+    Hr.mul_assign(&*SECP, r.expose_secret_key()).unwrap();
+
+    // c = H(transcript | G | Gx | H | Hx | Gr | Hr)
+    let c = challenge(transcript, G, Gx, H, Hx, &Gr, &Hr);
 
     // s = r + cx
     let mut s = c.clone();
     s.mul_assign(&*SECP, &x).unwrap();
-    s.add_assign(&*SECP, &r.secret_key).unwrap();
+    s.add_assign(&*SECP, r.expose_secret_key()).unwrap();
 
     Proof { s, c }
 }
@@ -45,6 +49,7 @@ pub fn verify(
     H: &PublicKey,
     Hx: &PublicKey,
     proof: &Proof, // (s = r + cx, c)
+    transcript: &Transcript,
 ) -> bool {
     let mut c_neg = proof.c.clone();
     c_neg.neg_assign(&*SECP).unwrap();
@@ -70,20 +75,33 @@ pub fn verify(
         PublicKey::from_combination(&*SECP, vec![&Hxc_neg, &Hs]).unwrap()
     };
 
-    // c = H(G | Gx | H | Hx | Gr | Hr)
-    let mut hasher = Sha256::default();
-    hasher.input(&G.serialize_vec(&*SECP, true));
-    hasher.input(&Gx.serialize_vec(&*SECP, true));
-    hasher.input(&H.serialize_vec(&*SECP, true));
-    hasher.input(&Hx.serialize_vec(&*SECP, true));
-    hasher.input(&Gr.serialize_vec(&*SECP, true));
-    hasher.input(&Hr.serialize_vec(&*SECP, true));
-    let c = SecretKey::from_slice(&*SECP, &hasher.result()[..]).unwrap();
+    // c = H(transcript | G | Gx | H | Hx | Gr | Hr)
+    let c = challenge(transcript, G, Gx, H, Hx, &Gr, &Hr);
 
     // c == c'
     proof.c == c
 }
 
+fn challenge(
+    transcript: &Transcript,
+    G: &PublicKey,
+    Gx: &PublicKey,
+    H: &PublicKey,
+    Hx: &PublicKey,
+    Gr: &PublicKey,
+    Hr: &PublicKey,
+) -> SecretKey {
+    let mut transcript = transcript.clone();
+    transcript.append_public_key(b"G", G);
+    transcript.append_public_key(b"Gx", Gx);
+    transcript.append_public_key(b"H", H);
+    transcript.append_public_key(b"Hx", Hx);
+    transcript.append_public_key(b"Gr", Gr);
+    transcript.append_public_key(b"Hr", Hr);
+
+    transcript.challenge_scalar(b"dleq").unwrap()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -101,8 +119,9 @@ mod test {
         let mut Hx = H;
         Hx.mul_assign(&*SECP, &x).unwrap();
 
-        let proof = crate::dleq::prove(&*G, &Gx, &H, &Hx, &x);
+        let transcript = Transcript::new(b"test");
+        let proof = crate::dleq::prove(&*G, &Gx, &H, &Hx, &x, &transcript);
 
-        assert!(crate::dleq::verify(&*G, &Gx, &H, &Hx, &proof))
+        assert!(crate::dleq::verify(&*G, &Gx, &H, &Hx, &proof, &transcript))
     }
 }
@@ -1,6 +1,8 @@
 use crate::{
     dleq,
+    error::CryptoError,
     keypair::{random_secret_key, KeyPair, Negate, PublicKey, SecretKey, XCoor, G, SECP},
+    transcript::Transcript,
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -9,7 +11,7 @@ pub struct Signature {
     R_x: SecretKey,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EncryptedSignature {
     R: PublicKey,
     R_hat: PublicKey,
@@ -17,7 +19,19 @@ pub struct EncryptedSignature {
     proof: dleq::Proof,
 }
 
-pub fn encsign(x: &KeyPair, Y: &PublicKey, message_hash: &[u8]) -> EncryptedSignature {
+impl_wire!(EncryptedSignature {
+    R,
+    R_hat,
+    s_hat,
+    proof,
+});
+
+pub fn encsign(
+    x: &KeyPair,
+    Y: &PublicKey,
+    message_hash: &[u8],
+    transcript: &Transcript,
+) -> EncryptedSignature {
     let r = random_secret_key();
     let mut R_hat = *G;
     R_hat.mul_assign(&*SECP, &r).unwrap();
@@ -25,13 +39,13 @@ pub fn encsign(x: &KeyPair, Y: &PublicKey, message_hash: &[u8]) -> EncryptedSign
     let mut R = *Y;
     R.mul_assign(&*SECP, &r).unwrap();
 
-    let proof = dleq::prove(&G, &R_hat, &Y, &R, &r);
+    let proof = dleq::prove(&G, &R_hat, &Y, &R, &r, transcript);
 
     let s_hat = {
         let R_x = SecretKey::from_slice(&*SECP, &R.x_coor()).unwrap();
 
         let mut s_hat = R_x;
-        s_hat.mul_assign(&*SECP, &x.secret_key).unwrap();
+        s_hat.mul_assign(&*SECP, x.expose_secret_key()).unwrap();
         s_hat
             .add_assign(
                 &*SECP,
@@ -55,12 +69,6 @@ pub fn encsign(x: &KeyPair, Y: &PublicKey, message_hash: &[u8]) -> EncryptedSign
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum EncVerifyError {
-    InvalidProof,
-    Invalid,
-}
-
 pub fn encverify(
     X: &PublicKey,
     Y: &PublicKey,
@@ -71,10 +79,11 @@ pub fn encverify(
         s_hat,
         proof,
     }: &EncryptedSignature,
-) -> Result<(), EncVerifyError> {
+    transcript: &Transcript,
+) -> Result<(), CryptoError> {
     //TODO: check that s_hat is not 0 -- it will cause a panic
-    if !dleq::verify(&*G, R_hat, Y, R, proof) {
-        return Err(EncVerifyError::InvalidProof);
+    if !dleq::verify(&*G, R_hat, Y, R, proof, transcript) {
+        return Err(CryptoError::InvalidProof("DLEQ"));
     }
 
     //TODO: Don't panic on something that can be provided by a malicious party
@@ -107,13 +116,13 @@ pub fn encverify(
     if &R_hat_candidate == R_hat {
         Ok(())
     } else {
-        Err(EncVerifyError::Invalid)
+        Err(CryptoError::InvalidSignature)
     }
 }
 
 pub fn decsig(y: &KeyPair, EncryptedSignature { R, s_hat, .. }: &EncryptedSignature) -> Signature {
     let s = {
-        let mut y_inv = y.secret_key.clone();
+        let mut y_inv = y.expose_secret_key().clone();
         y_inv.inv_assign(&*SECP).unwrap();
 
         let mut s = s_hat.clone();
@@ -135,6 +144,8 @@ pub struct RecoveryKey {
     s_hat: SecretKey,
 }
 
+impl_wire!(RecoveryKey { Y, s_hat });
+
 pub fn reckey(
     &Y: &PublicKey,
     EncryptedSignature { s_hat, .. }: &EncryptedSignature,
@@ -148,7 +159,7 @@ pub fn reckey(
 pub fn recover(
     Signature { s, .. }: &Signature,
     RecoveryKey { Y, s_hat }: &RecoveryKey,
-) -> anyhow::Result<KeyPair> {
+) -> crate::Result<KeyPair> {
     let y_macron = {
         let mut s_inv = s.clone();
         s_inv.inv_assign(&*SECP)?;
@@ -166,7 +177,7 @@ pub fn recover(
     } else if Gy_macron == Y.negate() {
         Ok(KeyPair::new(y_macron.negate()))
     } else {
-        Err(anyhow::anyhow!("recovery key does not match signature"))
+        Err(CryptoError::Calculate("decryption key from signature").into())
     }
 }
 
@@ -211,9 +222,18 @@ mod test {
         let y = KeyPair::new_random();
         let message_hash = b"xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
 
-        let enc_signature = encsign(&x, &y.public_key, message_hash);
+        let transcript = Transcript::new(b"test");
+
+        let enc_signature = encsign(&x, &y.public_key, message_hash, &transcript);
 
-        encverify(&x.public_key, &y.public_key, message_hash, &enc_signature).unwrap();
+        encverify(
+            &x.public_key,
+            &y.public_key,
+            message_hash,
+            &enc_signature,
+            &transcript,
+        )
+        .unwrap();
     }
 
     #[test]
@@ -224,7 +244,7 @@ mod test {
         let message_hash = b"mmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmm";
         let _message_hash = &Message::from_slice(message_hash).expect("message hash");
 
-        let encsig = encsign(&x, &y.public_key, message_hash);
+        let encsig = encsign(&x, &y.public_key, message_hash, &Transcript::new(b"test"));
 
         let sig = decsig(&y, &encsig);
 
@@ -240,12 +260,12 @@ mod test {
 
         let message_hash = b"mmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmm";
 
-        let encsig = encsign(&x, &y.public_key, message_hash);
+        let encsig = encsign(&x, &y.public_key, message_hash, &Transcript::new(b"test"));
         let sig = decsig(&y, &encsig);
 
         let rec_key = reckey(&y.public_key, &encsig);
         let y_tag = recover(&sig, &rec_key).unwrap();
 
-        assert_eq!(y.secret_key, y_tag.secret_key);
+        assert_eq!(y.expose_secret_key(), y_tag.expose_secret_key());
     }
 }
@@ -0,0 +1,314 @@
+//! Errors callers can act on, one enum per layer, and the `Error` wrapping
+//! them which every function of the library returns.
+//!
+//! Errors are raised in the enum of the layer where the cause is known and may
+//! be wrapped in context on their way up. `Recovery::of` finds them again and
+//! tells the caller whether to retry, abort the swap or blame the peer. Errors
+//! of no layer, such as a corrupted swap file, have no recovery.
+
+use crate::messages::{Abort, SwapId};
+use serde::Serialize;
+use std::fmt;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+    #[error(transparent)]
+    Bitcoin(#[from] BitcoinError),
+    #[error(transparent)]
+    Grin(#[from] GrinError),
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Request(#[from] RequestError),
+    #[error(transparent)]
+    Abort(#[from] Abort),
+    // The connection to the peer, a node or a wallet dropped
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{context}: {source}")]
+    Context { context: String, source: Box<Error> },
+}
+
+impl Error {
+    /// The error without the context it was wrapped in.
+    pub fn root(&self) -> &Self {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+}
+
+impl From<secp256k1zkp::Error> for Error {
+    fn from(error: secp256k1zkp::Error) -> Self {
+        Error::Crypto(CryptoError::Secp256k1(error.to_string()))
+    }
+}
+
+impl From<grin_core::core::transaction::Error> for Error {
+    fn from(error: grin_core::core::transaction::Error) -> Self {
+        Error::Grin(GrinError::InvalidTransaction(error.to_string()))
+    }
+}
+
+/// Wrap errors in what was being done when they happened, like
+/// `anyhow::Context`.
+pub trait Context<T> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T>;
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T> {
+        self.map_err(|error| Error::Context {
+            context: context.to_string(),
+            source: Box::new(error.into()),
+        })
+    }
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|error| Error::Context {
+            context: f().to_string(),
+            source: Box::new(error.into()),
+        })
+    }
+}
+
+/// What the caller of a failed operation should do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Recovery {
+    /// The failure is temporary, e.g. a node or the wallet is unreachable.
+    Retry,
+    /// The swap cannot go on, but the peer is not at fault.
+    AbortSwap,
+    /// The peer sent something invalid. Before funding, the swap should be
+    /// aborted with the matching `ReasonCode`.
+    BlamePeer,
+}
+
+impl Recovery {
+    pub fn of(error: &Error) -> Option<Self> {
+        match error.root() {
+            Error::Crypto(error) => Some(error.recovery()),
+            Error::Protocol(error) => Some(error.recovery()),
+            Error::Bitcoin(error) => Some(error.recovery()),
+            Error::Grin(error) => Some(error.recovery()),
+            Error::Wallet(error) => Some(error.recovery()),
+            Error::Storage(_) | Error::Request(_) => None,
+            Error::Abort(_) => Some(Recovery::AbortSwap),
+            Error::Io(_) => Some(Recovery::Retry),
+            Error::Context { .. } => unreachable!("root error has no context"),
+        }
+    }
+}
+
+/// Failures of the signature schemes and proofs.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum CryptoError {
+    #[error("failed to calculate {0}")]
+    Calculate(&'static str),
+    #[error("failed to verify signature")]
+    InvalidSignature,
+    #[error("failed to verify {0} proof")]
+    InvalidProof(&'static str),
+    #[error("secp256k1 operation failed: {0}")]
+    Secp256k1(String),
+}
+
+impl CryptoError {
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            CryptoError::Calculate(_) | CryptoError::Secp256k1(_) => Recovery::AbortSwap,
+            CryptoError::InvalidSignature | CryptoError::InvalidProof(_) => Recovery::BlamePeer,
+        }
+    }
+}
+
+/// The peer did not follow the protocol, or the parties disagree on the swap.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("opening does not match commitment")]
+    InvalidOpening,
+    #[error("received a message for swap {received} instead of swap {expected}")]
+    SwapIdMismatch { expected: SwapId, received: SwapId },
+    #[error("malformed message: {0}")]
+    Malformed(String),
+    #[error("failed to verify {0}'s offer signature")]
+    InvalidOfferSignature(&'static str),
+    #[error("offer not acceptable: {0}")]
+    InvalidOffer(String),
+    #[error("peer authenticated with an unexpected identity")]
+    UnexpectedIdentity,
+}
+
+impl ProtocolError {
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            ProtocolError::InvalidOffer(_) | ProtocolError::UnexpectedIdentity => {
+                Recovery::AbortSwap
+            }
+            _ => Recovery::BlamePeer,
+        }
+    }
+}
+
+/// Failures of the Bitcoin node and of the Bitcoin transactions of the swap.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum BitcoinError {
+    #[error("Bitcoin node request failed: {0}")]
+    Node(String),
+    // May be accepted later, e.g. once the refund is no longer locked
+    #[error("Bitcoin node rejected transaction {txid}: {reason}")]
+    Rejected { txid: String, reason: String },
+    // Can never be mined, e.g. an input was spent by a conflicting transaction
+    #[error("Bitcoin node rejected transaction {txid} for good: {reason}")]
+    RejectedPermanently { txid: String, reason: String },
+    #[error("failed to verify redeemer's Bitcoin {0} signature")]
+    InvalidRedeemerSignature(&'static str),
+}
+
+impl BitcoinError {
+    /// Classify the `reason` given by Bitcoin Core for rejecting a
+    /// transaction. Unknown reasons are assumed to be temporary.
+    pub fn rejected(txid: String, reason: String) -> Self {
+        const PERMANENT: &[&str] = &[
+            "txn-mempool-conflict",
+            "bad-txns-inputs-missingorspent",
+            "missing inputs",
+            "mandatory-script-verify-flag-failed",
+        ];
+
+        let lowercase_reason = reason.to_lowercase();
+        if PERMANENT
+            .iter()
+            .any(|permanent| lowercase_reason.contains(permanent))
+        {
+            BitcoinError::RejectedPermanently { txid, reason }
+        } else {
+            BitcoinError::Rejected { txid, reason }
+        }
+    }
+
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            BitcoinError::Node(_) | BitcoinError::Rejected { .. } => Recovery::Retry,
+            BitcoinError::RejectedPermanently { .. } => Recovery::AbortSwap,
+            BitcoinError::InvalidRedeemerSignature(_) => Recovery::BlamePeer,
+        }
+    }
+}
+
+/// Failures of the Grin node and of the Grin transactions of the swap.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum GrinError {
+    #[error("Grin node request failed: {0}")]
+    Node(String),
+    #[error("failed to verify redeemer's Grin {0} signature")]
+    InvalidRedeemerSignature(&'static str),
+    #[error("invalid Grin transaction: {0}")]
+    InvalidTransaction(String),
+}
+
+impl GrinError {
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            GrinError::Node(_) => Recovery::Retry,
+            GrinError::InvalidRedeemerSignature(_) => Recovery::BlamePeer,
+            GrinError::InvalidTransaction(_) => Recovery::AbortSwap,
+        }
+    }
+}
+
+/// Failures of the wallets funding the swap.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum WalletError {
+    #[error("insufficient funds: {0}")]
+    InsufficientFunds(String),
+    #[error("no Bitcoin output to fund the swap with")]
+    NoFundingOutput,
+    #[error("wallet unavailable: {0}")]
+    Unavailable(String),
+    #[error("timed out waiting for slate {0}")]
+    SlateTimeout(String),
+    // The wallet refused to build or complete a slate, e.g. a malformed one
+    #[error("slate rejected by wallet: {0}")]
+    Slate(String),
+}
+
+impl WalletError {
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            WalletError::InsufficientFunds(_)
+            | WalletError::NoFundingOutput
+            | WalletError::Slate(_) => Recovery::AbortSwap,
+            WalletError::Unavailable(_) | WalletError::SlateTimeout(_) => Recovery::Retry,
+        }
+    }
+}
+
+/// Failures of the files kept by this process: configuration, swap files,
+/// history and keys.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum StorageError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("corrupted {0}")]
+    Corrupted(String),
+}
+
+/// Requests of the user which cannot be carried out, e.g. for a swap which
+/// does not exist.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum RequestError {
+    #[error("unknown swap {0}")]
+    UnknownSwap(String),
+    #[error("swap {0} already exists")]
+    SwapExists(SwapId),
+    // The swap is not in a state where the request can be carried out
+    #[error("{0}")]
+    Refused(String),
+    #[error("invalid request: {0}")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recovery_is_found_through_context() {
+        let error: Result<()> = Err(BitcoinError::Node("timeout".into()).into());
+        let error = error.context("failed to fund swap").unwrap_err();
+
+        assert_eq!(Recovery::of(&error), Some(Recovery::Retry));
+    }
+
+    #[test]
+    fn conflicting_spend_aborts_swap() {
+        let conflict =
+            BitcoinError::rejected("txid".into(), "txn-mempool-conflict (code 18)".into());
+        let spent = BitcoinError::rejected("txid".into(), "Missing inputs".into());
+        let locked = BitcoinError::rejected("txid".into(), "non-final (code 64)".into());
+
+        assert_eq!(conflict.recovery(), Recovery::AbortSwap);
+        assert_eq!(spent.recovery(), Recovery::AbortSwap);
+        assert_eq!(locked.recovery(), Recovery::Retry);
+    }
+
+    #[test]
+    fn storage_errors_have_no_recovery() {
+        let error = Error::from(StorageError::Corrupted("swap file".into()));
+
+        assert_eq!(Recovery::of(&error), None);
+    }
+}
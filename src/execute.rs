@@ -1,6 +1,70 @@
+use crate::{expiry::unix_time_now, swap::Ledger};
+use secp256k1zkp::pedersen::Commitment;
+use serde::{Deserialize, Serialize};
+
 pub trait Execute {
-    type Wallet;
-    type Return;
+    type Wallet: ?Sized;
+
+    fn execute(self, wallet: &Self::Wallet) -> crate::Result<Receipt>;
+}
+
+/// What an action broadcast, in the same shape for both ledgers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub ledger: Ledger,
+    /// Transaction id on Bitcoin, excess of the kernel of the swap on Grin
+    pub reference: String,
+    /// Paid by the transaction, in the smallest unit of the ledger
+    pub fee: u64,
+    /// The outputs of the transaction whose value is known. On Grin these are
+    /// only the outputs of the swap, or the amount swept into the wallet.
+    pub amounts: Vec<u64>,
+    /// Seconds since the UNIX epoch
+    pub broadcast_at: u64,
+    /// Excesses of every kernel of the aggregated transaction, empty on
+    /// Bitcoin
+    pub kernels: Vec<String>,
+}
+
+impl Receipt {
+    /// The receipt of a Bitcoin transaction which was just broadcast.
+    pub fn bitcoin(transaction: &::bitcoin::Transaction, fee: u64) -> Self {
+        Self {
+            ledger: Ledger::Bitcoin,
+            reference: transaction.txid().to_string(),
+            fee,
+            amounts: transaction
+                .output
+                .iter()
+                .map(|output| output.value)
+                .collect(),
+            broadcast_at: unix_time_now(),
+            kernels: Vec::new(),
+        }
+    }
+
+    /// The receipt of a Grin transaction which was just posted, identified by
+    /// the kernel of the swap among those it aggregates.
+    pub fn grin(
+        transaction: &grin_core::core::Transaction,
+        excess: &Commitment,
+        amounts: Vec<u64>,
+    ) -> Self {
+        Self {
+            ledger: Ledger::Grin,
+            reference: excess_to_hex(excess),
+            fee: transaction.fee(),
+            amounts,
+            broadcast_at: unix_time_now(),
+            kernels: transaction
+                .kernels()
+                .iter()
+                .map(|kernel| excess_to_hex(&kernel.excess))
+                .collect(),
+        }
+    }
+}
 
-    fn execute(self, wallet: &Self::Wallet) -> anyhow::Result<Self::Return>;
+fn excess_to_hex(excess: &Commitment) -> String {
+    hex::encode(&excess.0[..])
 }
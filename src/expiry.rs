@@ -0,0 +1,245 @@
+//! Checking that the expiries of both ledgers are ordered safely.
+//!
+//! Alice funds the alpha ledger first and Bob only funds the beta ledger once
+//! that is confirmed. If the alpha refund became valid before the beta refund,
+//! Alice could redeem on the beta ledger and still refund on the alpha ledger.
+//! The alpha refund must therefore unlock strictly after the beta refund, with
+//! enough margin for Bob to redeem on the alpha ledger once he learns `y`.
+//!
+//! Ledgers express their expiries as block heights or timestamps, so heights
+//! are converted to times using an assumed block time.
+
+use crate::{
+    error::ProtocolError,
+    ledger::LedgerOffer,
+    wire::{malformed, Decode, Encode},
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Leaves half a day to get the alpha redeem transaction confirmed
+pub const DEFAULT_SAFETY_MARGIN: u64 = 12 * 60 * 60;
+
+/// Alice stops redeeming on the beta ledger this long before Bob can refund
+/// on it. A redeem still unconfirmed when the refund unlocks would reveal `y`
+/// to Bob while letting him take the beta asset back.
+pub const REDEEM_SAFETY_MARGIN: u64 = 3 * 60 * 60;
+
+/// When the refund transaction of a ledger becomes valid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefundLock {
+    Height(u64),
+    // Seconds since the UNIX epoch
+    Timestamp(u64),
+}
+
+impl RefundLock {
+    /// Whether the refund can be included in the block after `tip`.
+    ///
+    /// Like Bitcoin, a timestamp lock is compared against the median time of
+    /// the past blocks of the chain rather than the wall clock, which runs
+    /// ahead of it.
+    pub fn is_unlocked(self, tip: u64, median_time_past: Option<u64>) -> crate::Result<bool> {
+        match self {
+            RefundLock::Height(height) => Ok(tip >= height),
+            RefundLock::Timestamp(time) => median_time_past
+                .map(|median_time_past| median_time_past > time)
+                .ok_or_else(|| {
+                    ProtocolError::InvalidOffer(
+                        "timestamp refund lock without the median time past".into(),
+                    )
+                    .into()
+                }),
+        }
+    }
+}
+
+impl Encode for RefundLock {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let (tag, value) = match self {
+            RefundLock::Height(height) => (0u8, height),
+            RefundLock::Timestamp(time) => (1, time),
+        };
+
+        tag.encode(buf);
+        value.encode(buf);
+    }
+}
+
+impl Decode for RefundLock {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(RefundLock::Height(u64::decode(bytes)?)),
+            1 => Ok(RefundLock::Timestamp(u64::decode(bytes)?)),
+            other => Err(malformed(format!("unknown refund lock {}", other))),
+        }
+    }
+}
+
+/// Relates the block heights of a chain to time, assuming blocks are found at
+/// a constant rate from a known height onwards.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockClock {
+    pub anchor_height: u64,
+    pub anchor_time: u64,
+    pub block_time: u64,
+}
+
+impl BlockClock {
+    pub fn anchored_now(current_height: u64, block_time: u64) -> Self {
+        Self {
+            anchor_height: current_height,
+            anchor_time: unix_time_now(),
+            block_time,
+        }
+    }
+
+    pub fn time_at(&self, height: u64) -> u64 {
+        if height >= self.anchor_height {
+            self.anchor_time
+                .saturating_add((height - self.anchor_height).saturating_mul(self.block_time))
+        } else {
+            self.anchor_time
+                .saturating_sub((self.anchor_height - height).saturating_mul(self.block_time))
+        }
+    }
+
+    /// Estimated time at which `lock` is reached, in seconds since the UNIX
+    /// epoch.
+    pub fn unlock_time(&self, lock: RefundLock) -> u64 {
+        match lock {
+            RefundLock::Height(height) => self.time_at(height),
+            RefundLock::Timestamp(time) => time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiryValidator {
+    pub alpha_clock: BlockClock,
+    pub beta_clock: BlockClock,
+    // Seconds
+    pub safety_margin: u64,
+}
+
+impl ExpiryValidator {
+    pub fn new(alpha_clock: BlockClock, beta_clock: BlockClock) -> Self {
+        Self {
+            alpha_clock,
+            beta_clock,
+            safety_margin: DEFAULT_SAFETY_MARGIN,
+        }
+    }
+
+    pub fn validate<A, B>(&self, alpha_offer: &A, beta_offer: &B) -> crate::Result<()>
+    where
+        A: LedgerOffer,
+        B: LedgerOffer,
+    {
+        let alpha_unlock = self.alpha_clock.unlock_time(alpha_offer.refund_lock());
+        let beta_unlock = self.beta_clock.unlock_time(beta_offer.refund_lock());
+
+        if alpha_unlock <= beta_unlock.saturating_add(self.safety_margin) {
+            return Err(ProtocolError::InvalidOffer(format!(
+                "alpha refund unlocks at {}, which is not later than the beta refund at {} plus \
+                 a safety margin of {} seconds",
+                alpha_unlock, beta_unlock, self.safety_margin
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+pub fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is after the UNIX epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{bitcoin, grin};
+
+    fn offers(grin_height: u64, bitcoin_time: u32) -> (grin::Offer, bitcoin::Offer) {
+        let grin_offer = grin::Offer {
+            asset: 10_000_000_000,
+            base_fee: grin::DEFAULT_BASE_FEE,
+            expiry: grin::Expiry::Absolute(grin_height),
+        };
+        let bitcoin_offer = bitcoin::Offer {
+            asset: 100_000_000,
+            fee: 1_000,
+            expiry: bitcoin_time,
+            chain: bitcoin::ChainParams::bitcoin_regtest(),
+        };
+
+        (grin_offer, bitcoin_offer)
+    }
+
+    fn validator() -> ExpiryValidator {
+        let grin_clock = BlockClock {
+            anchor_height: 1_000,
+            anchor_time: 1_600_000_000,
+            block_time: 60,
+        };
+        let bitcoin_clock = BlockClock {
+            anchor_height: 100,
+            anchor_time: 1_600_000_000,
+            block_time: 600,
+        };
+
+        ExpiryValidator::new(grin_clock, bitcoin_clock)
+    }
+
+    #[test]
+    fn alpha_refund_must_unlock_after_beta_refund_plus_margin() {
+        let validator = validator();
+
+        // Grin refund one day after the anchor, Bitcoin refund one hour after it
+        let (grin_offer, bitcoin_offer) = offers(1_000 + 24 * 60, 1_600_000_000 + 60 * 60);
+        assert!(validator.validate(&grin_offer, &bitcoin_offer).is_ok());
+
+        // Swapping the ledgers makes the alpha refund unlock first
+        let validator = ExpiryValidator::new(validator.beta_clock, validator.alpha_clock);
+        assert!(validator.validate(&bitcoin_offer, &grin_offer).is_err());
+    }
+
+    #[test]
+    fn refunds_within_safety_margin_are_rejected() {
+        let validator = validator();
+
+        // Grin refund only 6 hours after the Bitcoin one
+        let (grin_offer, bitcoin_offer) = offers(1_000 + 7 * 60, 1_600_000_000 + 60 * 60);
+
+        assert!(validator.validate(&grin_offer, &bitcoin_offer).is_err());
+    }
+
+    #[test]
+    fn timestamp_lock_is_compared_against_median_time_past() -> crate::Result<()> {
+        let lock = RefundLock::Timestamp(1_600_000_000);
+
+        assert!(!lock.is_unlocked(100, Some(1_600_000_000))?);
+        assert!(lock.is_unlocked(100, Some(1_600_000_001))?);
+        assert!(lock.is_unlocked(100, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn refund_locks_round_trip_through_the_wire() -> crate::Result<()> {
+        for lock in vec![
+            RefundLock::Height(1_000),
+            RefundLock::Timestamp(1_600_000_000),
+        ] {
+            assert_eq!(
+                crate::wire::from_bytes::<RefundLock>(&crate::wire::to_bytes(&lock))?,
+                lock
+            );
+        }
+
+        Ok(())
+    }
+}
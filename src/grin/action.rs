@@ -1,24 +1,37 @@
 use crate::{
+    error::{Context, GrinError, WalletError},
     grin::{
         compute_excess_pk, compute_excess_sk, compute_offset, public_key_to_pedersen_commitment,
+        recovery::RecoveryData,
         wallet::{build_input, build_output},
-        Offer, PKs, SKs, SpecialOutputKeyPairsRedeemer, SpecialOutputs, Wallet,
+        GrinWallet, Offer, PKs, SKs, SpecialOutputKeyPairsRedeemer, SpecialOutputs,
     },
     keypair::{random_secret_key, KeyPair, PublicKey, SecretKey, SECP},
-    schnorr, Execute,
+    schnorr,
+    transcript::Transcript,
+    Execute, Receipt,
 };
-use anyhow::Context;
 use grin_core::core::{Input, KernelFeatures, Output, OutputFeatures, Transaction, TxKernel};
 use grin_keychain::BlindingFactor;
 use grin_wallet_libwallet::{ParticipantData, Slate};
-use secp256k1zkp::{aggsig, pedersen::RangeProof, Signature};
+use secp256k1zkp::{
+    aggsig,
+    pedersen::{Commitment, RangeProof},
+    Signature,
+};
 use std::convert::TryInto;
 
+#[derive(Clone)]
 pub struct Fund {
     transaction_from_special_input: Transaction,
     special_input: (u64, KeyPair),
 }
 
+impl_wire!(Fund {
+    transaction_from_special_input,
+    special_input,
+});
+
 impl Fund {
     pub fn new(
         inputs: Vec<(u64, PublicKey)>,
@@ -28,7 +41,7 @@ impl Fund {
         kernel_features: KernelFeatures,
         offset: SecretKey,
         special_input: (u64, KeyPair),
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         Ok(Self {
             transaction_from_special_input: new_transaction(
                 inputs,
@@ -44,12 +57,37 @@ impl Fund {
     }
 }
 
+impl Fund {
+    /// The excess of the kernel of the fund transaction, to find out when it
+    /// was confirmed.
+    pub fn excess(&self) -> Commitment {
+        self.transaction_from_special_input.kernels()[0].excess
+    }
+
+    /// The special output of the fund action is paid to by the funder's
+    /// wallet, so it can only be swept back into it.
+    pub fn recovery_data(&self, wallet_transaction_fee: u64) -> RecoveryData {
+        RecoveryData {
+            special_output: self.special_input.clone(),
+            transaction_to_special_output: None,
+            wallet_transaction_fee,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Refund {
     transaction_to_special_output: Transaction,
     special_output: (u64, KeyPair),
     wallet_transaction_fee: u64,
 }
 
+impl_wire!(Refund {
+    transaction_to_special_output,
+    special_output,
+    wallet_transaction_fee,
+});
+
 impl Refund {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -61,7 +99,7 @@ impl Refund {
         offset: SecretKey,
         special_output: (u64, KeyPair),
         wallet_transaction_fee: u64,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         Ok(Self {
             transaction_to_special_output: new_transaction(
                 inputs,
@@ -78,15 +116,51 @@ impl Refund {
     }
 }
 
+impl Refund {
+    /// The swap transaction paying the fund output into the special output,
+    /// which is valid on its own once its kernel is unlocked.
+    pub fn transaction_to_special_output(&self) -> &Transaction {
+        &self.transaction_to_special_output
+    }
+
+    pub fn recovery_data(&self) -> RecoveryData {
+        RecoveryData {
+            special_output: self.special_output.clone(),
+            transaction_to_special_output: Some(self.transaction_to_special_output.clone()),
+            wallet_transaction_fee: self.wallet_transaction_fee,
+        }
+    }
+}
+
+// Everything needed to build the redeem transaction except for the excess
+// signature, which can only be decrypted once `y` is known
+#[derive(Clone)]
 pub struct EncryptedRedeem {
-    incomplete_transaction_to_special_output:
-        Box<dyn FnOnce(Signature) -> anyhow::Result<Transaction>>,
+    inputs: Vec<(u64, PublicKey)>,
+    outputs: Vec<(u64, PublicKey, RangeProof)>,
+    excess_pk: PublicKey,
+    kernel_features: KernelFeatures,
+    offset: SecretKey,
     special_output: (u64, KeyPair),
     wallet_transaction_fee: u64,
     encsig: schnorr::EncryptedSignature,
     R_hat: PublicKey,
+    R: PublicKey,
 }
 
+impl_wire!(EncryptedRedeem {
+    inputs,
+    outputs,
+    excess_pk,
+    kernel_features,
+    offset,
+    special_output,
+    wallet_transaction_fee,
+    encsig,
+    R_hat,
+    R,
+});
+
 impl EncryptedRedeem {
     pub fn new(
         offer: Offer,
@@ -96,8 +170,13 @@ impl EncryptedRedeem {
         funder_PKs: PKs,
         Y: PublicKey,
         encsig: schnorr::EncryptedSignature,
-    ) -> anyhow::Result<Self> {
-        let offset = compute_offset(&funder_PKs.R_redeem, &redeemer_SKs.r_redeem.public_key)?;
+        transcript: &Transcript,
+    ) -> crate::Result<Self> {
+        let offset = compute_offset(
+            transcript,
+            &funder_PKs.R_redeem,
+            &redeemer_SKs.r_redeem.public_key,
+        )?;
 
         let excess_pk = compute_excess_pk(
             vec![&redeemer_SKs.x.public_key, &funder_PKs.X],
@@ -111,7 +190,7 @@ impl EncryptedRedeem {
         ])?;
         let R = PublicKey::from_combination(&*SECP, vec![&R_hat, &Y])?;
 
-        let kernel_features = KernelFeatures::Plain { fee: 0 };
+        let kernel_features = offer.redeem_kernel_features();
 
         if !aggsig::verify_single(
             &*SECP,
@@ -123,84 +202,80 @@ impl EncryptedRedeem {
             None,
             true,
         ) {
-            return Err(anyhow::anyhow!(
-                "failed to verify Grin encrypted redeem signature"
-            ));
+            return Err(GrinError::InvalidRedeemerSignature("encrypted redeem").into());
         }
 
-        let incomplete_transaction_to_special_output = {
-            let inputs = vec![(
-                offer.fund_output_amount(),
-                PublicKey::from_combination(&*SECP, vec![
-                    &redeemer_SKs.x.public_key,
-                    &funder_PKs.X,
-                ])?,
-            )];
-
-            let bulletproof = SECP.bullet_proof(
-                offer.fund_output_amount(),
-                special_output_keypairs_redeemer
-                    .redeem_output_key
-                    .secret_key
-                    .clone(),
-                random_secret_key(),
-                random_secret_key(),
-                None,
-                None,
-            );
-
-            let outputs = vec![(
-                offer.fund_output_amount(),
-                special_output_keypairs_redeemer
-                    .redeem_output_key
-                    .public_key,
-                bulletproof,
-            )];
-
-            Box::new(move |excess_sig| {
-                if !aggsig::verify_single(
-                    &*SECP,
-                    &excess_sig,
-                    &kernel_features.kernel_sig_msg()?,
-                    Some(&R),
-                    &excess_pk,
-                    Some(&excess_pk),
+        let inputs = vec![(
+            offer.fund_output_amount(),
+            PublicKey::from_combination(&*SECP, vec![&redeemer_SKs.x.public_key, &funder_PKs.X])?,
+        )];
+
+        let bulletproof = special_output_keypairs_redeemer
+            .redeem_output_proof
+            .unwrap_or_else(|| {
+                SECP.bullet_proof(
+                    offer.redeem_output_amount(),
+                    special_output_keypairs_redeemer
+                        .redeem_output_key
+                        .expose_secret_key()
+                        .clone(),
+                    random_secret_key(),
+                    random_secret_key(),
+                    None,
                     None,
-                    false,
-                ) {
-                    return Err(anyhow::anyhow!(
-                        "failed to verify Grin decrypted redeem signature"
-                    ));
-                }
-
-                new_transaction(
-                    inputs,
-                    outputs,
-                    excess_pk,
-                    excess_sig,
-                    kernel_features,
-                    offset,
                 )
-            })
-        };
+            });
+
+        let outputs = vec![(
+            offer.redeem_output_amount(),
+            special_output_keypairs_redeemer
+                .redeem_output_key
+                .public_key,
+            bulletproof,
+        )];
 
         Ok(Self {
-            incomplete_transaction_to_special_output,
+            inputs,
+            outputs,
+            excess_pk,
+            kernel_features,
+            offset,
             special_output: (
-                offer.fund_output_amount(),
+                offer.redeem_output_amount(),
                 special_output_keypairs_redeemer.redeem_output_key,
             ),
-            wallet_transaction_fee: offer.fee,
+            wallet_transaction_fee: offer.spend_fee(),
             encsig,
             R_hat,
+            R,
         })
     }
 
-    pub fn decrypt(self, y: &KeyPair) -> anyhow::Result<Redeem> {
+    pub fn decrypt(self, y: &KeyPair) -> crate::Result<Redeem> {
         let excess_sig = schnorr::decsig(&y, &self.encsig, &self.R_hat)?;
 
-        let transaction_to_special_output =
-            (self.incomplete_transaction_to_special_output)(excess_sig).context("redeem")?;
+        if !aggsig::verify_single(
+            &*SECP,
+            &excess_sig,
+            &self.kernel_features.kernel_sig_msg()?,
+            Some(&self.R),
+            &self.excess_pk,
+            Some(&self.excess_pk),
+            None,
+            false,
+        ) {
+            return Err(GrinError::InvalidRedeemerSignature("decrypted redeem").into());
+        }
+
+        let transaction_to_special_output = new_transaction(
+            self.inputs,
+            self.outputs,
+            self.excess_pk,
+            excess_sig,
+            self.kernel_features,
+            self.offset,
+        )
+        .context("redeem")?;
 
         Ok(Redeem {
             transaction_to_special_output,
@@ -210,13 +285,29 @@ impl EncryptedRedeem {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Redeem {
     transaction_to_special_output: Transaction,
     special_output: (u64, KeyPair),
     wallet_transaction_fee: u64,
 }
 
+impl_wire!(Redeem {
+    transaction_to_special_output,
+    special_output,
+    wallet_transaction_fee,
+});
+
+impl Redeem {
+    pub fn recovery_data(&self) -> RecoveryData {
+        RecoveryData {
+            special_output: self.special_output.clone(),
+            transaction_to_special_output: Some(self.transaction_to_special_output.clone()),
+            wallet_transaction_fee: self.wallet_transaction_fee,
+        }
+    }
+}
+
 fn new_transaction(
     inputs: Vec<(u64, PublicKey)>,
     outputs: Vec<(u64, PublicKey, RangeProof)>,
@@ -224,7 +315,7 @@ fn new_transaction(
     excess_sig: Signature,
     kernel_features: KernelFeatures,
     offset: SecretKey,
-) -> anyhow::Result<Transaction> {
+) -> crate::Result<Transaction> {
     let inputs = inputs
         .iter()
         .map(|(amount, blind_pk)| {
@@ -236,7 +327,7 @@ fn new_transaction(
                 public_key_to_pedersen_commitment(&commit_pk),
             ))
         })
-        .collect::<Result<Vec<Input>, anyhow::Error>>()?;
+        .collect::<Result<Vec<Input>, crate::Error>>()?;
 
     let outputs = outputs
         .iter()
@@ -253,7 +344,7 @@ fn new_transaction(
                 proof: *proof,
             })
         })
-        .collect::<Result<Vec<Output>, anyhow::Error>>()?;
+        .collect::<Result<Vec<Output>, crate::Error>>()?;
 
     let excess = public_key_to_pedersen_commitment(&excess_pk);
 
@@ -272,10 +363,9 @@ fn new_transaction(
 }
 
 impl Execute for Fund {
-    type Wallet = Wallet;
-    type Return = u64;
+    type Wallet = dyn GrinWallet;
 
-    fn execute(self, wallet: &Self::Wallet) -> anyhow::Result<Self::Return> {
+    fn execute(self, wallet: &Self::Wallet) -> crate::Result<Receipt> {
         // Build invoice to pay to special output
         let (slate, r, blind_excess_keypair) = {
             let mut slate = Slate::blank(2);
@@ -286,14 +376,15 @@ impl Execute for Fund {
             slate.version_info.block_header_version = 3;
             slate.lock_height = 0;
 
-            let special_output = build_output(slate.amount, &self.special_input.1.secret_key)?;
+            let special_output =
+                build_output(slate.amount, self.special_input.1.expose_secret_key())?;
             slate.tx = slate.tx.with_output(special_output);
 
             let r = KeyPair::new_random();
 
             // Using zero offset for "internal" transaction
             let blind_excess =
-                compute_excess_sk(vec![], vec![&self.special_input.1.secret_key], None)?;
+                compute_excess_sk(vec![], vec![self.special_input.1.expose_secret_key()], None)?;
             let blind_excess_keypair = KeyPair::new(blind_excess);
 
             slate.participant_data = vec![ParticipantData {
@@ -316,7 +407,7 @@ impl Execute for Fund {
                 .participant_data
                 .iter()
                 .find(|p| p.id == 0)
-                .ok_or_else(|| anyhow::anyhow!("missing sender data"))?;
+                .ok_or_else(|| WalletError::Slate("missing sender data".into()))?;
 
             let (sig, excess) = crate::schnorr::sign_2p_1(
                 &blind_excess_keypair,
@@ -326,7 +417,7 @@ impl Execute for Fund {
                 &KernelFeatures::Plain { fee: slate.fee }.kernel_sig_msg()?,
                 &sender_data
                     .part_sig
-                    .ok_or_else(|| anyhow::anyhow!("missing sender partsig"))?
+                    .ok_or_else(|| WalletError::Slate("missing sender partsig".into()))?
                     .try_into()?,
             )?;
 
@@ -336,29 +427,33 @@ impl Execute for Fund {
             tx.body.kernels[0].excess_sig = sig;
 
             tx.body.kernels[0].verify().map_err(|e| {
-                anyhow::anyhow!("failed to verify grin fund transaction kernel: {}", e)
+                GrinError::InvalidTransaction(format!("fund transaction kernel: {}", e))
             })?;
 
             tx
         };
 
+        let excess = self.excess();
+        // The special output is spent into the output of the swap
+        let swap_amount = self.special_input.0 - self.transaction_from_special_input.fee();
+
         let aggregate_transaction = grin_core::core::transaction::aggregate(vec![
             transaction_from_funder_wallet_to_special_output,
             self.transaction_from_special_input,
         ])
-        .map_err(|e| anyhow::anyhow!("failed to aggregate fund transaction: {}", e))?;
+        .map_err(|e| GrinError::InvalidTransaction(format!("fund transaction: {}", e)))?;
 
-        let fee = aggregate_transaction.fee();
-        wallet.post_transaction(aggregate_transaction)?;
+        wallet.post_transaction(aggregate_transaction.clone())?;
 
-        Ok(fee)
+        Ok(Receipt::grin(&aggregate_transaction, &excess, vec![
+            swap_amount,
+        ]))
     }
 }
 
 impl Execute for Redeem {
-    type Wallet = Wallet;
-    type Return = u64;
-    fn execute(self, wallet: &Self::Wallet) -> anyhow::Result<Self::Return> {
+    type Wallet = dyn GrinWallet;
+    fn execute(self, wallet: &Self::Wallet) -> crate::Result<Receipt> {
         aggregate_with_spending_transaction(
             self.transaction_to_special_output,
             self.special_output,
@@ -369,9 +464,8 @@ impl Execute for Redeem {
 }
 
 impl Execute for Refund {
-    type Wallet = Wallet;
-    type Return = u64;
-    fn execute(self, wallet: &Self::Wallet) -> anyhow::Result<Self::Return> {
+    type Wallet = dyn GrinWallet;
+    fn execute(self, wallet: &Self::Wallet) -> crate::Result<Receipt> {
         aggregate_with_spending_transaction(
             self.transaction_to_special_output,
             self.special_output,
@@ -385,19 +479,45 @@ pub fn aggregate_with_spending_transaction(
     transaction_to_special_output: Transaction,
     special_output: (u64, KeyPair),
     wallet_transaction_fee: u64,
-    wallet: &Wallet,
-) -> anyhow::Result<u64> {
+    wallet: &dyn GrinWallet,
+) -> crate::Result<Receipt> {
+    let excess = transaction_to_special_output.kernels()[0].excess;
+    let swept_amount = special_output.0 - wallet_transaction_fee;
+
+    let transaction_from_special_input_to_wallet =
+        spending_transaction(special_output, wallet_transaction_fee, wallet)?;
+
+    let aggregate_transaction = grin_core::core::transaction::aggregate(vec![
+        transaction_to_special_output,
+        transaction_from_special_input_to_wallet,
+    ])
+    .map_err(|e| GrinError::InvalidTransaction(format!("refund transaction: {}", e)))?;
+
+    wallet.post_transaction(aggregate_transaction.clone())?;
+
+    Ok(Receipt::grin(&aggregate_transaction, &excess, vec![
+        swept_amount,
+    ]))
+}
+
+/// Build a transaction spending the special output into the wallet, by having
+/// the wallet issue an invoice which is paid with the special output.
+pub fn spending_transaction(
+    special_output: (u64, KeyPair),
+    wallet_transaction_fee: u64,
+    wallet: &dyn GrinWallet,
+) -> crate::Result<Transaction> {
     let mut slate = wallet.issue_invoice(special_output.0 - wallet_transaction_fee)?;
 
     slate.fee = wallet_transaction_fee;
     slate.update_kernel();
 
-    let special_input = build_input(special_output.0, &special_output.1.secret_key)?;
+    let special_input = build_input(special_output.0, special_output.1.expose_secret_key())?;
     slate.tx = slate.tx.with_input(special_input);
 
     let r = KeyPair::new_random();
 
-    let blind_excess = compute_excess_sk(vec![&special_output.1.secret_key], vec![], None)?;
+    let blind_excess = compute_excess_sk(vec![special_output.1.expose_secret_key()], vec![], None)?;
     let blind_excess_keypair = KeyPair::new(blind_excess);
 
     slate.participant_data.push(ParticipantData {
@@ -413,12 +533,11 @@ pub fn aggregate_with_spending_transaction(
         .participant_data
         .iter()
         .find(|p| p.id == 1)
-        .ok_or_else(|| anyhow::anyhow!("missing sender data"))?;
+        .ok_or_else(|| WalletError::Slate("missing sender data".into()))?;
 
-    // The aggregate transaction will contain another kernel which will be height
-    // locked according to the expiry defined in the offer. Therefore, there is no
-    // need to height lock the kernel corresponding to the other transaction
-    // involved
+    // The aggregate transaction will contain another kernel which will be locked
+    // according to the expiry defined in the offer. Therefore, there is no need to
+    // lock the kernel corresponding to the other transaction involved
     let partial_sig = schnorr::sign_2p_0(
         &blind_excess_keypair,
         &r,
@@ -433,16 +552,5 @@ pub fn aggregate_with_spending_transaction(
         }
     }
 
-    let transaction_from_special_input_to_wallet = wallet.finalize_invoice(slate)?;
-
-    let aggregate_transaction = grin_core::core::transaction::aggregate(vec![
-        transaction_to_special_output,
-        transaction_from_special_input_to_wallet,
-    ])
-    .map_err(|e| anyhow::anyhow!("failed to aggregate refund transaction: {}", e))?;
-
-    let fee = aggregate_transaction.fee();
-    wallet.post_transaction(aggregate_transaction)?;
-
-    Ok(fee)
+    wallet.finalize_invoice(slate)
 }
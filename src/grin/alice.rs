@@ -1,17 +1,18 @@
 use crate::{
     commit::CoinTossingKeys,
     grin::{
-        action, bulletproof, normalize_redeem_keys_alice, EncryptedSignature, Funder0, Funder1,
-        Funder2, KeyPair, Offer, PKs, Redeemer0, Redeemer1, Redeemer2, RedeemerSigs,
-        SpecialOutputKeyPairsFunder, SpecialOutputKeyPairsRedeemer, SpecialOutputs,
+        action, bulletproof, cooperative, event, normalize_redeem_keys_alice, EncryptedSignature,
+        Funder0, Funder1, Funder2, KeyPair, Offer, PKs, Redeemer0, Redeemer1, Redeemer2,
+        RedeemerSigs, SpecialOutputKeyPairsFunder, SpecialOutputKeyPairsRedeemer, SpecialOutputs,
     },
+    ledger,
+    transcript::Transcript,
 };
 use std::convert::TryInto;
 
 #[derive(Clone)]
 pub struct AliceFunder0 {
     pub common: Funder0,
-    pub bulletproof_common_nonce: bulletproof::CommonNonce,
     pub bulletproof_round_1_self: bulletproof::Round1,
 }
 
@@ -20,32 +21,45 @@ impl AliceFunder0 {
         offer: Offer,
         special_outputs: SpecialOutputs,
         special_output_keypairs_funder: SpecialOutputKeyPairsFunder,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let common = Funder0::new(offer, special_outputs, special_output_keypairs_funder);
-
-        let bulletproof_common_nonce =
-            bulletproof::CommonNonce::derive(&common.SKs_self.x.public_key)?;
-        let bulletproof_round_1_self = bulletproof::Round1::new(&common.SKs_self.x.secret_key)?;
+        let bulletproof_round_1_self =
+            bulletproof::Round1::new(common.SKs_self.x.expose_secret_key())?;
 
         Ok(Self {
             common,
-            bulletproof_common_nonce,
             bulletproof_round_1_self,
         })
     }
+}
+
+impl ledger::AliceFunder0 for AliceFunder0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = bulletproof::Round1;
+    type Next = AliceFunder1;
+
+    fn offer(&self) -> &Offer {
+        &self.common.offer
+    }
 
-    pub fn transition(
+    fn bulletproof_round_1(&self) -> bulletproof::Round1 {
+        self.bulletproof_round_1_self.clone()
+    }
+
+    fn transition(
         self,
         PKs_other: PKs,
         bulletproof_round_1_other: bulletproof::Round1,
-    ) -> anyhow::Result<AliceFunder1> {
+        transcript: &Transcript,
+    ) -> crate::Result<AliceFunder1> {
         Ok(AliceFunder1(Funder1 {
             offer: self.common.offer,
             special_outputs: self.common.special_outputs,
             special_output_keypairs_funder: self.common.special_output_keypairs_funder,
             SKs_self: self.common.SKs_self,
             PKs_other,
-            bulletproof_common_nonce: self.bulletproof_common_nonce,
+            transcript: transcript.clone(),
             bulletproof_round_1_self: self.bulletproof_round_1_self,
             bulletproof_round_1_other,
         }))
@@ -54,13 +68,16 @@ impl AliceFunder0 {
 
 pub struct AliceFunder1(pub Funder1);
 
-impl AliceFunder1 {
-    pub fn transition(
+impl ledger::AliceFunder1 for AliceFunder1 {
+    type RedeemerSigs = (RedeemerSigs, bulletproof::Round2);
+    type EncryptedSignature = EncryptedSignature;
+    type Next = AliceFunder2;
+
+    fn transition(
         self,
-        redeemer_sigs: RedeemerSigs,
+        (redeemer_sigs, bulletproof_round_2_other): (RedeemerSigs, bulletproof::Round2),
         y: &KeyPair,
-        bulletproof_round_2_other: bulletproof::Round2,
-    ) -> anyhow::Result<(AliceFunder2, EncryptedSignature)> {
+    ) -> crate::Result<(AliceFunder2, EncryptedSignature)> {
         let (state, redeem_encsig) =
             self.0
                 .transition(redeemer_sigs, &y.public_key, bulletproof_round_2_other)?;
@@ -74,7 +91,6 @@ pub struct AliceFunder2(pub Funder2);
 #[derive(Clone)]
 pub struct AliceRedeemer0 {
     pub common: Redeemer0,
-    pub bulletproof_common_nonce: bulletproof::CommonNonce,
     pub bulletproof_round_1_self: bulletproof::Round1,
 }
 
@@ -83,26 +99,40 @@ impl AliceRedeemer0 {
         offer: Offer,
         special_outputs: SpecialOutputs,
         special_output_keypairs_redeemer: SpecialOutputKeyPairsRedeemer,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let common = Redeemer0::new(offer, special_outputs, special_output_keypairs_redeemer);
-
-        let bulletproof_common_nonce =
-            bulletproof::CommonNonce::derive(&common.SKs_self.x.public_key)?;
-        let bulletproof_round_1_self = bulletproof::Round1::new(&common.SKs_self.x.secret_key)?;
+        let bulletproof_round_1_self =
+            bulletproof::Round1::new(common.SKs_self.x.expose_secret_key())?;
 
         Ok(Self {
             common,
-            bulletproof_common_nonce,
             bulletproof_round_1_self,
         })
     }
+}
+
+impl ledger::AliceRedeemer0 for AliceRedeemer0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = bulletproof::Round1;
+    type RedeemerSigs = (RedeemerSigs, bulletproof::Round2);
+    type Next = AliceRedeemer1;
+
+    fn offer(&self) -> &Offer {
+        &self.common.offer
+    }
+
+    fn bulletproof_round_1(&self) -> bulletproof::Round1 {
+        self.bulletproof_round_1_self.clone()
+    }
 
-    pub fn transition(
+    fn transition(
         mut self,
         mut PKs_other: PKs,
         mut y: &mut KeyPair,
         bulletproof_round_1_other: bulletproof::Round1,
-    ) -> anyhow::Result<(AliceRedeemer1, RedeemerSigs, bulletproof::Round2)> {
+        transcript: &Transcript,
+    ) -> crate::Result<(AliceRedeemer1, (RedeemerSigs, bulletproof::Round2))> {
         normalize_redeem_keys_alice(
             &mut self.common.SKs_self.r_redeem,
             &mut PKs_other.R_redeem,
@@ -110,11 +140,11 @@ impl AliceRedeemer0 {
         )?;
 
         let (state, redeemer_sigs, bulletproof_round_2_self) = self.common.transition(
-            self.bulletproof_common_nonce,
             self.bulletproof_round_1_self.clone(),
             bulletproof_round_1_other.clone(),
             PKs_other,
             y.public_key,
+            transcript,
         )?;
 
         Ok((
@@ -123,8 +153,7 @@ impl AliceRedeemer0 {
                 bulletproof_round_1_self: self.bulletproof_round_1_self,
                 bulletproof_round_1_other,
             },
-            redeemer_sigs,
-            bulletproof_round_2_self,
+            (redeemer_sigs, bulletproof_round_2_self),
         ))
     }
 }
@@ -135,24 +164,35 @@ pub struct AliceRedeemer1 {
     pub bulletproof_round_1_other: bulletproof::Round1,
 }
 
-impl AliceRedeemer1 {
-    pub fn transition(
+impl ledger::AliceRedeemer1 for AliceRedeemer1 {
+    type EncryptedSignature = EncryptedSignature;
+    type Next = AliceRedeemer2;
+
+    fn transition(
         self,
-        y: KeyPair,
         redeem_encsig: EncryptedSignature,
-    ) -> anyhow::Result<AliceRedeemer2> {
+        y: &KeyPair,
+    ) -> crate::Result<AliceRedeemer2> {
         let Redeemer2 {
             encrypted_redeem_action,
+            fund_event,
+            cooperative_refund,
         } = self.common.transition(y.public_key, redeem_encsig)?;
 
-        let redeem_action = encrypted_redeem_action.decrypt(&y)?;
+        let redeem_action = encrypted_redeem_action.decrypt(y)?;
 
-        Ok(AliceRedeemer2 { redeem_action })
+        Ok(AliceRedeemer2 {
+            redeem_action,
+            fund_event,
+            cooperative_refund,
+        })
     }
 }
 
 pub struct AliceRedeemer2 {
     pub redeem_action: action::Redeem,
+    pub fund_event: event::Fund,
+    pub cooperative_refund: cooperative::Redeemer,
 }
 
 impl Into<CoinTossingKeys> for AliceFunder0 {
@@ -169,9 +209,15 @@ impl Into<CoinTossingKeys> for AliceRedeemer0 {
     }
 }
 
+impl Into<CoinTossingKeys> for PKs {
+    fn into(self) -> CoinTossingKeys {
+        vec![self.X, self.R_fund, self.R_redeem, self.R_refund]
+    }
+}
+
 impl TryInto<PKs> for CoinTossingKeys {
-    type Error = anyhow::Error;
-    fn try_into(self) -> anyhow::Result<PKs> {
+    type Error = crate::Error;
+    fn try_into(self) -> crate::Result<PKs> {
         Ok(PKs {
             X: self[0],
             R_fund: self[1],
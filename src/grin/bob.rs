@@ -1,10 +1,12 @@
 use crate::{
     grin::{
-        action, bulletproof, event, normalize_redeem_keys_bob, EncryptedSignature, Funder0,
-        Funder1, Offer, PKs, PublicKey, Redeemer0, Redeemer1, Redeemer2, RedeemerSigs, SKs,
-        SpecialOutputKeyPairsFunder, SpecialOutputKeyPairsRedeemer, SpecialOutputs,
+        action, bulletproof, cooperative, event, normalize_redeem_keys_bob, EncryptedSignature,
+        Funder0, Funder1, Offer, PKs, PublicKey, Redeemer0, Redeemer1, Redeemer2, RedeemerSigs,
+        SKs, SpecialOutputKeyPairsFunder, SpecialOutputKeyPairsRedeemer, SpecialOutputs,
     },
+    ledger,
     schnorr::RecoveryKey,
+    transcript::Transcript,
 };
 use std::convert::TryFrom;
 
@@ -12,7 +14,6 @@ use std::convert::TryFrom;
 pub struct BobFunder0 {
     pub common: Funder0,
     pub bulletproof_round_1_self: bulletproof::Round1,
-    pub bulletproof_round_1_other: bulletproof::Round1,
 }
 
 impl BobFunder0 {
@@ -20,34 +21,55 @@ impl BobFunder0 {
         offer: Offer,
         special_outputs: SpecialOutputs,
         special_output_keypairs_funder: SpecialOutputKeyPairsFunder,
-        bulletproof_round_1_other: bulletproof::Round1,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let common = Funder0::new(offer, special_outputs, special_output_keypairs_funder);
-        let bulletproof_round_1_self = bulletproof::Round1::new(&common.SKs_self.x.secret_key)?;
+        let bulletproof_round_1_self =
+            bulletproof::Round1::new(common.SKs_self.x.expose_secret_key())?;
 
         Ok(Self {
             common,
             bulletproof_round_1_self,
-            bulletproof_round_1_other,
         })
     }
+}
+
+impl ledger::BobFunder0 for BobFunder0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = bulletproof::Round1;
+    type RedeemerSigs = (RedeemerSigs, bulletproof::Round2);
+    type EncryptedSignature = EncryptedSignature;
+    type Next = BobFunder1;
+
+    fn offer(&self) -> &Offer {
+        &self.common.offer
+    }
+
+    fn public_keys(&self) -> PKs {
+        self.common.SKs_self.clone().into()
+    }
+
+    fn bulletproof_round_1(&self) -> bulletproof::Round1 {
+        self.bulletproof_round_1_self.clone()
+    }
 
-    pub fn transition(
+    fn transition(
         self,
         PKs_other: PKs,
-        redeemer_sigs: RedeemerSigs,
+        (redeemer_sigs, bulletproof_round_2_other): (RedeemerSigs, bulletproof::Round2),
         Y: &PublicKey,
-        bulletproof_round_2_other: bulletproof::Round2,
-    ) -> anyhow::Result<(BobFunder1, EncryptedSignature)> {
+        bulletproof_round_1_other: bulletproof::Round1,
+        transcript: &Transcript,
+    ) -> crate::Result<(BobFunder1, EncryptedSignature)> {
         let state = Funder1 {
             offer: self.common.offer.clone(),
             special_outputs: self.common.special_outputs.clone(),
             special_output_keypairs_funder: self.common.special_output_keypairs_funder,
             SKs_self: self.common.SKs_self.clone(),
             PKs_other: PKs_other.clone(),
-            bulletproof_common_nonce: bulletproof::CommonNonce::derive(&PKs_other.X)?,
+            transcript: transcript.clone(),
             bulletproof_round_1_self: self.bulletproof_round_1_self,
-            bulletproof_round_1_other: self.bulletproof_round_1_other,
+            bulletproof_round_1_other,
         };
 
         let (state, redeem_encsig) =
@@ -60,8 +82,10 @@ impl BobFunder0 {
                 special_outputs: self.common.special_outputs,
                 SKs_self: self.common.SKs_self,
                 PKs_other,
+                transcript: transcript.clone(),
                 fund_action: state.fund_action,
                 refund_action: state.refund_action,
+                cooperative_refund: state.cooperative_refund,
                 recovery_key,
             },
             redeem_encsig,
@@ -73,22 +97,28 @@ pub struct BobFunder1 {
     special_outputs: SpecialOutputs,
     SKs_self: SKs,
     PKs_other: PKs,
+    transcript: Transcript,
     fund_action: action::Fund,
     refund_action: action::Refund,
+    cooperative_refund: cooperative::Funder,
     recovery_key: RecoveryKey,
 }
 
-impl BobFunder1 {
-    pub fn transition(self) -> anyhow::Result<BobFunder2> {
+impl ledger::BobFunder1 for BobFunder1 {
+    type Next = BobFunder2;
+
+    fn transition(self) -> crate::Result<BobFunder2> {
         let redeem_event = event::Redeem::new(
             &self.special_outputs,
             &self.PKs_other,
             &self.SKs_self.into(),
+            &self.transcript,
         )?;
 
         Ok(BobFunder2 {
             fund_action: self.fund_action,
             refund_action: self.refund_action,
+            cooperative_refund: self.cooperative_refund,
             recovery_key: self.recovery_key,
             redeem_event,
         })
@@ -98,6 +128,7 @@ impl BobFunder1 {
 pub struct BobFunder2 {
     pub fund_action: action::Fund,
     pub refund_action: action::Refund,
+    pub cooperative_refund: cooperative::Funder,
     pub recovery_key: RecoveryKey,
     pub redeem_event: event::Redeem,
 }
@@ -106,7 +137,6 @@ pub struct BobFunder2 {
 pub struct BobRedeemer0 {
     pub common: Redeemer0,
     pub bulletproof_round_1_self: bulletproof::Round1,
-    pub bulletproof_round_1_other: bulletproof::Round1,
 }
 
 impl BobRedeemer0 {
@@ -114,72 +144,92 @@ impl BobRedeemer0 {
         offer: Offer,
         special_outputs: SpecialOutputs,
         special_output_keypairs_redeemer: SpecialOutputKeyPairsRedeemer,
-        bulletproof_round_1_other: bulletproof::Round1,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let common = Redeemer0::new(offer, special_outputs, special_output_keypairs_redeemer);
-        let bulletproof_round_1_self = bulletproof::Round1::new(&common.SKs_self.x.secret_key)?;
+        let bulletproof_round_1_self =
+            bulletproof::Round1::new(common.SKs_self.x.expose_secret_key())?;
 
         Ok(Self {
             common,
             bulletproof_round_1_self,
-            bulletproof_round_1_other,
         })
     }
+}
 
-    pub fn transition(
+impl ledger::BobRedeemer0 for BobRedeemer0 {
+    type Offer = Offer;
+    type PKs = PKs;
+    type BulletproofRound1 = bulletproof::Round1;
+    type RedeemerSigs = (RedeemerSigs, bulletproof::Round2);
+    type Next = BobRedeemer1;
+
+    fn offer(&self) -> &Offer {
+        &self.common.offer
+    }
+
+    fn public_keys(&self) -> PKs {
+        self.common.SKs_self.clone().into()
+    }
+
+    fn bulletproof_round_1(&self) -> bulletproof::Round1 {
+        self.bulletproof_round_1_self.clone()
+    }
+
+    fn transition(
         mut self,
         mut PKs_other: PKs,
         mut Y: &mut PublicKey,
-    ) -> anyhow::Result<(BobRedeemer1, RedeemerSigs, bulletproof::Round2)> {
+        bulletproof_round_1_other: bulletproof::Round1,
+        transcript: &Transcript,
+    ) -> crate::Result<(BobRedeemer1, (RedeemerSigs, bulletproof::Round2))> {
         normalize_redeem_keys_bob(
             &mut PKs_other.R_redeem,
             &mut self.common.SKs_self.r_redeem,
             &mut Y,
         )?;
 
-        let bulletproof_common_nonce = bulletproof::CommonNonce::derive(&PKs_other.X)?;
         let (state, redeemer_sigs, bulletproof_round_2_self) = self.common.transition(
-            bulletproof_common_nonce,
             self.bulletproof_round_1_self,
-            self.bulletproof_round_1_other,
+            bulletproof_round_1_other,
             PKs_other,
             Y.clone(),
+            transcript,
         )?;
 
-        Ok((BobRedeemer1(state), redeemer_sigs, bulletproof_round_2_self))
+        Ok((
+            BobRedeemer1(state),
+            (redeemer_sigs, bulletproof_round_2_self),
+        ))
     }
 }
 
 pub struct BobRedeemer1(pub Redeemer1);
 
-impl BobRedeemer1 {
-    pub fn transition(
+impl ledger::BobRedeemer1 for BobRedeemer1 {
+    type EncryptedSignature = EncryptedSignature;
+    type Next = BobRedeemer2;
+
+    fn transition(
         self,
-        Y: PublicKey,
+        Y: &PublicKey,
         redeem_encsig: EncryptedSignature,
-    ) -> anyhow::Result<BobRedeemer2> {
+    ) -> crate::Result<BobRedeemer2> {
         let Redeemer2 {
             encrypted_redeem_action,
-        } = self.0.transition(Y, redeem_encsig)?;
+            fund_event,
+            cooperative_refund,
+        } = self.0.transition(*Y, redeem_encsig)?;
 
         Ok(BobRedeemer2 {
             encrypted_redeem_action,
+            fund_event,
+            cooperative_refund,
         })
     }
 }
 
 pub struct BobRedeemer2 {
     pub encrypted_redeem_action: action::EncryptedRedeem,
-}
-
-impl Into<PKs> for BobFunder0 {
-    fn into(self) -> PKs {
-        self.common.SKs_self.into()
-    }
-}
-
-impl Into<PKs> for BobRedeemer0 {
-    fn into(self) -> PKs {
-        self.common.SKs_self.into()
-    }
+    pub fund_event: event::Fund,
+    pub cooperative_refund: cooperative::Redeemer,
 }
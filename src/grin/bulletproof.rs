@@ -1,18 +1,21 @@
 use crate::{
+    error::CryptoError,
     grin::public_key_to_pedersen_commitment,
     keypair::{random_secret_key, PublicKey, SecretKey, SECP},
+    ledger::KeyExchangeRound,
+    transcript::Transcript,
+    Hash,
 };
+use blake2::{Blake2b, Digest};
 use secp256k1zkp::pedersen::RangeProof;
-use sha2::{Digest, Sha256};
 
 #[derive(Clone, Debug)]
 pub struct CommonNonce(SecretKey);
 
 impl CommonNonce {
-    pub fn derive(pk: &PublicKey) -> anyhow::Result<Self> {
-        let mut hasher = Sha256::new();
-        hasher.input(pk.0);
-        let sk = SecretKey::from_slice(&*SECP, &hasher.result())?;
+    /// Both parties derive the same nonce from the transcript of the swap.
+    pub fn derive(transcript: &Transcript) -> crate::Result<Self> {
+        let sk = transcript.challenge_scalar(b"grin/bulletproof_common_nonce")?;
         Ok(Self(sk))
     }
 }
@@ -23,9 +26,24 @@ pub struct Round1 {
     pub T_2: PublicKey,
 }
 
+impl_wire!(Round1 { T_1, T_2 });
+
+impl Hash for Round1 {
+    fn hash(&self) -> [u8; 64] {
+        let mut hasher = Blake2b::new();
+
+        hasher.input(self.T_1.0);
+        hasher.input(self.T_2.0);
+
+        let mut hash = [0u8; 64];
+        hash.copy_from_slice(&hasher.result());
+        hash
+    }
+}
+
 impl Round1 {
     /// To generate T_1 and T_2 for each party we hash their x_fund
-    pub fn new(private_nonce: &SecretKey) -> anyhow::Result<Self> {
+    pub fn new(private_nonce: &SecretKey) -> crate::Result<Self> {
         // --- BEGIN INIT UNUSED VALUES ---
         let value = 0;
         let blind = random_secret_key();
@@ -53,10 +71,18 @@ impl Round1 {
     }
 }
 
+impl KeyExchangeRound for Round1 {
+    fn append_to(&self, label: &'static [u8], transcript: &mut Transcript) {
+        transcript.append_hash(label, self);
+    }
+}
+
 pub struct Round2 {
     tau_x: SecretKey,
 }
 
+impl_wire!(Round2 { tau_x });
+
 impl Round2 {
     pub fn new(
         private_nonce: &SecretKey,
@@ -72,7 +98,7 @@ impl Round2 {
             T_1: T_1_F,
             T_2: T_2_F,
         }: &Round1,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let mut T_one = PublicKey::from_combination(&*SECP, vec![&T_1_R, &T_1_F])?;
         let mut T_two = PublicKey::from_combination(&*SECP, vec![&T_2_R, &T_2_F])?;
 
@@ -123,7 +149,7 @@ impl Round3 {
         }: &Round1,
         Round2 { tau_x: tau_x_R }: &Round2,
         Round2 { tau_x: tau_x_F }: &Round2,
-    ) -> anyhow::Result<Self> {
+    ) -> crate::Result<Self> {
         let mut T_one = PublicKey::from_combination(&*SECP, vec![&T_1_R, &T_1_F])?;
         let mut T_two = PublicKey::from_combination(&*SECP, vec![&T_2_R, &T_2_F])?;
 
@@ -153,9 +179,10 @@ impl Round3 {
                 Some(&private_nonce),
                 0,
             )
-            .ok_or_else(|| anyhow::anyhow!("failed to generate bulletproof"))?;
+            .ok_or(CryptoError::Calculate("bulletproof"))?;
 
-        SECP.verify_bullet_proof(commit, bulletproof, None)?;
+        SECP.verify_bullet_proof(commit, bulletproof, None)
+            .map_err(|_| CryptoError::InvalidProof("bulletproof"))?;
 
         Ok(Round3 { bulletproof })
     }
@@ -167,13 +194,13 @@ mod test {
     use crate::keypair::{KeyPair, Negate};
 
     #[test]
-    fn can_generate_multiparty_bulletproof() -> anyhow::Result<()> {
+    fn can_generate_multiparty_bulletproof() -> crate::Result<()> {
         let value = 1_000_000;
 
         let x_input = KeyPair::new_random();
 
         let x_alice = KeyPair::new_random();
-        let common_nonce = CommonNonce::derive(&x_alice.public_key)?;
+        let common_nonce = CommonNonce::derive(&Transcript::new(b"test"))?;
 
         let x_bob = KeyPair::new_random();
 
@@ -183,15 +210,15 @@ mod test {
             &x_input.public_key.negate(),
         ])?;
 
-        let mut x_bob_prime = x_bob.secret_key;
-        x_bob_prime.add_assign(&*SECP, &x_input.secret_key.negate())?;
+        let mut x_bob_prime = x_bob.expose_secret_key().clone();
+        x_bob_prime.add_assign(&*SECP, &x_input.expose_secret_key().negate())?;
 
-        let round1_alice = Round1::new(&x_alice.secret_key)?;
+        let round1_alice = Round1::new(x_alice.expose_secret_key())?;
         let round1_bob = Round1::new(&x_bob_prime)?;
 
         let round2_alice = Round2::new(
-            &x_alice.secret_key,
-            &x_alice.secret_key,
+            x_alice.expose_secret_key(),
+            x_alice.expose_secret_key(),
             &X,
             value,
             &common_nonce,
@@ -210,8 +237,8 @@ mod test {
         )?;
 
         assert!(Round3::new(
-            &x_alice.secret_key,
-            &x_alice.secret_key,
+            x_alice.expose_secret_key(),
+            x_alice.expose_secret_key(),
             &X,
             value,
             &common_nonce,
@@ -0,0 +1,213 @@
+//! Refunding the fund output of a swap before its expiry, with the help of the
+//! redeemer.
+//!
+//! The parties sign a transaction with the same inputs and outputs as the
+//! refund transaction, but under a plain kernel, so that it can be published at
+//! once. The funder proposes a nonce, the redeemer replies with their nonce and
+//! their partial signature and the funder completes the signature.
+
+use crate::{
+    error::GrinError,
+    grin::{
+        action, compute_excess_pk, compute_excess_sk, compute_offset, KernelFeatures, Offer, PKs,
+        SKs, SpecialOutputKeyPairsFunder, SpecialOutputs,
+    },
+    keypair::{random_secret_key, KeyPair, PublicKey, SECP},
+    schnorr,
+    transcript::Transcript,
+};
+
+// Sent by the funder
+pub struct Request {
+    pub R_funder: PublicKey,
+}
+
+// Sent by the redeemer
+pub struct Response {
+    pub R_redeemer: PublicKey,
+    pub s_redeemer: schnorr::PartialSignature,
+}
+
+pub struct Funder {
+    offer: Offer,
+    special_output_keypairs_funder: SpecialOutputKeyPairsFunder,
+    SKs_self: SKs,
+    PKs_other: PKs,
+    transcript: Transcript,
+}
+
+impl Funder {
+    pub fn new(
+        offer: Offer,
+        special_output_keypairs_funder: SpecialOutputKeyPairsFunder,
+        SKs_self: SKs,
+        PKs_other: PKs,
+        transcript: Transcript,
+    ) -> Self {
+        Self {
+            offer,
+            special_output_keypairs_funder,
+            SKs_self,
+            PKs_other,
+            transcript,
+        }
+    }
+
+    pub fn request(self) -> (FunderRequested, Request) {
+        let r = KeyPair::new_random();
+        let request = Request {
+            R_funder: r.public_key,
+        };
+
+        (FunderRequested { common: self, r }, request)
+    }
+}
+
+pub struct FunderRequested {
+    common: Funder,
+    r: KeyPair,
+}
+
+impl FunderRequested {
+    pub fn receive(self, response: Response) -> crate::Result<action::Refund> {
+        let Funder {
+            offer,
+            special_output_keypairs_funder,
+            SKs_self: funder_SKs,
+            PKs_other: redeemer_PKs,
+            transcript,
+        } = self.common;
+
+        let X =
+            PublicKey::from_combination(&*SECP, vec![&redeemer_PKs.X, &funder_SKs.x.public_key])?;
+
+        let offset = compute_offset(&transcript, &self.r.public_key, &response.R_redeemer)?;
+
+        let half_excess_keypair_funder = KeyPair::new(compute_excess_sk(
+            vec![funder_SKs.x.expose_secret_key()],
+            vec![special_output_keypairs_funder
+                .refund_output_key
+                .expose_secret_key()],
+            None,
+        )?);
+
+        let half_excess_pk_redeemer =
+            compute_excess_pk(vec![&redeemer_PKs.X], vec![], Some(&offset))?;
+
+        let kernel_features = kernel_features(&offer);
+
+        let (excess_sig, excess) = schnorr::sign_2p_1(
+            &half_excess_keypair_funder,
+            &self.r,
+            &half_excess_pk_redeemer,
+            &response.R_redeemer,
+            &kernel_features.kernel_sig_msg()?,
+            &response.s_redeemer,
+        )
+        .map_err(|_| GrinError::InvalidRedeemerSignature("cooperative refund"))?;
+
+        let bulletproof = special_output_keypairs_funder
+            .refund_output_proof
+            .unwrap_or_else(|| {
+                SECP.bullet_proof(
+                    offer.refund_output_amount(),
+                    special_output_keypairs_funder
+                        .refund_output_key
+                        .expose_secret_key()
+                        .clone(),
+                    random_secret_key(),
+                    random_secret_key(),
+                    None,
+                    None,
+                )
+            });
+
+        action::Refund::new(
+            vec![(offer.fund_output_amount(), X)],
+            vec![(
+                offer.refund_output_amount(),
+                special_output_keypairs_funder.refund_output_key.public_key,
+                bulletproof,
+            )],
+            excess,
+            excess_sig,
+            kernel_features,
+            offset,
+            (
+                offer.refund_output_amount(),
+                special_output_keypairs_funder.refund_output_key,
+            ),
+            offer.spend_fee(),
+        )
+    }
+}
+
+pub struct Redeemer {
+    offer: Offer,
+    special_outputs: SpecialOutputs,
+    SKs_self: SKs,
+    PKs_other: PKs,
+    transcript: Transcript,
+}
+
+impl Redeemer {
+    pub fn new(
+        offer: Offer,
+        special_outputs: SpecialOutputs,
+        SKs_self: SKs,
+        PKs_other: PKs,
+        transcript: Transcript,
+    ) -> Self {
+        Self {
+            offer,
+            special_outputs,
+            SKs_self,
+            PKs_other,
+            transcript,
+        }
+    }
+
+    /// Sign the cooperative refund requested by the funder.
+    ///
+    /// Once the signature is sent, the funder can take the fund output back at
+    /// any time. It must only be signed when the redeemer is certain not to
+    /// lose anything as a result, e.g. because they never funded the other
+    /// ledger or because their own refund there is already confirmed.
+    pub fn sign(&self, request: Request) -> crate::Result<Response> {
+        let r = KeyPair::new_random();
+
+        let offset = compute_offset(&self.transcript, &request.R_funder, &r.public_key)?;
+
+        let half_excess_keypair_redeemer = KeyPair::new(compute_excess_sk(
+            vec![self.SKs_self.x.expose_secret_key()],
+            vec![],
+            Some(&offset),
+        )?);
+
+        let half_excess_pk_funder = compute_excess_pk(
+            vec![&self.PKs_other.X],
+            vec![&self.special_outputs.refund_output_key],
+            None,
+        )?;
+
+        let s_redeemer = schnorr::sign_2p_0(
+            &half_excess_keypair_redeemer,
+            &r,
+            &half_excess_pk_funder,
+            &request.R_funder,
+            &kernel_features(&self.offer).kernel_sig_msg()?,
+        )?;
+
+        Ok(Response {
+            R_redeemer: r.public_key,
+            s_redeemer,
+        })
+    }
+}
+
+// Same fee as the refund kernel, but without the lock height
+fn kernel_features(offer: &Offer) -> KernelFeatures {
+    KernelFeatures::Plain {
+        fee: offer.refund_fee(),
+    }
+}
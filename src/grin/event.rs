@@ -1,19 +1,75 @@
-use crate::grin::{
-    compute_excess_pk, compute_offset, public_key_to_pedersen_commitment, PKs, SpecialOutputs,
+use crate::{
+    grin::{
+        compute_excess_pk, compute_offset, public_key_to_pedersen_commitment, Offer, PKs,
+        SpecialOutputs,
+    },
+    keypair::{PublicKey, SECP},
+    transcript::Transcript,
 };
 use secp256k1zkp::pedersen::Commitment;
 
+#[derive(Clone)]
+pub struct Fund {
+    pub excess: Commitment,
+    // The multiparty output `fund_output_amount*H + X`
+    pub output: Commitment,
+}
+
+impl_wire!(Fund { excess, output });
+
+impl Fund {
+    pub fn new(
+        offer: &Offer,
+        special_outputs: &SpecialOutputs,
+        redeemer_PKs: &PKs,
+        funder_PKs: &PKs,
+        transcript: &Transcript,
+    ) -> crate::Result<Self> {
+        let offset = compute_offset(transcript, &funder_PKs.R_fund, &redeemer_PKs.R_fund)?;
+
+        let excess_pk = compute_excess_pk(
+            vec![&special_outputs.fund_input_key],
+            vec![&redeemer_PKs.X, &funder_PKs.X],
+            Some(&offset),
+        )?;
+
+        let output = {
+            let X = PublicKey::from_combination(&*SECP, vec![&redeemer_PKs.X, &funder_PKs.X])?;
+
+            let commit_blind = public_key_to_pedersen_commitment(&X);
+            let commit_value = SECP.commit_value(offer.fund_output_amount())?;
+            SECP.commit_sum(vec![commit_blind, commit_value], Vec::new())?
+        };
+
+        Ok(Self {
+            excess: public_key_to_pedersen_commitment(&excess_pk),
+            output,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FundConfirmed {
+    pub kernel_height: u64,
+    pub output_height: u64,
+    pub confirmations: u64,
+}
+
+#[derive(Clone)]
 pub struct Redeem {
     pub excess: Commitment,
 }
 
+impl_wire!(Redeem { excess });
+
 impl Redeem {
     pub fn new(
         special_outputs: &SpecialOutputs,
         redeemer_PKs: &PKs,
         funder_PKs: &PKs,
-    ) -> anyhow::Result<Self> {
-        let offset = compute_offset(&funder_PKs.R_redeem, &redeemer_PKs.R_redeem)?;
+        transcript: &Transcript,
+    ) -> crate::Result<Self> {
+        let offset = compute_offset(transcript, &funder_PKs.R_redeem, &redeemer_PKs.R_redeem)?;
 
         let excess_pk = compute_excess_pk(
             vec![&redeemer_PKs.X, &funder_PKs.X],
@@ -0,0 +1,436 @@
+use crate::{
+    error::{Context, GrinError, WalletError},
+    grin::GrinWallet,
+};
+use grin_core::{
+    core::{Transaction, TxKernel},
+    ser::{ser_vec, ProtocolVersion},
+};
+use grin_wallet_impls::HTTPNodeClient;
+use grin_wallet_libwallet::{NodeClient, Slate, SlateVersion, TxWrapper, VersionedSlate};
+use secp256k1zkp::pedersen::Commitment;
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Stdin, Stdout, Write},
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A way of handing slates over to a grin-wallet which is run separately by
+/// the operator and of reading back the slates it produces.
+///
+/// Slates are in the V3 JSON format which grin-wallet 3 reads and writes with
+/// `-m file` and the `-i` option of `receive` and `finalize`.
+pub trait SlateExchange {
+    fn send(&self, name: &str, slate: &Slate) -> crate::Result<()>;
+    fn receive(&self, name: &str) -> crate::Result<Slate>;
+}
+
+/// Exchanges slates through files in a directory shared with the operator's
+/// grin-wallet.
+///
+/// A slate sent under `name` is written to `<dir>/<name>.tx`. The answer is
+/// expected at `<dir>/<name>.tx.response`, which is where grin-wallet writes
+/// its output by default. grin-wallet does not write it atomically, so it is
+/// read again until it holds a whole slate.
+pub struct FileExchange {
+    pub dir: PathBuf,
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+}
+
+impl FileExchange {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            poll_interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(60 * 60),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.tx", name))
+    }
+
+    fn response_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.tx.response", name))
+    }
+}
+
+impl SlateExchange for FileExchange {
+    // Written next to the slate and renamed, so that the slate is never seen
+    // half-written
+    fn send(&self, name: &str, slate: &Slate) -> crate::Result<()> {
+        let path = self.path(name);
+        let tmp_path = path.with_extension("tx.tmp");
+
+        fs::write(&tmp_path, serialize_slate(slate))
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .with_context(|| format!("failed to write slate to {:?}", path))
+    }
+
+    fn receive(&self, name: &str) -> crate::Result<Slate> {
+        let path = self.response_path(name);
+        let start = Instant::now();
+
+        loop {
+            let last_error = match fs::read_to_string(&path) {
+                Ok(json) => match deserialize_slate(&json) {
+                    Ok(slate) => return Ok(slate),
+                    // Still being written
+                    Err(e) => e.to_string(),
+                },
+                Err(e) => e.to_string(),
+            };
+
+            if start.elapsed() > self.timeout {
+                return Err(
+                    WalletError::SlateTimeout(format!("{:?} ({})", path, last_error)).into(),
+                );
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+/// Exchanges slates which the operator copies between this process and their
+/// grin-wallet, usually through the terminal. A pasted slate may span several
+/// lines.
+pub struct StdioExchange<R, W> {
+    input: Mutex<R>,
+    output: Mutex<W>,
+}
+
+impl<R, W> StdioExchange<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input: Mutex::new(input),
+            output: Mutex::new(output),
+        }
+    }
+}
+
+impl StdioExchange<BufReader<Stdin>, Stdout> {
+    pub fn stdio() -> Self {
+        Self::new(BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> SlateExchange for StdioExchange<R, W> {
+    fn send(&self, name: &str, slate: &Slate) -> crate::Result<()> {
+        let mut output = self.output.lock().expect("no panic while holding the lock");
+
+        writeln!(output, "Slate {}:", name)?;
+        writeln!(output, "{}", serialize_slate(slate))?;
+        output.flush()?;
+
+        Ok(())
+    }
+
+    fn receive(&self, name: &str) -> crate::Result<Slate> {
+        {
+            let mut output = self.output.lock().expect("no panic while holding the lock");
+            writeln!(output, "Paste the response to slate {}:", name)?;
+            output.flush()?;
+        }
+
+        let mut input = self.input.lock().expect("no panic while holding the lock");
+        let mut response = String::new();
+        loop {
+            if input.read_line(&mut response)? == 0 {
+                return Err(WalletError::Unavailable(format!(
+                    "input closed before the response to slate {}",
+                    name
+                ))
+                .into());
+            }
+
+            // The slate is complete once it is a whole JSON value
+            if serde_json::from_str::<serde_json::Value>(&response).is_ok() {
+                break;
+            }
+        }
+
+        deserialize_slate(response.trim())
+    }
+}
+
+/// For processes which only need the node, such as a watchtower: there is no
+/// wallet to exchange slates with.
+pub struct NoExchange;
+
+impl SlateExchange for NoExchange {
+    fn send(&self, name: &str, _: &Slate) -> crate::Result<()> {
+        Err(WalletError::Unavailable(format!("no wallet to send slate {} to", name)).into())
+    }
+
+    fn receive(&self, name: &str) -> crate::Result<Slate> {
+        Err(WalletError::Unavailable(format!("no wallet to receive slate {} from", name)).into())
+    }
+}
+
+/// A Grin wallet which is not under the control of this process.
+///
+/// Slates are handed to the operator through a `SlateExchange`, while the chain
+/// is queried and transactions are posted through the API of a Grin node.
+///
+/// The node does not know which outputs belong to the wallet, so its balance
+/// has to be declared by the operator with `with_balance`.
+///
+/// The operator is expected to:
+/// - pay invoices sent under the name `fund-<slate id>` with `grin-wallet pay`;
+/// - issue invoices with `grin-wallet invoice` and hand them back as the answer
+///   to the name `invoice-<amount>`;
+/// - finalize the slates sent under the name `finalize-<slate id>` with
+///   `grin-wallet finalize --nopost`.
+///
+/// It is critical that the finalized transaction is not posted by the
+/// operator's wallet, since it spends a special output which only exists once
+/// it is aggregated with the swap transaction.
+pub struct ExternalWallet<E> {
+    exchange: E,
+    node_client: HTTPNodeClient,
+    balance: Option<u64>,
+}
+
+impl<E: SlateExchange> ExternalWallet<E> {
+    pub fn new(exchange: E, node_url: &str, node_api_secret: Option<String>) -> Self {
+        Self {
+            exchange,
+            node_client: HTTPNodeClient::new(node_url, node_api_secret),
+            balance: None,
+        }
+    }
+
+    /// The grin the operator makes available to swaps from their wallet.
+    pub fn with_balance(self, balance: u64) -> Self {
+        Self {
+            balance: Some(balance),
+            ..self
+        }
+    }
+}
+
+impl<E: SlateExchange> GrinWallet for ExternalWallet<E> {
+    fn get_chain_tip(&self) -> crate::Result<u64> {
+        self.node_client
+            .get_chain_tip()
+            .map(|(tip, _)| tip)
+            .map_err(|e| GrinError::Node(format!("could not get chain tip: {}", e)).into())
+    }
+
+    fn process_invoice(&self, slate: Slate) -> crate::Result<Slate> {
+        let name = format!("fund-{}", slate.id);
+
+        self.exchange.send(&name, &slate)?;
+        let processed_slate = self.exchange.receive(&name)?;
+        check_slate_id(&slate, &processed_slate)?;
+
+        Ok(processed_slate)
+    }
+
+    fn issue_invoice(&self, amount: u64) -> crate::Result<Slate> {
+        let name = format!("invoice-{}", amount);
+        let slate = self.exchange.receive(&name)?;
+
+        if slate.amount != amount {
+            return Err(WalletError::Slate(format!(
+                "invoice amount {} does not match expected amount {}",
+                slate.amount, amount
+            ))
+            .into());
+        }
+
+        Ok(slate)
+    }
+
+    fn finalize_invoice(&self, slate: Slate) -> crate::Result<Transaction> {
+        let name = format!("finalize-{}", slate.id);
+
+        self.exchange.send(&name, &slate)?;
+        let finalized_slate = self.exchange.receive(&name)?;
+        check_slate_id(&slate, &finalized_slate)?;
+
+        Ok(finalized_slate.tx)
+    }
+
+    fn post_transaction(&self, transaction: Transaction) -> crate::Result<()> {
+        let tx_hex = grin_util::to_hex(
+            ser_vec(&transaction, ProtocolVersion::local())
+                .expect("serializing to a vector cannot fail"),
+        );
+
+        self.node_client
+            .post_tx(&TxWrapper { tx_hex }, false)
+            .map_err(|e| GrinError::Node(format!("could not post transaction: {}", e)).into())
+    }
+
+    fn get_balance(&self) -> crate::Result<u64> {
+        self.balance.ok_or_else(|| {
+            WalletError::Unavailable("no balance was declared for the external wallet".into())
+                .into()
+        })
+    }
+
+    fn find_kernel(&self, excess: &Commitment) -> crate::Result<TxKernel> {
+        // The node client requires a mutable reference even though querying
+        // for a kernel doesn't change its state
+        self.node_client
+            .clone()
+            .get_kernel(excess, None, None)
+            .map_err(|e| GrinError::Node(format!("failed to search for kernel: {}", e)))?
+            .map(|(kernel, ..)| kernel)
+            .ok_or_else(|| {
+                GrinError::Node(format!(
+                    "could not find kernel for commitment: {:?}",
+                    excess
+                ))
+                .into()
+            })
+    }
+
+    fn find_kernel_height(&self, excess: &Commitment) -> crate::Result<Option<u64>> {
+        self.node_client
+            .clone()
+            .get_kernel(excess, None, None)
+            .map(|kernel| kernel.map(|(_, height, _)| height))
+            .map_err(|e| GrinError::Node(format!("failed to search for kernel: {}", e)).into())
+    }
+
+    fn find_unspent_output_height(&self, commit: &Commitment) -> crate::Result<Option<u64>> {
+        self.node_client
+            .get_outputs_from_node(vec![*commit])
+            .map(|outputs| outputs.get(commit).map(|(_, height, _)| *height))
+            .map_err(|e| GrinError::Node(format!("failed to search for output: {}", e)).into())
+    }
+}
+
+fn serialize_slate(slate: &Slate) -> String {
+    let versioned_slate = VersionedSlate::into_version(slate.clone(), SlateVersion::V3);
+
+    serde_json::to_string(&versioned_slate).expect("slates serialize to JSON")
+}
+
+fn deserialize_slate(json: &str) -> crate::Result<Slate> {
+    Slate::deserialize_upgrade(json)
+        .map_err(|e| WalletError::Slate(format!("invalid slate: {}", e)).into())
+}
+
+// A slate coming back under another id belongs to another transaction, e.g.
+// a response pasted for the wrong prompt
+fn check_slate_id(sent: &Slate, received: &Slate) -> crate::Result<()> {
+    if sent.id != received.id {
+        return Err(WalletError::Slate(format!(
+            "received slate {} in response to slate {}",
+            received.id, sent.id
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::messages::SwapId;
+    use std::io::Cursor;
+
+    // Answers every slate with the same response
+    struct FixedExchange(Slate);
+
+    impl SlateExchange for FixedExchange {
+        fn send(&self, _: &str, _: &Slate) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn receive(&self, _: &str) -> crate::Result<Slate> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn stdio_exchange_reads_back_slate() -> crate::Result<()> {
+        let slate = Slate::blank(2);
+
+        let sending = StdioExchange::new(Cursor::new(Vec::new()), Vec::new());
+        sending.send("fund", &slate)?;
+        let sent = String::from_utf8(sending.output.into_inner().unwrap()).unwrap();
+
+        let response = sent.trim_start_matches("Slate fund:\n");
+        let receiving = StdioExchange::new(Cursor::new(response.as_bytes().to_vec()), Vec::new());
+        let received = receiving.receive("fund")?;
+
+        assert_eq!(received.id, slate.id);
+        assert_eq!(
+            String::from_utf8(receiving.output.into_inner().unwrap()).unwrap(),
+            "Paste the response to slate fund:\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn stdio_exchange_reads_slate_over_several_lines() -> crate::Result<()> {
+        let slate = Slate::blank(2);
+        let versioned_slate = VersionedSlate::into_version(slate.clone(), SlateVersion::V3);
+        let pasted = serde_json::to_string_pretty(&versioned_slate).unwrap();
+
+        let exchange = StdioExchange::new(Cursor::new(pasted.into_bytes()), Vec::new());
+
+        assert_eq!(exchange.receive("fund")?.id, slate.id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_exchange_waits_for_whole_response() -> crate::Result<()> {
+        let dir = std::env::temp_dir().join(format!("slates-{}", SwapId::random()));
+        fs::create_dir_all(&dir)?;
+        let exchange = FileExchange {
+            dir: dir.clone(),
+            poll_interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(10),
+        };
+
+        let slate = Slate::blank(2);
+        let json = serialize_slate(&slate);
+        let path = exchange.response_path("fund");
+        fs::write(&path, &json[..json.len() / 2])?;
+
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            fs::write(&path, json)
+        });
+        let received = exchange.receive("fund")?;
+        writer.join().expect("writer does not panic")?;
+
+        assert_eq!(received.id, slate.id);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn declared_balance_is_returned() {
+        let wallet = ExternalWallet::new(NoExchange, "http://localhost:1", None);
+        assert!(wallet.get_balance().is_err());
+
+        let wallet = wallet.with_balance(1_000);
+        assert_eq!(wallet.get_balance().unwrap(), 1_000);
+    }
+
+    #[test]
+    fn response_to_another_slate_is_rejected() {
+        let wallet = ExternalWallet::new(
+            FixedExchange(Slate::blank(2)),
+            "http://localhost:3413",
+            None,
+        );
+
+        assert!(wallet.process_invoice(Slate::blank(2)).is_err());
+        assert!(wallet.finalize_invoice(Slate::blank(2)).is_err());
+    }
+}
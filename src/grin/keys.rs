@@ -31,6 +31,13 @@ pub struct PKs {
     pub R_refund: PublicKey,
 }
 
+impl_wire!(PKs {
+    X,
+    R_fund,
+    R_redeem,
+    R_refund,
+});
+
 impl Hash for PKs {
     fn hash(&self) -> [u8; 64] {
         let mut hasher = Blake2b::new();
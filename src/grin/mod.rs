@@ -1,15 +1,21 @@
-use crate::keypair::{KeyPair, Negate, PublicKey, SecretKey, XCoor, YCoor, G, SECP};
+use crate::{
+    error::CryptoError,
+    keypair::{KeyPair, Negate, PublicKey, SecretKey, YCoor, G, SECP},
+    transcript::Transcript,
+};
 use secp256k1zkp::{key::ZERO_KEY, pedersen};
-use sha2::{Digest, Sha256};
 
 pub mod action;
 pub mod alice;
 pub mod bob;
 pub mod bulletproof;
+pub mod cooperative;
 pub mod event;
+pub mod external;
 pub mod keygen;
 pub mod keys;
 pub mod offer;
+pub mod recovery;
 pub mod sign;
 pub mod special_outputs;
 pub mod wallet;
@@ -18,18 +24,23 @@ pub use crate::{
     grin::{
         alice::*,
         bob::*,
+        external::{ExternalWallet, FileExchange, NoExchange, SlateExchange, StdioExchange},
         keygen::keygen,
         keys::{PKs, SKs},
-        offer::Offer,
+        offer::{Expiry, Offer},
         sign::FunderActions,
         special_outputs::*,
-        wallet::{Node, Wallet, Wallets},
+        wallet::{GrinWallet, Node, Wallet, Wallets},
     },
     schnorr::EncryptedSignature,
 };
 pub use grin_core::{
+    consensus::BLOCK_TIME_SEC,
     core::KernelFeatures,
-    libtx::aggsig::{add_signatures, calculate_partial_sig, verify_partial_sig},
+    libtx::{
+        aggsig::{add_signatures, calculate_partial_sig, verify_partial_sig},
+        DEFAULT_BASE_FEE,
+    },
 };
 pub use secp256k1zkp::Signature;
 pub use sign::RedeemerSigs;
@@ -65,7 +76,7 @@ pub struct Funder1 {
     pub special_output_keypairs_funder: SpecialOutputKeyPairsFunder,
     pub SKs_self: SKs,
     pub PKs_other: PKs,
-    pub bulletproof_common_nonce: bulletproof::CommonNonce,
+    pub transcript: Transcript,
     pub bulletproof_round_1_self: bulletproof::Round1,
     pub bulletproof_round_1_other: bulletproof::Round1,
 }
@@ -76,7 +87,7 @@ impl Funder1 {
         redeemer_sigs: RedeemerSigs,
         Y: &PublicKey,
         bulletproof_round_2_other: bulletproof::Round2,
-    ) -> anyhow::Result<(Funder2, EncryptedSignature)> {
+    ) -> crate::Result<(Funder2, EncryptedSignature)> {
         let (FunderActions { fund, refund }, redeem_encsig) = sign::funder(
             &self.offer,
             &self.special_outputs,
@@ -84,8 +95,8 @@ impl Funder1 {
             &self.SKs_self,
             &self.PKs_other,
             &Y,
+            &self.transcript,
             redeemer_sigs,
-            &self.bulletproof_common_nonce,
             &self.bulletproof_round_1_other,
             &self.bulletproof_round_1_self,
             &bulletproof_round_2_other,
@@ -94,6 +105,13 @@ impl Funder1 {
         let state = Funder2 {
             fund_action: fund,
             refund_action: refund,
+            cooperative_refund: cooperative::Funder::new(
+                self.offer,
+                self.special_output_keypairs_funder,
+                self.SKs_self,
+                self.PKs_other,
+                self.transcript,
+            ),
         };
 
         Ok((state, redeem_encsig))
@@ -103,6 +121,7 @@ impl Funder1 {
 pub struct Funder2 {
     pub fund_action: action::Fund,
     pub refund_action: action::Refund,
+    pub cooperative_refund: cooperative::Funder,
 }
 
 #[derive(Clone)]
@@ -131,12 +150,12 @@ impl Redeemer0 {
 
     pub fn transition(
         self,
-        bulletproof_common_nonce: bulletproof::CommonNonce,
         bulletproof_round_1_self: bulletproof::Round1,
         bulletproof_round_1_other: bulletproof::Round1,
         PKs_other: PKs,
         Y: PublicKey,
-    ) -> anyhow::Result<(Redeemer1, RedeemerSigs, bulletproof::Round2)> {
+        transcript: &Transcript,
+    ) -> crate::Result<(Redeemer1, RedeemerSigs, bulletproof::Round2)> {
         let (redeemer_sigs, bulletproof_round_2_self) = sign::redeemer(
             &self.offer,
             &self.special_outputs,
@@ -144,7 +163,7 @@ impl Redeemer0 {
             &self.SKs_self,
             &PKs_other,
             &Y,
-            &bulletproof_common_nonce,
+            transcript,
             &bulletproof_round_1_self,
             &bulletproof_round_1_other,
         )?;
@@ -155,6 +174,7 @@ impl Redeemer0 {
             special_output_keypairs_redeemer: self.special_output_keypairs_redeemer,
             SKs_self: self.SKs_self,
             PKs_other,
+            transcript: transcript.clone(),
         };
 
         Ok((state, redeemer_sigs, bulletproof_round_2_self))
@@ -167,6 +187,7 @@ pub struct Redeemer1 {
     pub special_output_keypairs_redeemer: SpecialOutputKeyPairsRedeemer,
     pub SKs_self: SKs,
     pub PKs_other: PKs,
+    pub transcript: Transcript,
 }
 
 impl Redeemer1 {
@@ -174,7 +195,23 @@ impl Redeemer1 {
         self,
         Y: PublicKey,
         redeem_encsig: EncryptedSignature,
-    ) -> anyhow::Result<Redeemer2> {
+    ) -> crate::Result<Redeemer2> {
+        let fund_event = event::Fund::new(
+            &self.offer,
+            &self.special_outputs,
+            &self.SKs_self.clone().into(),
+            &self.PKs_other,
+            &self.transcript,
+        )?;
+
+        let cooperative_refund = cooperative::Redeemer::new(
+            self.offer.clone(),
+            self.special_outputs.clone(),
+            self.SKs_self.clone(),
+            self.PKs_other.clone(),
+            self.transcript.clone(),
+        );
+
         let encrypted_redeem_action = action::EncryptedRedeem::new(
             self.offer,
             self.special_outputs,
@@ -183,30 +220,35 @@ impl Redeemer1 {
             self.PKs_other,
             Y,
             redeem_encsig,
+            &self.transcript,
         )?;
 
         Ok(Redeemer2 {
             encrypted_redeem_action,
+            fund_event,
+            cooperative_refund,
         })
     }
 }
 
 pub struct Redeemer2 {
     pub encrypted_redeem_action: action::EncryptedRedeem,
+    pub fund_event: event::Fund,
+    pub cooperative_refund: cooperative::Redeemer,
 }
 
 pub fn compute_excess_sk(
     inputs: Vec<&SecretKey>,
     outputs: Vec<&SecretKey>,
     offset: Option<&SecretKey>,
-) -> anyhow::Result<SecretKey> {
+) -> crate::Result<SecretKey> {
     // TODO: Since this lets you use ZERO_KEY I don't see why you need the match
     // statements you should be able to do it all sequentially? Or is it that
     // add_assign will work if self is ZERO_KEY but not the argument? (that
     // would be dumb).
     let mut total = match (inputs.clone(), outputs.clone()) {
         (inputs, outputs) if inputs.is_empty() && outputs.is_empty() => {
-            return Err(anyhow::anyhow!("invalid arguments"))
+            return Err(CryptoError::Calculate("excess without inputs or outputs").into())
         }
         (inputs, outputs) if inputs.is_empty() && !outputs.is_empty() => {
             let mut total = ZERO_KEY;
@@ -253,10 +295,10 @@ pub fn compute_excess_pk(
     inputs: Vec<&PublicKey>,
     outputs: Vec<&PublicKey>,
     offset: Option<&SecretKey>,
-) -> anyhow::Result<PublicKey> {
+) -> crate::Result<PublicKey> {
     let total = match (inputs.clone(), outputs.clone()) {
         (inputs, outputs) if inputs.is_empty() && outputs.is_empty() => {
-            return Err(anyhow::anyhow!("invalid arguments"))
+            return Err(CryptoError::Calculate("excess without inputs or outputs").into())
         }
         (inputs, outputs) if inputs.is_empty() && !outputs.is_empty() => {
             PublicKey::from_combination(&*SECP, outputs)?
@@ -286,23 +328,26 @@ pub fn compute_excess_pk(
     }
 }
 
-pub fn compute_offset(funder_R: &PublicKey, redeemer_R: &PublicKey) -> anyhow::Result<SecretKey> {
-    let mut hasher = Sha256::default();
-
+pub fn compute_offset(
+    transcript: &Transcript,
+    funder_R: &PublicKey,
+    redeemer_R: &PublicKey,
+) -> crate::Result<SecretKey> {
     // NOTE: the offset is any random value known only to the two parties so we
-    // just hash two nonces together. Other parties should never discover this
-    // representation of R.
-    hasher.input(&funder_R.x_coor());
-    hasher.input(&redeemer_R.x_coor());
+    // just hash two nonces together with the transcript of the swap. Other
+    // parties should never discover this representation of R.
+    let mut transcript = transcript.clone();
+    transcript.append_public_key(b"funder_R", funder_R);
+    transcript.append_public_key(b"redeemer_R", redeemer_R);
 
-    Ok(SecretKey::from_slice(&*SECP, &hasher.result())?)
+    transcript.challenge_scalar(b"grin/offset")
 }
 
 pub fn normalize_redeem_keys_alice(
     r0: &mut KeyPair,
     R1: &mut PublicKey,
     y: &mut KeyPair,
-) -> anyhow::Result<()> {
+) -> crate::Result<()> {
     let R = PublicKey::from_combination(&*SECP, vec![&r0.public_key, &R1, &y.public_key])?;
     let mut R_y = purerust_secp256k1::curve::Field::default();
     assert!(R_y.set_b32(&R.y_coor()));
@@ -325,7 +370,7 @@ pub fn normalize_redeem_keys_bob(
     R0: &mut PublicKey,
     r1: &mut KeyPair,
     Y: &mut PublicKey,
-) -> anyhow::Result<()> {
+) -> crate::Result<()> {
     let R = PublicKey::from_combination(&*SECP, vec![&R0, &r1.public_key, &Y])?;
     let mut R_y = purerust_secp256k1::curve::Field::default();
     assert!(R_y.set_b32(&R.y_coor()));
@@ -388,7 +433,7 @@ mod test {
     fn to_commitment_vs_commit() {
         let x = KeyPair::new_random();
         let ours = public_key_to_pedersen_commitment(&x.public_key);
-        let theirs = SECP.commit(0, x.secret_key).unwrap();
+        let theirs = SECP.commit(0, x.expose_secret_key().clone()).unwrap();
 
         assert_eq!(theirs, ours);
     }
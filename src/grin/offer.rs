@@ -1,24 +1,190 @@
+use crate::{
+    expiry::RefundLock,
+    grin::KernelFeatures,
+    ledger::LedgerOffer,
+    wire::{malformed, Decode, Encode},
+    Hash,
+};
+use blake2::{Blake2b, Digest};
+use grin_core::libtx::tx_fee;
+
 #[derive(Debug, Clone)]
 pub struct Offer {
     pub asset: u64,
-    pub fee: u64, // for simplicity we don't model separate fee values for different transactions
-    pub expiry: u64, // block height
+    pub base_fee: u64, // per unit of transaction weight
+    pub expiry: Expiry,
+}
+
+impl_wire!(Offer {
+    asset,
+    base_fee,
+    expiry,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expiry {
+    // The refund becomes valid at this block height
+    Absolute(u64),
 }
 
+// Every transaction built during the swap has the same shape: it spends one
+// (special) input into one (special) output under one kernel. Its fee is
+// computed from that weight and carried by its own kernel.
+//
+// The funder pays for all the fees involved in the swap on top of the asset
+// with the exception of the fee of the transaction which moves the asset out
+// of their wallet, since it is computed by the wallet itself
 impl Offer {
     pub fn fund_input_amount(&self) -> u64 {
-        self.asset + (2 * self.fee)
+        self.fund_output_amount() + self.fund_fee()
     }
 
+    // Redeem and refund transactions have the same weight, so the fund output
+    // covers the fees of either of them
     pub fn fund_output_amount(&self) -> u64 {
-        self.asset + self.fee
+        self.redeem_output_amount() + self.redeem_fee()
     }
 
     pub fn redeem_output_amount(&self) -> u64 {
-        self.asset
+        self.asset + self.spend_fee()
     }
 
     pub fn refund_output_amount(&self) -> u64 {
+        self.asset + self.spend_fee()
+    }
+
+    pub fn fund_fee(&self) -> u64 {
+        self.swap_transaction_fee()
+    }
+
+    pub fn redeem_fee(&self) -> u64 {
+        self.swap_transaction_fee()
+    }
+
+    pub fn refund_fee(&self) -> u64 {
+        self.swap_transaction_fee()
+    }
+
+    // Fee of the wallet transaction which spends the special output resulting
+    // from a redeem or refund
+    pub fn spend_fee(&self) -> u64 {
+        self.swap_transaction_fee()
+    }
+
+    pub fn fund_kernel_features(&self) -> KernelFeatures {
+        KernelFeatures::Plain {
+            fee: self.fund_fee(),
+        }
+    }
+
+    pub fn redeem_kernel_features(&self) -> KernelFeatures {
+        KernelFeatures::Plain {
+            fee: self.redeem_fee(),
+        }
+    }
+
+    pub fn refund_kernel_features(&self) -> KernelFeatures {
+        match self.expiry {
+            Expiry::Absolute(lock_height) => KernelFeatures::HeightLocked {
+                fee: self.refund_fee(),
+                lock_height,
+            },
+        }
+    }
+
+    fn swap_transaction_fee(&self) -> u64 {
+        tx_fee(1, 1, 1, Some(self.base_fee))
+    }
+}
+
+impl LedgerOffer for Offer {
+    fn asset(&self) -> u64 {
         self.asset
     }
+
+    fn with_asset(self, asset: u64) -> Self {
+        Self { asset, ..self }
+    }
+
+    fn refund_lock(&self) -> RefundLock {
+        match self.expiry {
+            Expiry::Absolute(height) => RefundLock::Height(height),
+        }
+    }
+}
+
+impl Hash for Offer {
+    fn hash(&self) -> [u8; 64] {
+        let mut hasher = Blake2b::new();
+
+        hasher.input(b"grin");
+        hasher.input(self.asset.to_be_bytes());
+        hasher.input(self.base_fee.to_be_bytes());
+        match self.expiry {
+            Expiry::Absolute(height) => {
+                hasher.input([0u8]);
+                hasher.input(height.to_be_bytes());
+            }
+        }
+
+        let mut hash = [0u8; 64];
+        hash.copy_from_slice(&hasher.result());
+        hash
+    }
+}
+
+impl Encode for Expiry {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Expiry::Absolute(height) => {
+                0u8.encode(buf);
+                height.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for Expiry {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(Expiry::Absolute(u64::decode(bytes)?)),
+            other => Err(malformed(format!("unknown Grin expiry {}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn amounts_cover_weight_based_fees() {
+        let offer = Offer {
+            asset: 10_000_000_000,
+            base_fee: 1_000_000,
+            expiry: Expiry::Absolute(0),
+        };
+
+        assert_eq!(
+            offer.fund_input_amount() - offer.fund_output_amount(),
+            offer.fund_fee()
+        );
+        assert_eq!(
+            offer.fund_output_amount() - offer.redeem_output_amount(),
+            offer.redeem_fee()
+        );
+        assert_eq!(
+            offer.fund_output_amount() - offer.refund_output_amount(),
+            offer.refund_fee()
+        );
+        assert_eq!(
+            offer.redeem_output_amount() - offer.asset,
+            offer.spend_fee()
+        );
+
+        // after cut-through, a redeem or refund aggregated with the wallet
+        // transaction has one input, one output and two kernels
+        assert!(offer.redeem_fee() + offer.spend_fee() >= tx_fee(1, 1, 2, Some(offer.base_fee)));
+        assert!(offer.refund_fee() + offer.spend_fee() >= tx_fee(1, 1, 2, Some(offer.base_fee)));
+    }
 }
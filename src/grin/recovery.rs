@@ -0,0 +1,200 @@
+use crate::{
+    error::RequestError,
+    grin::{
+        action::{aggregate_with_spending_transaction, spending_transaction},
+        wallet::build_output,
+        GrinWallet,
+    },
+    keypair::KeyPair,
+    Receipt,
+};
+use grin_core::core::Transaction;
+
+/// The secrets and public parameters needed to get Grin out of a special
+/// output, without the rest of the state of the swap.
+///
+/// It should be exported by the caller as soon as the transaction paying to the
+/// special output is known, and kept until the special output is spent. The
+/// wire encoding is the export format.
+#[derive(Debug, Clone)]
+pub struct RecoveryData {
+    pub special_output: (u64, KeyPair),
+    /// The swap transaction paying to the special output, if it is one of
+    /// ours.
+    pub transaction_to_special_output: Option<Transaction>,
+    pub wallet_transaction_fee: u64,
+}
+
+impl_wire!(RecoveryData {
+    special_output,
+    transaction_to_special_output,
+    wallet_transaction_fee,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stranded {
+    /// The special output was published on its own and can be spent into the
+    /// wallet.
+    SpecialOutput { height: u64 },
+    /// The inputs of the swap transaction are still unspent, so it can be
+    /// published together with a transaction spending the special output into
+    /// the wallet.
+    SwapTransaction,
+}
+
+impl RecoveryData {
+    /// Look for funds which can be recovered on the chain.
+    pub fn scan(&self, wallet: &dyn GrinWallet) -> crate::Result<Option<Stranded>> {
+        let (amount, keypair) = &self.special_output;
+        let special_output = build_output(*amount, keypair.expose_secret_key())?;
+
+        if let Some(height) = wallet.find_unspent_output_height(&special_output.commit)? {
+            return Ok(Some(Stranded::SpecialOutput { height }));
+        }
+
+        if let Some(transaction) = &self.transaction_to_special_output {
+            for input in transaction.inputs() {
+                if wallet.find_unspent_output_height(&input.commit)?.is_none() {
+                    return Ok(None);
+                }
+            }
+
+            return Ok(Some(Stranded::SwapTransaction));
+        }
+
+        Ok(None)
+    }
+
+    /// Spend whatever was found by `scan` into the wallet.
+    ///
+    /// Publishing a swap transaction will fail if its kernel is locked until a
+    /// height which has not been reached yet.
+    pub fn sweep(self, wallet: &dyn GrinWallet) -> crate::Result<Receipt> {
+        match self.scan(wallet)? {
+            Some(Stranded::SpecialOutput { .. }) => {
+                let swept_amount = self.special_output.0 - self.wallet_transaction_fee;
+                let transaction =
+                    spending_transaction(self.special_output, self.wallet_transaction_fee, wallet)?;
+                let excess = transaction.kernels()[0].excess;
+
+                wallet.post_transaction(transaction.clone())?;
+
+                Ok(Receipt::grin(&transaction, &excess, vec![swept_amount]))
+            }
+            Some(Stranded::SwapTransaction) => aggregate_with_spending_transaction(
+                self.transaction_to_special_output
+                    .expect("only found if there is a swap transaction"),
+                self.special_output,
+                self.wallet_transaction_fee,
+                wallet,
+            ),
+            None => Err(RequestError::Refused("no Grin funds to recover".into()).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        error::WalletError,
+        wire::{from_bytes, to_bytes},
+    };
+    use grin_core::core::TxKernel;
+    use grin_wallet_libwallet::{ParticipantData, Slate};
+    use secp256k1zkp::pedersen::Commitment;
+    use std::cell::RefCell;
+
+    // Sees a single unspent output on the chain and finalizes every invoice it
+    // issues as is
+    struct StubWallet {
+        unspent_output: Commitment,
+        posted: RefCell<Vec<Transaction>>,
+    }
+
+    impl GrinWallet for StubWallet {
+        fn get_chain_tip(&self) -> crate::Result<u64> {
+            Ok(100)
+        }
+
+        fn process_invoice(&self, _: Slate) -> crate::Result<Slate> {
+            Err(WalletError::Unavailable("not used in this test".into()).into())
+        }
+
+        fn issue_invoice(&self, amount: u64) -> crate::Result<Slate> {
+            let mut slate = Slate::blank(2);
+            slate.amount = amount;
+            slate.participant_data.push(ParticipantData {
+                id: 1,
+                public_blind_excess: KeyPair::new_random().public_key,
+                public_nonce: KeyPair::new_random().public_key,
+                part_sig: None,
+                message: None,
+                message_sig: None,
+            });
+
+            Ok(slate)
+        }
+
+        fn finalize_invoice(&self, slate: Slate) -> crate::Result<Transaction> {
+            Ok(slate.tx)
+        }
+
+        fn post_transaction(&self, transaction: Transaction) -> crate::Result<()> {
+            self.posted.borrow_mut().push(transaction);
+            Ok(())
+        }
+
+        fn get_balance(&self) -> crate::Result<u64> {
+            Err(WalletError::Unavailable("not used in this test".into()).into())
+        }
+
+        fn find_kernel(&self, _: &Commitment) -> crate::Result<TxKernel> {
+            Err(WalletError::Unavailable("not used in this test".into()).into())
+        }
+
+        fn find_kernel_height(&self, _: &Commitment) -> crate::Result<Option<u64>> {
+            Ok(None)
+        }
+
+        fn find_unspent_output_height(&self, commit: &Commitment) -> crate::Result<Option<u64>> {
+            Ok(if *commit == self.unspent_output {
+                Some(90)
+            } else {
+                None
+            })
+        }
+    }
+
+    #[test]
+    fn special_output_is_swept_with_imported_data() -> crate::Result<()> {
+        let amount = 10_000_000_000;
+        let keypair = KeyPair::new_random();
+        let wallet = StubWallet {
+            unspent_output: build_output(amount, keypair.expose_secret_key())?.commit,
+            posted: RefCell::new(Vec::new()),
+        };
+
+        let exported = to_bytes(&RecoveryData {
+            special_output: (amount, keypair.clone()),
+            transaction_to_special_output: None,
+            wallet_transaction_fee: 1_000_000,
+        });
+        drop(keypair);
+
+        let imported: RecoveryData = from_bytes(&exported)?;
+        assert_eq!(
+            imported.scan(&wallet)?,
+            Some(Stranded::SpecialOutput { height: 90 })
+        );
+
+        let receipt = imported.sweep(&wallet)?;
+
+        let posted = wallet.posted.borrow();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].inputs()[0].commit, wallet.unspent_output);
+        assert_eq!(receipt.amounts, vec![amount - 1_000_000]);
+
+        Ok(())
+    }
+}
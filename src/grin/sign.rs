@@ -1,11 +1,12 @@
 use crate::{
+    error::GrinError,
     grin::{
-        action, bulletproof, compute_excess_pk, compute_excess_sk, compute_offset, KernelFeatures,
-        Offer, PKs, SKs, SpecialOutputKeyPairsFunder, SpecialOutputKeyPairsRedeemer,
-        SpecialOutputs,
+        action, bulletproof, compute_excess_pk, compute_excess_sk, compute_offset, Offer, PKs, SKs,
+        SpecialOutputKeyPairsFunder, SpecialOutputKeyPairsRedeemer, SpecialOutputs,
     },
     keypair::{random_secret_key, KeyPair, PublicKey, SECP},
     schnorr,
+    transcript::Transcript,
 };
 
 pub struct RedeemerSigs {
@@ -14,6 +15,12 @@ pub struct RedeemerSigs {
     pub s_hat_redeem: schnorr::PartialEncryptedSignature,
 }
 
+impl_wire!(RedeemerSigs {
+    s_fund,
+    s_refund,
+    s_hat_redeem,
+});
+
 #[allow(clippy::too_many_arguments)]
 pub fn redeemer(
     offer: &Offer,
@@ -22,16 +29,22 @@ pub fn redeemer(
     redeemer_SKs: &SKs,
     funder_PKs: &PKs,
     Y: &PublicKey,
-    bulletproof_common_nonce: &bulletproof::CommonNonce,
+    transcript: &Transcript,
     bulletproof_round_1_redeemer: &bulletproof::Round1,
     bulletproof_round_1_funder: &bulletproof::Round1,
-) -> anyhow::Result<(RedeemerSigs, bulletproof::Round2)> {
+) -> crate::Result<(RedeemerSigs, bulletproof::Round2)> {
+    let bulletproof_common_nonce = bulletproof::CommonNonce::derive(transcript)?;
+
     let (s_fund, bulletproof_round_2_redeemer) = {
-        let offset = compute_offset(&funder_PKs.R_fund, &redeemer_SKs.r_fund.public_key)?;
+        let offset = compute_offset(
+            transcript,
+            &funder_PKs.R_fund,
+            &redeemer_SKs.r_fund.public_key,
+        )?;
 
         let half_excess_keypair_redeemer = KeyPair::new(compute_excess_sk(
             vec![],
-            vec![&redeemer_SKs.x.secret_key],
+            vec![redeemer_SKs.x.expose_secret_key()],
             Some(&offset),
         )?);
 
@@ -47,7 +60,7 @@ pub fn redeemer(
                 &redeemer_SKs.r_fund,
                 &half_excess_pk_funder,
                 &funder_PKs.R_fund,
-                &KernelFeatures::Plain { fee: 0 }.kernel_sig_msg()?,
+                &offer.fund_kernel_features().kernel_sig_msg()?,
             )?
         };
 
@@ -58,8 +71,8 @@ pub fn redeemer(
             ])?;
 
             bulletproof::Round2::new(
-                &redeemer_SKs.x.secret_key,
-                &redeemer_SKs.x.secret_key,
+                redeemer_SKs.x.expose_secret_key(),
+                redeemer_SKs.x.expose_secret_key(),
                 &excess_pk,
                 offer.fund_output_amount(),
                 &bulletproof_common_nonce,
@@ -72,11 +85,18 @@ pub fn redeemer(
     };
 
     let s_refund = {
-        let offset = compute_offset(&funder_PKs.R_refund, &redeemer_SKs.r_refund.public_key)?;
+        let offset = compute_offset(
+            transcript,
+            &funder_PKs.R_refund,
+            &redeemer_SKs.r_refund.public_key,
+        )?;
 
         let half_excess_keypair_redeemer = {
-            let half_excess_sk_redeemer =
-                compute_excess_sk(vec![&redeemer_SKs.x.secret_key], vec![], Some(&offset))?;
+            let half_excess_sk_redeemer = compute_excess_sk(
+                vec![redeemer_SKs.x.expose_secret_key()],
+                vec![],
+                Some(&offset),
+            )?;
             KeyPair::new(half_excess_sk_redeemer)
         };
 
@@ -91,24 +111,22 @@ pub fn redeemer(
             &redeemer_SKs.r_refund,
             &half_excess_pk_funder,
             &funder_PKs.R_refund,
-            &KernelFeatures::HeightLocked {
-                fee: 0,
-                lock_height: offer.expiry,
-            }
-            .kernel_sig_msg()?,
+            &offer.refund_kernel_features().kernel_sig_msg()?,
         )?
     };
 
     let s_hat_redeem = {
-        let offset = compute_offset(&funder_PKs.R_redeem, &redeemer_SKs.r_redeem.public_key)?;
+        let offset = compute_offset(
+            transcript,
+            &funder_PKs.R_redeem,
+            &redeemer_SKs.r_redeem.public_key,
+        )?;
 
         let half_excess_keypair_redeemer = KeyPair::new(compute_excess_sk(
-            vec![&redeemer_SKs.x.secret_key],
-            vec![
-                &special_output_keypairs_redeemer
-                    .redeem_output_key
-                    .secret_key,
-            ],
+            vec![redeemer_SKs.x.expose_secret_key()],
+            vec![special_output_keypairs_redeemer
+                .redeem_output_key
+                .expose_secret_key()],
             Some(&offset),
         )?);
 
@@ -120,7 +138,7 @@ pub fn redeemer(
             &half_excess_pk_funder,
             &funder_PKs.R_redeem,
             &Y,
-            &KernelFeatures::Plain { fee: 0 }.kernel_sig_msg()?,
+            &offer.redeem_kernel_features().kernel_sig_msg()?,
         )?
     };
 
@@ -134,16 +152,6 @@ pub fn redeemer(
     ))
 }
 
-#[derive(Debug, Clone, thiserror::Error)]
-pub enum RedeemerSignatureError {
-    #[error("fund")]
-    Fund,
-    #[error("redeem")]
-    Redeem,
-    #[error("refund")]
-    Refund,
-}
-
 pub struct FunderActions {
     pub fund: action::Fund,
     pub refund: action::Refund,
@@ -157,31 +165,38 @@ pub fn funder(
     funder_SKs: &SKs,
     redeemer_PKs: &PKs,
     Y: &PublicKey,
+    transcript: &Transcript,
     RedeemerSigs {
         s_fund: s_fund_redeemer,
         s_refund: s_refund_redeemer,
         s_hat_redeem: s_hat_redeem_redeemer,
     }: RedeemerSigs,
-    bulletproof_common_nonce: &bulletproof::CommonNonce,
     bulletproof_round_1_redeemer: &bulletproof::Round1,
     bulletproof_round_1_funder: &bulletproof::Round1,
     bulletproof_round_2_redeemer: &bulletproof::Round2,
-) -> anyhow::Result<(FunderActions, schnorr::EncryptedSignature)> {
+) -> crate::Result<(FunderActions, schnorr::EncryptedSignature)> {
+    let bulletproof_common_nonce = bulletproof::CommonNonce::derive(transcript)?;
     let X = PublicKey::from_combination(&*SECP, vec![&redeemer_PKs.X, &funder_SKs.x.public_key])?;
 
     let fund = {
-        let offset = compute_offset(&funder_SKs.r_fund.public_key, &redeemer_PKs.R_fund)?;
+        let offset = compute_offset(
+            transcript,
+            &funder_SKs.r_fund.public_key,
+            &redeemer_PKs.R_fund,
+        )?;
 
         let half_excess_keypair_funder = KeyPair::new(compute_excess_sk(
-            vec![&special_output_keypairs_funder.fund_input_key.secret_key],
-            vec![&funder_SKs.x.secret_key],
+            vec![special_output_keypairs_funder
+                .fund_input_key
+                .expose_secret_key()],
+            vec![funder_SKs.x.expose_secret_key()],
             None,
         )?);
 
         let half_excess_pk_redeemer =
             compute_excess_pk(vec![], vec![&redeemer_PKs.X], Some(&offset))?;
 
-        let kernel_features = KernelFeatures::Plain { fee: 0 };
+        let kernel_features = offer.fund_kernel_features();
 
         let (excess_sig, excess_pk) = schnorr::sign_2p_1(
             &half_excess_keypair_funder,
@@ -191,12 +206,12 @@ pub fn funder(
             &kernel_features.kernel_sig_msg()?,
             &s_fund_redeemer,
         )
-        .map_err(|_| RedeemerSignatureError::Fund)?;
+        .map_err(|_| GrinError::InvalidRedeemerSignature("fund"))?;
 
         let bulletproof = {
             let bulletproof_round_2_funder = bulletproof::Round2::new(
-                &funder_SKs.x.secret_key,
-                &funder_SKs.x.secret_key,
+                funder_SKs.x.expose_secret_key(),
+                funder_SKs.x.expose_secret_key(),
                 &X,
                 offer.fund_output_amount(),
                 &bulletproof_common_nonce,
@@ -204,8 +219,8 @@ pub fn funder(
                 &bulletproof_round_1_funder,
             )?;
             bulletproof::Round3::new(
-                &funder_SKs.x.secret_key,
-                &funder_SKs.x.secret_key,
+                funder_SKs.x.expose_secret_key(),
+                funder_SKs.x.expose_secret_key(),
                 &X,
                 offer.fund_output_amount(),
                 &bulletproof_common_nonce,
@@ -219,7 +234,7 @@ pub fn funder(
 
         action::Fund::new(
             vec![(
-                offer.fund_output_amount(),
+                offer.fund_input_amount(),
                 special_output_keypairs_funder.fund_input_key.public_key,
             )],
             vec![(offer.fund_output_amount(), X, bulletproof)],
@@ -228,28 +243,31 @@ pub fn funder(
             kernel_features,
             offset,
             (
-                offer.fund_output_amount(),
+                offer.fund_input_amount(),
                 special_output_keypairs_funder.fund_input_key.clone(),
             ),
         )?
     };
 
     let refund = {
-        let offset = compute_offset(&funder_SKs.r_refund.public_key, &redeemer_PKs.R_refund)?;
+        let offset = compute_offset(
+            transcript,
+            &funder_SKs.r_refund.public_key,
+            &redeemer_PKs.R_refund,
+        )?;
 
         let half_excess_keypair_funder = KeyPair::new(compute_excess_sk(
-            vec![&funder_SKs.x.secret_key],
-            vec![&special_output_keypairs_funder.refund_output_key.secret_key],
+            vec![funder_SKs.x.expose_secret_key()],
+            vec![special_output_keypairs_funder
+                .refund_output_key
+                .expose_secret_key()],
             None,
         )?);
 
         let half_excess_pk_redeemer =
             compute_excess_pk(vec![&redeemer_PKs.X], vec![], Some(&offset))?;
 
-        let kernel_features = KernelFeatures::HeightLocked {
-            fee: 0,
-            lock_height: offer.expiry,
-        };
+        let kernel_features = offer.refund_kernel_features();
 
         let (excess_sig, excess) = schnorr::sign_2p_1(
             &half_excess_keypair_funder,
@@ -259,24 +277,28 @@ pub fn funder(
             &kernel_features.kernel_sig_msg()?,
             &s_refund_redeemer,
         )
-        .map_err(|_| RedeemerSignatureError::Refund)?;
-
-        let bulletproof = SECP.bullet_proof(
-            offer.fund_output_amount(),
-            special_output_keypairs_funder
-                .refund_output_key
-                .secret_key
-                .clone(),
-            random_secret_key(),
-            random_secret_key(),
-            None,
-            None,
-        );
+        .map_err(|_| GrinError::InvalidRedeemerSignature("refund"))?;
+
+        let bulletproof = special_output_keypairs_funder
+            .refund_output_proof
+            .unwrap_or_else(|| {
+                SECP.bullet_proof(
+                    offer.refund_output_amount(),
+                    special_output_keypairs_funder
+                        .refund_output_key
+                        .expose_secret_key()
+                        .clone(),
+                    random_secret_key(),
+                    random_secret_key(),
+                    None,
+                    None,
+                )
+            });
 
         action::Refund::new(
             vec![(offer.fund_output_amount(), X)],
             vec![(
-                offer.fund_output_amount(),
+                offer.refund_output_amount(),
                 special_output_keypairs_funder.refund_output_key.public_key,
                 bulletproof,
             )],
@@ -285,18 +307,22 @@ pub fn funder(
             kernel_features,
             offset,
             (
-                offer.fund_output_amount(),
+                offer.refund_output_amount(),
                 special_output_keypairs_funder.refund_output_key.clone(),
             ),
-            offer.fee,
+            offer.spend_fee(),
         )?
     };
 
     let encsign_redeem = {
-        let offset = compute_offset(&funder_SKs.r_redeem.public_key, &redeemer_PKs.R_redeem)?;
+        let offset = compute_offset(
+            transcript,
+            &funder_SKs.r_redeem.public_key,
+            &redeemer_PKs.R_redeem,
+        )?;
 
         let half_excess_keypair_funder = KeyPair::new(compute_excess_sk(
-            vec![&funder_SKs.x.secret_key],
+            vec![funder_SKs.x.expose_secret_key()],
             vec![],
             None,
         )?);
@@ -313,10 +339,10 @@ pub fn funder(
             &half_excess_pk_redeemer,
             &redeemer_PKs.R_redeem,
             &Y,
-            &KernelFeatures::Plain { fee: 0 }.kernel_sig_msg()?,
+            &offer.redeem_kernel_features().kernel_sig_msg()?,
             &s_hat_redeem_redeemer,
         )
-        .map_err(|_| RedeemerSignatureError::Redeem)?
+        .map_err(|_| GrinError::InvalidRedeemerSignature("redeem"))?
     };
 
     Ok((FunderActions { fund, refund }, encsign_redeem))
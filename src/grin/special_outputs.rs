@@ -1,4 +1,14 @@
-use crate::{KeyPair, PublicKey};
+use crate::{
+    error::{CryptoError, StorageError},
+    grin::Offer,
+    KeyPair, PublicKey,
+};
+use grin_core::libtx::proof::{self, ProofBuilder};
+use grin_keychain::{ExtKeychain, ExtKeychainPath, Identifier, Keychain, SwitchCommitmentType};
+use rand::Rng;
+use secp256k1zkp::pedersen::RangeProof;
+use std::fmt;
+use zeroize::Zeroize;
 
 /// Special outputs allow the signing phase to occur without knowledge of the
 /// actual wallet outputs of either party. They can be generated locally and
@@ -12,10 +22,20 @@ pub struct SpecialOutputs {
     pub refund_output_key: PublicKey,
 }
 
+impl_wire!(SpecialOutputs {
+    fund_input_key,
+    redeem_output_key,
+    refund_output_key,
+});
+
 #[derive(Debug, Clone)]
 pub struct SpecialOutputKeyPairsFunder {
     pub fund_input_key: KeyPair,
     pub refund_output_key: KeyPair,
+    // Built with the proof builder of the swap seed so that a wallet restored
+    // from it identifies the refund output as its own. A random bulletproof is
+    // used if absent
+    pub refund_output_proof: Option<RangeProof>,
 }
 
 impl SpecialOutputKeyPairsFunder {
@@ -23,19 +43,222 @@ impl SpecialOutputKeyPairsFunder {
         Self {
             fund_input_key: KeyPair::new_random(),
             refund_output_key: KeyPair::new_random(),
+            refund_output_proof: None,
         }
     }
+
+    pub fn derive(seed: &SwapSeed, swap_index: u32, offer: &Offer) -> crate::Result<Self> {
+        let keychain = seed.keychain()?;
+        let fund_input_key = derive_keypair(&keychain, swap_index, SpecialOutput::FundInput)?;
+        let (refund_output_key, refund_output_proof) = derive_keypair_and_proof(
+            &keychain,
+            swap_index,
+            SpecialOutput::RefundOutput,
+            offer.refund_output_amount(),
+        )?;
+
+        Ok(Self {
+            fund_input_key,
+            refund_output_key,
+            refund_output_proof: Some(refund_output_proof),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct SpecialOutputKeyPairsRedeemer {
     pub redeem_output_key: KeyPair,
+    // Built with the proof builder of the swap seed so that a wallet restored
+    // from it identifies the redeem output as its own. A random bulletproof is
+    // used if absent
+    pub redeem_output_proof: Option<RangeProof>,
 }
 
 impl SpecialOutputKeyPairsRedeemer {
     pub fn new_random() -> Self {
         Self {
             redeem_output_key: KeyPair::new_random(),
+            redeem_output_proof: None,
         }
     }
+
+    pub fn derive(seed: &SwapSeed, swap_index: u32, offer: &Offer) -> crate::Result<Self> {
+        let (redeem_output_key, redeem_output_proof) = derive_keypair_and_proof(
+            &seed.keychain()?,
+            swap_index,
+            SpecialOutput::RedeemOutput,
+            offer.redeem_output_amount(),
+        )?;
+
+        Ok(Self {
+            redeem_output_key,
+            redeem_output_proof: Some(redeem_output_proof),
+        })
+    }
+}
+
+/// Seed of the keychain from which the special outputs of every swap are
+/// derived. It is kept apart from the seed of the Grin wallet, which the swap
+/// never needs to know, and is wiped from memory once dropped.
+pub struct SwapSeed([u8; 32]);
+
+impl SwapSeed {
+    pub fn new_random() -> Self {
+        Self(rand::thread_rng().gen())
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> crate::Result<Self> {
+        if bytes.len() != 32 {
+            return Err(StorageError::Corrupted(format!(
+                "swap seed of {} bytes instead of 32",
+                bytes.len()
+            ))
+            .into());
+        }
+
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(bytes);
+
+        Ok(Self(seed))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    // Only built for the duration of a derivation
+    fn keychain(&self) -> crate::Result<ExtKeychain> {
+        ExtKeychain::from_seed(&self.0, false)
+            .map_err(|_| CryptoError::Calculate("swap keychain").into())
+    }
+}
+
+impl fmt::Debug for SwapSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SwapSeed(<redacted>)")
+    }
+}
+
+impl Drop for SwapSeed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+// Arbitrary account index under which all special output keys are derived, so
+// that they never collide with the keys of regular wallet outputs
+pub const SPECIAL_OUTPUTS_ACCOUNT: u32 = 0x7377_6170;
+
+#[derive(Debug, Clone, Copy)]
+pub enum SpecialOutput {
+    FundInput = 0,
+    RedeemOutput = 1,
+    RefundOutput = 2,
+}
+
+/// Special output keys are derived at `m/SPECIAL_OUTPUTS_ACCOUNT/swap_index/n`
+/// where `n` identifies the special output within the swap.
+pub fn special_output_key_id(swap_index: u32, special_output: SpecialOutput) -> Identifier {
+    ExtKeychainPath::new(
+        3,
+        SPECIAL_OUTPUTS_ACCOUNT,
+        swap_index,
+        special_output as u32,
+        0,
+    )
+    .to_identifier()
+}
+
+pub fn derive_keypair(
+    keychain: &ExtKeychain,
+    swap_index: u32,
+    special_output: SpecialOutput,
+) -> crate::Result<KeyPair> {
+    // Without switch commitments the derived key does not depend on the amount
+    let secret_key = keychain
+        .derive_key(
+            0,
+            &special_output_key_id(swap_index, special_output),
+            &SwitchCommitmentType::None,
+        )
+        .map_err(|_| CryptoError::Calculate("special output key"))?;
+
+    Ok(KeyPair::new(secret_key))
+}
+
+fn derive_keypair_and_proof(
+    keychain: &ExtKeychain,
+    swap_index: u32,
+    special_output: SpecialOutput,
+    amount: u64,
+) -> crate::Result<(KeyPair, RangeProof)> {
+    let key_id = special_output_key_id(swap_index, special_output);
+    let keypair = derive_keypair(keychain, swap_index, special_output)?;
+
+    let commit = keychain
+        .commit(amount, &key_id, &SwitchCommitmentType::None)
+        .map_err(|_| CryptoError::Calculate("special output commitment"))?;
+    let proof = proof::create(
+        keychain,
+        &ProofBuilder::new(keychain),
+        amount,
+        &key_id,
+        &SwitchCommitmentType::None,
+        commit,
+        None,
+    )
+    .map_err(|_| CryptoError::Calculate("special output bulletproof"))?;
+
+    Ok((keypair, proof))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_derives_same_special_outputs() -> crate::Result<()> {
+        let offer = Offer {
+            asset: 10_000_000_000,
+            base_fee: crate::grin::DEFAULT_BASE_FEE,
+            expiry: crate::grin::Expiry::Absolute(1_440),
+        };
+        let seed = SwapSeed::new_random();
+        let copy = SwapSeed::from_slice(seed.as_bytes())?;
+
+        let first = SpecialOutputKeyPairsFunder::derive(&seed, 42, &offer)?;
+        let second = SpecialOutputKeyPairsFunder::derive(&copy, 42, &offer)?;
+        assert_eq!(
+            first.refund_output_key.public_key,
+            second.refund_output_key.public_key
+        );
+
+        let other = SpecialOutputKeyPairsFunder::derive(&SwapSeed::new_random(), 42, &offer)?;
+        assert_ne!(
+            first.refund_output_key.public_key,
+            other.refund_output_key.public_key
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn derivation_is_deterministic() {
+        let keychain = ExtKeychain::from_random_seed(false).unwrap();
+
+        let first = derive_keypair(&keychain, 42, SpecialOutput::RefundOutput).unwrap();
+        let second = derive_keypair(&keychain, 42, SpecialOutput::RefundOutput).unwrap();
+
+        assert_eq!(first.public_key, second.public_key);
+    }
+
+    #[test]
+    fn different_swaps_derive_different_keys() {
+        let keychain = ExtKeychain::from_random_seed(false).unwrap();
+
+        let first = derive_keypair(&keychain, 42, SpecialOutput::RefundOutput).unwrap();
+        let second = derive_keypair(&keychain, 43, SpecialOutput::RefundOutput).unwrap();
+
+        assert_ne!(first.public_key, second.public_key);
+    }
 }
@@ -1,4 +1,5 @@
 use crate::{
+    error::{Context, GrinError, WalletError},
     grin::{event, Signature},
     keypair::{random_secret_key, SECP},
     look_for::LookFor,
@@ -23,7 +24,7 @@ pub struct Node {
 }
 
 impl Node {
-    pub fn start() -> anyhow::Result<(Self, Wallets)> {
+    pub fn start() -> crate::Result<(Self, Wallets)> {
         let chain_dir = "target/test_output/";
 
         let _ = std::fs::remove_dir_all(chain_dir);
@@ -37,8 +38,9 @@ impl Node {
         for id in vec!["node", "funder", "redeemer"].iter() {
             let node_client = LocalWalletClient::new(id, wallet_proxy.tx.clone());
             let mut wallet = Box::new(
-                DefaultWalletImpl::<LocalWalletClient>::new(node_client.clone())
-                    .map_err(|e| anyhow::anyhow!("failed to instantiate Grin wallet: {}", e))?,
+                DefaultWalletImpl::<LocalWalletClient>::new(node_client.clone()).map_err(|e| {
+                    WalletError::Unavailable(format!("failed to instantiate Grin wallet: {}", e))
+                })?,
             )
                 as Box<
                     dyn WalletInst<
@@ -48,17 +50,26 @@ impl Node {
                     >,
                 >;
             let lc_provider = wallet.lc_provider().map_err(|e| {
-                anyhow::anyhow!("failed to get stored instance of lifecycle provider: {}", e)
+                WalletError::Unavailable(format!(
+                    "failed to get stored instance of lifecycle provider: {}",
+                    e
+                ))
             })?;
             lc_provider
                 .set_top_level_directory(&format!("{}/{}", "target/test_output/", id))
-                .map_err(|e| anyhow::anyhow!("failed to set top level directory: {}", e))?;
+                .map_err(|e| {
+                    WalletError::Unavailable(format!("failed to set top level directory: {}", e))
+                })?;
             lc_provider
                 .create_wallet(None, None, 32, ZeroingString::from(""), false)
-                .map_err(|e| anyhow::anyhow!("failed to create Grin wallet: {}", e))?;
+                .map_err(|e| {
+                    WalletError::Unavailable(format!("failed to create Grin wallet: {}", e))
+                })?;
             let mask = lc_provider
                 .open_wallet(None, ZeroingString::from(""), false, false)
-                .map_err(|e| anyhow::anyhow!("failed to open Grin wallet: {}", e))?;
+                .map_err(|e| {
+                    WalletError::Unavailable(format!("failed to open Grin wallet: {}", e))
+                })?;
 
             let wallet = Arc::new(Mutex::new(wallet));
             wallet_proxy.add_wallet(
@@ -105,14 +116,14 @@ impl Node {
     }
 
     // 1 block reward (60 grin) is spendable after 4 blocks have been mined
-    fn award_60_grin(&self, wallet: &Wallet) -> anyhow::Result<()> {
+    fn award_60_grin(&self, wallet: &Wallet) -> crate::Result<()> {
         award_block_to_wallet(
             wallet.chain.as_ref(),
             Vec::new(),
             wallet.inner.clone(),
             wallet.mask.as_ref(),
         )
-        .map_err(|e| anyhow::anyhow!("could not award grin to wallet: {}", e))?;
+        .map_err(|e| WalletError::Unavailable(format!("could not award grin to wallet: {}", e)))?;
 
         for _ in 1..4 {
             award_blocks_to_wallet(
@@ -122,7 +133,9 @@ impl Node {
                 3,
                 false,
             )
-            .map_err(|e| anyhow::anyhow!("could not award grin to wallet: {}", e))?;
+            .map_err(|e| {
+                WalletError::Unavailable(format!("could not award grin to wallet: {}", e))
+            })?;
         }
 
         Ok(())
@@ -157,15 +170,32 @@ pub struct Wallet {
     chain: Arc<Chain>,
 }
 
-impl Wallet {
-    pub fn get_chain_tip(&self) -> anyhow::Result<u64> {
+/// The slate operations required to execute the Grin side of a swap.
+///
+/// Abstracting over them allows the actions in `grin::action` to be executed
+/// against any wallet, be it the one provided by the Grin test framework, a
+/// wallet accessed over HTTP or a custodial wallet service.
+pub trait GrinWallet {
+    fn get_chain_tip(&self) -> crate::Result<u64>;
+    fn process_invoice(&self, slate: Slate) -> crate::Result<Slate>;
+    fn issue_invoice(&self, amount: u64) -> crate::Result<Slate>;
+    fn finalize_invoice(&self, slate: Slate) -> crate::Result<Transaction>;
+    fn post_transaction(&self, transaction: Transaction) -> crate::Result<()>;
+    fn get_balance(&self) -> crate::Result<u64>;
+    fn find_kernel(&self, excess: &Commitment) -> crate::Result<TxKernel>;
+    fn find_kernel_height(&self, excess: &Commitment) -> crate::Result<Option<u64>>;
+    fn find_unspent_output_height(&self, commit: &Commitment) -> crate::Result<Option<u64>>;
+}
+
+impl GrinWallet for Wallet {
+    fn get_chain_tip(&self) -> crate::Result<u64> {
         self.node_client
             .get_chain_tip()
             .map(|(tip, _)| tip)
-            .map_err(|e| anyhow::anyhow!("could not get Grin chain tip: {}", e))
+            .map_err(|e| GrinError::Node(format!("could not get chain tip: {}", e)).into())
     }
 
-    pub fn process_invoice(&self, slate: Slate) -> anyhow::Result<Slate> {
+    fn process_invoice(&self, slate: Slate) -> crate::Result<Slate> {
         let mut processed_slate = Slate::blank(2);
         grin_wallet_controller::controller::owner_single_use(
             self.inner.clone(),
@@ -186,9 +216,10 @@ impl Wallet {
             },
         )
         .map(|_| processed_slate)
-        .map_err(|e| anyhow::anyhow!("could not process invoice: {}", e))
+        .map_err(|e| WalletError::Slate(format!("could not process invoice: {}", e)).into())
     }
-    pub fn post_transaction(&self, transaction: Transaction) -> anyhow::Result<()> {
+
+    fn post_transaction(&self, transaction: Transaction) -> crate::Result<()> {
         grin_wallet_controller::controller::owner_single_use(
             self.inner.clone(),
             self.mask.as_ref(),
@@ -197,10 +228,10 @@ impl Wallet {
                 Ok(())
             },
         )
-        .map_err(|e| anyhow::anyhow!("could not post transaction: {}", e))
+        .map_err(|e| GrinError::Node(format!("could not post transaction: {}", e)).into())
     }
 
-    pub fn issue_invoice(&self, amount: u64) -> anyhow::Result<Slate> {
+    fn issue_invoice(&self, amount: u64) -> crate::Result<Slate> {
         let mut invoice_slate = Slate::blank(2);
         grin_wallet_controller::controller::owner_single_use(
             self.inner.clone(),
@@ -215,10 +246,10 @@ impl Wallet {
             },
         )
         .map(|_| invoice_slate)
-        .map_err(|e| anyhow::anyhow!("could not issue invoice: {}", e))
+        .map_err(|e| WalletError::Slate(format!("could not issue invoice: {}", e)).into())
     }
 
-    pub fn finalize_invoice(&self, slate: Slate) -> anyhow::Result<Transaction> {
+    fn finalize_invoice(&self, slate: Slate) -> crate::Result<Transaction> {
         let mut finalized_slate = Slate::blank(2);
         grin_wallet_controller::controller::foreign_single_use(
             self.inner.clone(),
@@ -229,39 +260,86 @@ impl Wallet {
             },
         )
         .map(|_| finalized_slate.tx)
-        .map_err(|e| anyhow::anyhow!("could not finalize invoice: {}", e))
+        .map_err(|e| WalletError::Slate(format!("could not finalize invoice: {}", e)).into())
     }
 
-    pub fn get_balance(&self) -> anyhow::Result<u64> {
+    fn get_balance(&self) -> crate::Result<u64> {
         wallet_info(self.inner.clone(), self.mask.as_ref())
             .map(|info| info.amount_currently_spendable)
-            .map_err(|e| anyhow::anyhow!("failed to access wallet balance: {}", e))
+            .map_err(|e| {
+                WalletError::Unavailable(format!("failed to access wallet balance: {}", e)).into()
+            })
     }
 
-    pub fn find_kernel(&self, excess: &Commitment) -> anyhow::Result<TxKernel> {
+    fn find_kernel(&self, excess: &Commitment) -> crate::Result<TxKernel> {
         self.chain
             .get_kernel_height(&excess, None, None)
-            .map_err(|e| anyhow::anyhow!("failed to search for kernel: {}", e))?
+            .map_err(|e| GrinError::Node(format!("failed to search for kernel: {}", e)))?
             .map(|(kernel, ..)| kernel)
-            .ok_or_else(|| anyhow::anyhow!("could not find kernel for commitment: {:?}", excess))
+            .ok_or_else(|| {
+                GrinError::Node(format!(
+                    "could not find kernel for commitment: {:?}",
+                    excess
+                ))
+                .into()
+            })
+    }
+
+    fn find_kernel_height(&self, excess: &Commitment) -> crate::Result<Option<u64>> {
+        self.chain
+            .get_kernel_height(&excess, None, None)
+            .map(|kernel| kernel.map(|(_, height, _)| height))
+            .map_err(|e| GrinError::Node(format!("failed to search for kernel: {}", e)).into())
+    }
+
+    fn find_unspent_output_height(&self, commit: &Commitment) -> crate::Result<Option<u64>> {
+        self.node_client
+            .get_outputs_from_node(vec![*commit])
+            .map(|outputs| outputs.get(commit).map(|(_, height, _)| *height))
+            .map_err(|e| GrinError::Node(format!("failed to search for output: {}", e)).into())
     }
 }
 
-impl LookFor for Wallet {
-    type Event = event::Redeem;
+impl<W: GrinWallet + ?Sized> LookFor<event::Redeem> for W {
     type Extract = Signature;
 
-    fn look_for(&self, event: Self::Event) -> anyhow::Result<Self::Extract> {
+    fn look_for(&self, event: event::Redeem) -> crate::Result<Self::Extract> {
         let kernel = self.find_kernel(&event.excess)?;
 
         Ok(kernel.excess_sig)
     }
 }
 
-pub fn build_input(amount: u64, secret_key: &SecretKey) -> anyhow::Result<Input> {
+// `None` until the fund kernel is on chain, or once the fund output has been
+// spent
+impl<W: GrinWallet + ?Sized> LookFor<event::Fund> for W {
+    type Extract = Option<event::FundConfirmed>;
+
+    fn look_for(&self, event: event::Fund) -> crate::Result<Self::Extract> {
+        let kernel_height = match self.find_kernel_height(&event.excess)? {
+            Some(height) => height,
+            None => return Ok(None),
+        };
+        let output_height = match self.find_unspent_output_height(&event.output)? {
+            Some(height) => height,
+            None => return Ok(None),
+        };
+
+        let tip = self.get_chain_tip()?;
+        let confirmations = (tip + 1).saturating_sub(std::cmp::max(kernel_height, output_height));
+
+        Ok(Some(event::FundConfirmed {
+            kernel_height,
+            output_height,
+            confirmations,
+        }))
+    }
+}
+
+pub fn build_input(amount: u64, secret_key: &SecretKey) -> crate::Result<Input> {
     let commit = SECP
         .commit(amount, secret_key.clone())
-        .map_err(|e| anyhow::anyhow!("failed to build Pedersen commitment: {}", e))?;
+        .context("failed to build Pedersen commitment")?;
 
     Ok(Input {
         features: OutputFeatures::Plain,
@@ -269,10 +347,10 @@ pub fn build_input(amount: u64, secret_key: &SecretKey) -> anyhow::Result<Input>
     })
 }
 
-pub fn build_output(amount: u64, secret_key: &SecretKey) -> anyhow::Result<Output> {
+pub fn build_output(amount: u64, secret_key: &SecretKey) -> crate::Result<Output> {
     let commit = SECP
         .commit(amount, secret_key.clone())
-        .map_err(|e| anyhow::anyhow!("failed to build Pedersen commitment: {}", e))?;
+        .context("failed to build Pedersen commitment")?;
 
     // These are just used for random number generation inside bullet proof C
     let rewind_nonce = random_secret_key();
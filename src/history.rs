@@ -0,0 +1,374 @@
+//! An audit trail of every swap.
+//!
+//! Each swap has its own append-only file of JSON records, one per line, in
+//! the history directory. Records are only ever added, so the file tells what
+//! happened in the swap even after its `SwapFile` was deleted.
+//!
+//! Messages are recorded by the hash of their encoding, since some of them
+//! carry secrets once combined with the state of the swap.
+
+use crate::{
+    error::{Error, StorageError},
+    expiry::unix_time_now,
+    swap::{Ledger, Status},
+    Receipt,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone)]
+pub struct History {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub swap_id: String,
+    // Seconds since the UNIX epoch
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub entry: Entry,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Entry {
+    MessageSent {
+        message: String,
+        hash: String,
+    },
+    MessageReceived {
+        message: String,
+        hash: String,
+    },
+    ActionExecuted {
+        action: Action,
+        #[serde(flatten)]
+        receipt: Receipt,
+    },
+    EventObserved {
+        ledger: Ledger,
+        event: ChainEvent,
+    },
+    StatusChanged {
+        status: Status,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Fund,
+    Redeem,
+    Refund,
+    // Recovering Grin left in a special output
+    Sweep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainEvent {
+    // The peer funded the ledger we redeem on
+    Funded,
+    // The peer redeemed, revealing the secret
+    Redeemed,
+    // The ledger we funded allows us to refund
+    RefundUnlocked,
+}
+
+/// Which records to return, all of them by default.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub swap_id: Option<String>,
+    // Both in seconds since the UNIX epoch, inclusive
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl Entry {
+    /// A message of type `T`, recorded under the name of its type without the
+    /// module path and generic parameters.
+    pub fn message_sent<T>(encoded: &[u8]) -> Self {
+        Entry::MessageSent {
+            message: message_name::<T>(),
+            hash: hash(encoded),
+        }
+    }
+
+    pub fn message_received<T>(encoded: &[u8]) -> Self {
+        Entry::MessageReceived {
+            message: message_name::<T>(),
+            hash: hash(encoded),
+        }
+    }
+}
+
+fn message_name<T>() -> String {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+
+    name.rsplit("::").next().unwrap_or(name).to_string()
+}
+
+fn hash(encoded: &[u8]) -> String {
+    hex::encode(Sha256::digest(encoded))
+}
+
+impl History {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn append(&self, swap_id: &str, entry: Entry) -> crate::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let record = Record {
+            swap_id: swap_id.to_string(),
+            timestamp: unix_time_now(),
+            entry,
+        };
+        let mut line = serde_json::to_vec(&record).expect("history records serialize to JSON");
+        line.push(b'\n');
+
+        // A single write, so that a crash cannot leave half a record behind
+        // another one
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(swap_id))?
+            .write_all(&line)?;
+
+        Ok(())
+    }
+
+    pub fn append_all(&self, swap_id: &str, entries: Vec<Entry>) -> crate::Result<()> {
+        for entry in entries {
+            self.append(swap_id, entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// The records matching `query`, oldest first.
+    pub fn query(&self, query: &Query) -> crate::Result<Vec<Record>> {
+        let swap_ids = match &query.swap_id {
+            Some(swap_id) => vec![swap_id.clone()],
+            None => self.swap_ids()?,
+        };
+
+        let mut records = Vec::new();
+        for swap_id in swap_ids {
+            records.extend(self.read(&swap_id)?.into_iter().filter(|record| {
+                query.since.map_or(true, |since| record.timestamp >= since)
+                    && query.until.map_or(true, |until| record.timestamp <= until)
+            }));
+        }
+        // Stable, so the records of a swap stay in the order they were written
+        records.sort_by_key(|record| record.timestamp);
+
+        Ok(records)
+    }
+
+    /// Write the records matching `query` as CSV, with a header line.
+    pub fn export_csv<W: Write>(&self, query: &Query, mut writer: W) -> crate::Result<()> {
+        writeln!(writer, "swap_id,timestamp,kind,ledger,name,reference,fee")?;
+
+        for record in self.query(query)? {
+            let (kind, ledger, name, reference, fee) = match &record.entry {
+                Entry::MessageSent { message, hash } => {
+                    ("message_sent", None, message.clone(), hash.clone(), None)
+                }
+                Entry::MessageReceived { message, hash } => (
+                    "message_received",
+                    None,
+                    message.clone(),
+                    hash.clone(),
+                    None,
+                ),
+                Entry::ActionExecuted { action, receipt } => (
+                    "action_executed",
+                    Some(receipt.ledger),
+                    to_csv_name(action),
+                    receipt.reference.clone(),
+                    Some(receipt.fee),
+                ),
+                Entry::EventObserved { ledger, event } => (
+                    "event_observed",
+                    Some(*ledger),
+                    to_csv_name(event),
+                    String::new(),
+                    None,
+                ),
+                Entry::StatusChanged { status } => (
+                    "status_changed",
+                    None,
+                    to_csv_name(status),
+                    String::new(),
+                    None,
+                ),
+            };
+
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                csv_field(&record.swap_id),
+                record.timestamp,
+                kind,
+                ledger
+                    .map(|ledger| to_csv_name(&ledger))
+                    .unwrap_or_default(),
+                csv_field(&name),
+                csv_field(&reference),
+                fee.map(|fee| fee.to_string()).unwrap_or_default(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn swap_ids(&self) -> crate::Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut swap_ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(false, |extension| extension == "jsonl")
+            {
+                if let Some(swap_id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    swap_ids.push(swap_id.to_string());
+                }
+            }
+        }
+        swap_ids.sort();
+
+        Ok(swap_ids)
+    }
+
+    fn read(&self, swap_id: &str) -> crate::Result<Vec<Record>> {
+        let path = self.path(swap_id);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        BufReader::new(fs::File::open(&path)?)
+            .lines()
+            .map(|line| {
+                serde_json::from_str(&line?).map_err(|e| {
+                    Error::from(StorageError::Corrupted(format!(
+                        "history {}: {}",
+                        path.display(),
+                        e
+                    )))
+                })
+            })
+            .collect()
+    }
+
+    fn path(&self, swap_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", swap_id))
+    }
+}
+
+/// The history kept next to the swaps saved in `swap_dir`.
+pub fn in_swap_dir(swap_dir: &Path) -> History {
+    History::new(swap_dir.join("history"))
+}
+
+// The snake case name the value is serialized to
+fn to_csv_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        other => panic!("{:?} is not a name", other),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn history() -> History {
+        History::new(
+            std::env::temp_dir().join(format!("history-{}", crate::messages::SwapId::random())),
+        )
+    }
+
+    #[test]
+    fn records_are_appended_per_swap() -> crate::Result<()> {
+        let history = history();
+
+        history.append("a", Entry::StatusChanged {
+            status: Status::KeysExchanged,
+        })?;
+        history.append("b", Entry::message_sent::<Vec<u8>>(b"message"))?;
+        history.append("a", Entry::StatusChanged {
+            status: Status::Funded,
+        })?;
+
+        let records = history.query(&Query {
+            swap_id: Some("a".to_string()),
+            ..Query::default()
+        })?;
+        assert_eq!(
+            records
+                .into_iter()
+                .map(|record| record.entry)
+                .collect::<Vec<_>>(),
+            vec![
+                Entry::StatusChanged {
+                    status: Status::KeysExchanged
+                },
+                Entry::StatusChanged {
+                    status: Status::Funded
+                },
+            ]
+        );
+        assert_eq!(history.query(&Query::default())?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn csv_has_a_line_per_record() -> crate::Result<()> {
+        let history = history();
+
+        history.append("a", Entry::ActionExecuted {
+            action: Action::Fund,
+            receipt: Receipt {
+                ledger: Ledger::Grin,
+                reference: "08aa".to_string(),
+                fee: 8_000_000,
+                amounts: vec![1_000_000_000],
+                broadcast_at: 1_600_000_000,
+                kernels: vec!["08aa".to_string(), "09bb".to_string()],
+            },
+        })?;
+
+        let mut csv = Vec::new();
+        history.export_csv(&Query::default(), &mut csv)?;
+        let csv = String::from_utf8(csv).expect("CSV is UTF-8");
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("a,"));
+        assert!(lines[1].ends_with(",action_executed,grin,fund,08aa,8000000"));
+
+        Ok(())
+    }
+}
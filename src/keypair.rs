@@ -1,6 +1,8 @@
 use crate::bitcoin::{Address, BitcoinPublicKey, Network};
 use rand::Rng;
 use secp256k1zkp::{ContextFlag, Message, Secp256k1, Signature};
+use std::fmt;
+use zeroize::Zeroize;
 
 pub use secp256k1zkp::key::{PublicKey, SecretKey, ZERO_KEY};
 
@@ -23,12 +25,32 @@ lazy_static::lazy_static! {
     };
 }
 
-#[derive(Debug, Clone)]
+/// A secret key along with its public key.
+///
+/// The secret key is wiped from memory when the key pair is dropped and left
+/// out of the `Debug` output, so that printing a protocol state does not leak
+/// it. Reading it requires calling `expose_secret_key`.
+#[derive(Clone)]
 pub struct KeyPair {
-    pub secret_key: SecretKey,
+    secret_key: SecretKey,
     pub public_key: PublicKey,
 }
 
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("secret_key", &"<redacted>")
+            .field("public_key", &self.public_key)
+            .finish()
+    }
+}
+
+impl Drop for KeyPair {
+    fn drop(&mut self) {
+        self.secret_key.0.zeroize();
+    }
+}
+
 impl KeyPair {
     pub fn new(secret_key: SecretKey) -> Self {
         let public_key = PublicKey::from_secret_key(&*SECP, &secret_key)
@@ -51,6 +73,12 @@ impl KeyPair {
         KeyPair::new(random_secret_key())
     }
 
+    /// The secret key, for the signing and blinding computations which need
+    /// it. Copies of it are not wiped, so keep them short-lived.
+    pub fn expose_secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
     pub fn sign_ecdsa(&self, message: &Message) -> Signature {
         SECP.sign(message, &self.secret_key).expect("cannot fail")
     }
@@ -131,3 +159,16 @@ impl Negate for KeyPair {
 pub fn random_secret_key() -> SecretKey {
     SecretKey::from_slice(&*SECP, &rand::thread_rng().gen::<[u8; 32]>()).unwrap()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn debug_output_does_not_contain_secret_key() {
+        let keypair = KeyPair::new_random();
+        let secret_hex = hex::encode(&keypair.expose_secret_key().0[..]);
+
+        assert!(!format!("{:?}", keypair).contains(&secret_hex));
+    }
+}
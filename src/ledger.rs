@@ -0,0 +1,169 @@
+//! The steps each party takes on a single ledger, regardless of the ledger.
+//!
+//! Alice funds the alpha ledger and redeems on the beta ledger, and Bob does
+//! the opposite. The protocol in `alice.rs` and `bob.rs` is written once in
+//! terms of these traits, so any pair of ledgers which implement them can be
+//! swapped.
+//!
+//! Ledgers with scripts (Bitcoin) and scriptless ledgers (Grin) differ in what
+//! the redeemer has to sign for the funder and in whether the parties have to
+//! build a multiparty bulletproof for the fund output. Both are captured by the
+//! associated types: the bulletproof round 1 is exchanged together with the
+//! commitment and the public keys, and is empty on ledgers with scripts.
+//!
+//! The first transition of every role receives the transcript of the key
+//! exchange, from which ledgers derive any value both parties need to agree on.
+
+use crate::{
+    commit::CoinTossingKeys,
+    expiry::RefundLock,
+    transcript::Transcript,
+    wire::{Decode, Encode},
+    Hash, KeyPair, PublicKey,
+};
+
+/// The terms of a swap on a single ledger.
+pub trait LedgerOffer: Hash + Clone {
+    fn asset(&self) -> u64;
+    fn with_asset(self, asset: u64) -> Self;
+    fn refund_lock(&self) -> RefundLock;
+}
+
+/// What a party contributes to a multiparty computation run during the key
+/// exchange, such as the first round of a multiparty bulletproof. Ledgers
+/// which do not need one use `()`, which is sent as nothing.
+pub trait KeyExchangeRound: Encode + Decode + Clone {
+    fn append_to(&self, label: &'static [u8], transcript: &mut Transcript);
+}
+
+impl KeyExchangeRound for () {
+    fn append_to(&self, _: &'static [u8], _: &mut Transcript) {}
+}
+
+pub trait AliceFunder0: Into<CoinTossingKeys> + Clone {
+    type Offer: LedgerOffer;
+    type PKs: Hash;
+    type BulletproofRound1: KeyExchangeRound;
+    type Next: AliceFunder1;
+
+    fn offer(&self) -> &Self::Offer;
+
+    fn bulletproof_round_1(&self) -> Self::BulletproofRound1;
+
+    fn transition(
+        self,
+        PKs_other: Self::PKs,
+        bulletproof_round_1_other: Self::BulletproofRound1,
+        transcript: &Transcript,
+    ) -> crate::Result<Self::Next>;
+}
+
+pub trait AliceFunder1 {
+    type RedeemerSigs;
+    type EncryptedSignature;
+    type Next;
+
+    fn transition(
+        self,
+        redeemer_sigs: Self::RedeemerSigs,
+        y: &KeyPair,
+    ) -> crate::Result<(Self::Next, Self::EncryptedSignature)>;
+}
+
+pub trait AliceRedeemer0: Into<CoinTossingKeys> + Clone {
+    type Offer: LedgerOffer;
+    type PKs: Hash;
+    type BulletproofRound1: KeyExchangeRound;
+    type RedeemerSigs;
+    type Next: AliceRedeemer1;
+
+    fn offer(&self) -> &Self::Offer;
+
+    fn bulletproof_round_1(&self) -> Self::BulletproofRound1;
+
+    /// The redeemer may change `y` to satisfy the constraints of the ledger.
+    /// Its counterpart is expected to do the same to `Y`.
+    fn transition(
+        self,
+        PKs_other: Self::PKs,
+        y: &mut KeyPair,
+        bulletproof_round_1_other: Self::BulletproofRound1,
+        transcript: &Transcript,
+    ) -> crate::Result<(Self::Next, Self::RedeemerSigs)>;
+}
+
+pub trait AliceRedeemer1 {
+    type EncryptedSignature;
+    type Next;
+
+    fn transition(
+        self,
+        redeem_encsig: Self::EncryptedSignature,
+        y: &KeyPair,
+    ) -> crate::Result<Self::Next>;
+}
+
+pub trait BobRedeemer0: Clone {
+    type Offer: LedgerOffer;
+    type PKs: Hash;
+    type BulletproofRound1: KeyExchangeRound;
+    type RedeemerSigs;
+    type Next: BobRedeemer1;
+
+    fn offer(&self) -> &Self::Offer;
+
+    fn public_keys(&self) -> Self::PKs;
+
+    fn bulletproof_round_1(&self) -> Self::BulletproofRound1;
+
+    /// The redeemer may change `Y` to satisfy the constraints of the ledger,
+    /// mirroring what Alice does to `y`.
+    fn transition(
+        self,
+        PKs_other: Self::PKs,
+        Y: &mut PublicKey,
+        bulletproof_round_1_other: Self::BulletproofRound1,
+        transcript: &Transcript,
+    ) -> crate::Result<(Self::Next, Self::RedeemerSigs)>;
+}
+
+pub trait BobRedeemer1 {
+    type EncryptedSignature;
+    type Next;
+
+    fn transition(
+        self,
+        Y: &PublicKey,
+        redeem_encsig: Self::EncryptedSignature,
+    ) -> crate::Result<Self::Next>;
+}
+
+pub trait BobFunder0: Clone {
+    type Offer: LedgerOffer;
+    type PKs: Hash;
+    type BulletproofRound1: KeyExchangeRound;
+    type RedeemerSigs;
+    type EncryptedSignature;
+    type Next: BobFunder1;
+
+    fn offer(&self) -> &Self::Offer;
+
+    fn public_keys(&self) -> Self::PKs;
+
+    fn bulletproof_round_1(&self) -> Self::BulletproofRound1;
+
+    fn transition(
+        self,
+        PKs_other: Self::PKs,
+        redeemer_sigs: Self::RedeemerSigs,
+        Y: &PublicKey,
+        bulletproof_round_1_other: Self::BulletproofRound1,
+        transcript: &Transcript,
+    ) -> crate::Result<(Self::Next, Self::EncryptedSignature)>;
+}
+
+pub trait BobFunder1 {
+    type Next;
+
+    fn transition(self) -> crate::Result<Self::Next>;
+}
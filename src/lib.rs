@@ -1,19 +1,34 @@
 #![allow(non_snake_case)]
 
+#[macro_use]
+pub mod wire;
+
 pub mod alice;
 pub mod bitcoin;
 pub mod bob;
+pub mod channel;
 pub mod commit;
+pub mod daemon;
 pub mod dleq;
 pub mod ecdsa;
+pub mod error;
 pub mod execute;
+pub mod expiry;
 pub mod grin;
+pub mod history;
 pub mod keypair;
+pub mod ledger;
 pub mod look_for;
+pub mod manager;
 pub mod messages;
+pub mod negotiation;
 pub mod schnorr;
+pub mod swap;
+pub mod transcript;
+pub mod watchtower;
 
-pub use execute::Execute;
+pub use error::{Error, Result};
+pub use execute::{Execute, Receipt};
 pub use keypair::{KeyPair, PublicKey, SecretKey};
 pub use look_for::LookFor;
 
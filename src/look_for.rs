@@ -1,6 +1,5 @@
-pub trait LookFor {
-    type Event;
+pub trait LookFor<E> {
     type Extract;
 
-    fn look_for(&self, event: Self::Event) -> anyhow::Result<Self::Extract>;
+    fn look_for(&self, event: E) -> crate::Result<Self::Extract>;
 }
@@ -0,0 +1,293 @@
+//! Running several swaps at once against the same wallets.
+//!
+//! The protocol states only know about a single swap, so a party running many
+//! of them needs to keep track of which state belongs to which `SwapId` and of
+//! the funds each swap is going to spend. Otherwise two swaps could be built on
+//! the same Bitcoin output, or together ask a Grin wallet for more than it
+//! holds, and one of them would fail to fund after the counterparty has already
+//! committed to it.
+//!
+//! Reservations only exist in the manager. The Grin wallet locks its outputs
+//! itself once an invoice is processed, which happens when the fund action is
+//! executed.
+
+use crate::{
+    bitcoin::{self, wallet::Output},
+    error::{RequestError, WalletError},
+    grin::GrinWallet,
+    messages::SwapId,
+};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+pub struct Manager<S> {
+    swaps: Mutex<HashMap<SwapId, Swap<S>>>,
+}
+
+struct Swap<S> {
+    // Taken out while the state is transitioning
+    state: Option<S>,
+    reservation: Reservation,
+}
+
+/// The funds set aside for a swap.
+#[derive(Debug, Clone, Default)]
+pub struct Reservation {
+    pub bitcoin_input: Option<bitcoin::OutPoint>,
+    pub grin_amount: u64,
+}
+
+impl<S> Default for Manager<S> {
+    fn default() -> Self {
+        Self {
+            swaps: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<S> Manager<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking a swap, usually right after creating its first state.
+    pub fn insert(&self, swap_id: SwapId, state: S) -> crate::Result<()> {
+        self.insert_swap(swap_id, Some(state))
+    }
+
+    /// Start tracking a swap before its first state exists, so that funds can
+    /// be reserved while it is being negotiated. The swap counts as taken
+    /// until its first state is put.
+    pub fn insert_taken(&self, swap_id: SwapId) -> crate::Result<()> {
+        self.insert_swap(swap_id, None)
+    }
+
+    /// Take the state of a swap out of the manager to transition it. Messages
+    /// for the same swap are rejected until the next state is put back.
+    pub fn take(&self, swap_id: SwapId) -> crate::Result<S> {
+        self.swaps()
+            .get_mut(&swap_id)
+            .ok_or_else(|| RequestError::UnknownSwap(swap_id.to_string()))?
+            .state
+            .take()
+            .ok_or_else(|| {
+                RequestError::Refused(format!("swap {} is already being processed", swap_id)).into()
+            })
+    }
+
+    pub fn put(&self, swap_id: SwapId, state: S) -> crate::Result<()> {
+        let mut swaps = self.swaps();
+        let swap = swaps
+            .get_mut(&swap_id)
+            .ok_or_else(|| RequestError::UnknownSwap(swap_id.to_string()))?;
+
+        if swap.state.is_some() {
+            return Err(RequestError::Refused(format!("swap {} was not taken", swap_id)).into());
+        }
+        swap.state = Some(state);
+
+        Ok(())
+    }
+
+    /// Set aside an output of the Bitcoin wallet worth at least `amount` for
+    /// the swap. It is not given to any other swap until this one is removed.
+    pub fn reserve_bitcoin_input(
+        &self,
+        swap_id: SwapId,
+        wallet: &bitcoin::wallet::FunderWallet,
+        amount: u64,
+    ) -> crate::Result<Output> {
+        let mut swaps = self.swaps();
+
+        let reserved = swaps
+            .values()
+            .filter_map(|swap| swap.reservation.bitcoin_input)
+            .collect::<Vec<_>>();
+
+        let swap = swaps
+            .get_mut(&swap_id)
+            .ok_or_else(|| RequestError::UnknownSwap(swap_id.to_string()))?;
+        if swap.reservation.bitcoin_input.is_some() {
+            return Err(RequestError::Refused(format!(
+                "swap {} already reserved a Bitcoin output",
+                swap_id
+            ))
+            .into());
+        }
+
+        let output = wallet
+            .fund_inputs()
+            .iter()
+            .find(|output| output.txout.value >= amount && !reserved.contains(&output.outpoint))
+            .ok_or_else(|| {
+                WalletError::InsufficientFunds(format!(
+                    "no unreserved Bitcoin output worth at least {}",
+                    amount
+                ))
+            })?;
+
+        swap.reservation.bitcoin_input = Some(output.outpoint);
+
+        Ok(output.clone())
+    }
+
+    /// The outputs of the Bitcoin wallet not reserved by any swap.
+    pub fn unreserved_bitcoin_inputs(&self, wallet: &bitcoin::wallet::FunderWallet) -> Vec<Output> {
+        let swaps = self.swaps();
+
+        wallet
+            .fund_inputs()
+            .iter()
+            .filter(|output| {
+                !swaps
+                    .values()
+                    .any(|swap| swap.reservation.bitcoin_input == Some(output.outpoint))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Set aside `amount` of the spendable balance of the Grin wallet for the
+    /// swap, on top of what other swaps have reserved.
+    pub fn reserve_grin_amount(
+        &self,
+        swap_id: SwapId,
+        wallet: &dyn GrinWallet,
+        amount: u64,
+    ) -> crate::Result<()> {
+        self.reserve_grin_amount_within(swap_id, amount, wallet.get_balance()?)
+    }
+
+    /// Set aside `amount` grin for the swap, as long as all the reservations
+    /// together stay within `inventory`.
+    pub fn reserve_grin_amount_within(
+        &self,
+        swap_id: SwapId,
+        amount: u64,
+        inventory: u64,
+    ) -> crate::Result<()> {
+        let mut swaps = self.swaps();
+
+        let reserved = swaps
+            .values()
+            .map(|swap| swap.reservation.grin_amount)
+            .sum::<u64>();
+        if reserved.saturating_add(amount) > inventory {
+            return Err(WalletError::InsufficientFunds(format!(
+                "cannot reserve {} grin, {} of the spendable {} are already reserved",
+                amount, reserved, inventory
+            ))
+            .into());
+        }
+
+        let swap = swaps
+            .get_mut(&swap_id)
+            .ok_or_else(|| RequestError::UnknownSwap(swap_id.to_string()))?;
+        swap.reservation.grin_amount += amount;
+
+        Ok(())
+    }
+
+    pub fn reserved_grin_amount(&self) -> u64 {
+        self.swaps()
+            .values()
+            .map(|swap| swap.reservation.grin_amount)
+            .sum()
+    }
+
+    /// Stop tracking a swap and release its reservations. To be called once
+    /// the swap is aborted, refunded or redeemed.
+    pub fn remove(&self, swap_id: SwapId) -> Option<(Option<S>, Reservation)> {
+        self.swaps()
+            .remove(&swap_id)
+            .map(|swap| (swap.state, swap.reservation))
+    }
+
+    pub fn swap_ids(&self) -> Vec<SwapId> {
+        self.swaps().keys().copied().collect()
+    }
+
+    fn insert_swap(&self, swap_id: SwapId, state: Option<S>) -> crate::Result<()> {
+        let mut swaps = self.swaps();
+
+        if swaps.contains_key(&swap_id) {
+            return Err(RequestError::SwapExists(swap_id).into());
+        }
+
+        swaps.insert(swap_id, Swap {
+            state,
+            reservation: Reservation::default(),
+        });
+
+        Ok(())
+    }
+
+    fn swaps(&self) -> MutexGuard<HashMap<SwapId, Swap<S>>> {
+        self.swaps.lock().expect("manager lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        bitcoin::{wallet::FunderWallet, OutPoint, TxOut},
+        KeyPair,
+    };
+
+    fn output(value: u64, vout: u32) -> Output {
+        let keypair = KeyPair::new_random();
+        let txout = TxOut {
+            value,
+            script_pubkey: keypair.to_bitcoin_address().script_pubkey(),
+        };
+
+        Output::new(
+            keypair,
+            OutPoint {
+                vout,
+                ..OutPoint::null()
+            },
+            txout,
+        )
+    }
+
+    #[test]
+    fn bitcoin_outputs_are_reserved_once() -> crate::Result<()> {
+        let mut wallet = FunderWallet::new(String::new(), output(1_000, 0))?;
+        wallet.add_fund_input(output(2_000, 1));
+
+        let manager = Manager::new();
+        let (first, second, third) = (SwapId::random(), SwapId::random(), SwapId::random());
+        manager.insert(first, ())?;
+        manager.insert(second, ())?;
+        manager.insert(third, ())?;
+
+        let first_output = manager.reserve_bitcoin_input(first, &wallet, 500)?;
+        let second_output = manager.reserve_bitcoin_input(second, &wallet, 500)?;
+        assert_ne!(first_output.outpoint, second_output.outpoint);
+        assert!(manager.reserve_bitcoin_input(third, &wallet, 500).is_err());
+
+        manager.remove(first);
+        let third_output = manager.reserve_bitcoin_input(third, &wallet, 500)?;
+        assert_eq!(third_output.outpoint, first_output.outpoint);
+
+        Ok(())
+    }
+
+    #[test]
+    fn swaps_inserted_taken_wait_for_their_first_state() -> crate::Result<()> {
+        let manager = Manager::new();
+        let swap_id = SwapId::random();
+
+        manager.insert_taken(swap_id)?;
+        assert!(manager.take(swap_id).is_err());
+
+        manager.put(swap_id, 0)?;
+        assert_eq!(manager.take(swap_id)?, 0);
+
+        Ok(())
+    }
+}
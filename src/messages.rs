@@ -1,34 +1,409 @@
 use crate::{
     commit::{Commitment, Opening},
-    grin,
+    error::{BitcoinError, CryptoError, Error, GrinError, ProtocolError, RequestError},
+    ledger::KeyExchangeRound,
+    transcript::Transcript,
+    wire::{malformed, take, Decode, Encode},
+    Hash,
 };
+use rand::Rng;
+use std::{convert::TryInto, fmt, str::FromStr};
 
-// Sent by Alice
-pub struct Message0 {
+/// Identifies a swap in every message, so that a party running several swaps
+/// at once can route each message to the right state. Both parties must agree
+/// on it before the first message. When running over an authenticated channel
+/// it is taken from `channel::Session::swap_id`, which binds the swap to the
+/// session since it is part of the transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SwapId(pub [u8; 16]);
+
+impl SwapId {
+    pub fn random() -> Self {
+        Self(rand::thread_rng().gen())
+    }
+
+    /// Where the keys of the swap are derived in the swap keychain, see
+    /// `grin::special_output_key_id`. The index is taken from the identifier
+    /// so that it can be found again from the swap file alone, and stays below
+    /// the range of hardened indices.
+    pub fn keychain_index(&self) -> u32 {
+        u32::from_be_bytes([self.0[0], self.0[1], self.0[2], self.0[3]]) & 0x7fff_ffff
+    }
+
+    /// Check that a message received for this swap carries its identifier.
+    pub fn check(&self, received: SwapId) -> Result<(), ProtocolError> {
+        if *self == received {
+            Ok(())
+        } else {
+            Err(ProtocolError::SwapIdMismatch {
+                expected: *self,
+                received,
+            })
+        }
+    }
+}
+
+impl fmt::Display for SwapId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for SwapId {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let bytes =
+            hex::decode(s).map_err(|e| RequestError::Invalid(format!("swap id {}: {}", s, e)))?;
+        let id = bytes.as_slice().try_into().map_err(|_| {
+            RequestError::Invalid(format!("swap id must be 16 bytes, got {}", bytes.len()))
+        })?;
+
+        Ok(SwapId(id))
+    }
+}
+
+impl Encode for SwapId {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+}
+
+impl Decode for SwapId {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok(SwapId(take(bytes, 16)?.try_into().map_err(malformed)?))
+    }
+}
+
+// The messages exchanged before the signing phase are added to the transcript
+// by both parties. The signatures exchanged afterwards are not, since the
+// transcript is handed to the ledgers as it is at the end of the key exchange
+
+// Sent by Alice. The bulletproof rounds are empty on ledgers which do not
+// need one
+pub struct Message0<RA, RB> {
+    pub swap_id: SwapId,
     pub commitment: Commitment,
-    pub bulletproof_round_1_alice: grin::bulletproof::Round1,
+    pub bulletproof_round_1_alice: (RA, RB),
+}
+
+impl_wire!(Message0<RA, RB> {
+    swap_id,
+    commitment,
+    bulletproof_round_1_alice,
+});
+
+impl<RA, RB> Message0<RA, RB>
+where
+    RA: KeyExchangeRound,
+    RB: KeyExchangeRound,
+{
+    pub fn append_to(&self, transcript: &mut Transcript) {
+        transcript.append_hash(b"commitment", &self.commitment);
+        let (alpha, beta) = &self.bulletproof_round_1_alice;
+        alpha.append_to(b"bulletproof_round_1_alice", transcript);
+        beta.append_to(b"bulletproof_round_1_alice", transcript);
+    }
 }
 
 // Sent by Bob
-pub struct Message1<A, B> {
+pub struct Message1<A, B, RA, RB> {
+    pub swap_id: SwapId,
     pub PKs_alpha: A,
     pub PKs_beta: B,
-    pub bulletproof_round_1_bob: grin::bulletproof::Round1,
+    pub bulletproof_round_1_bob: (RA, RB),
+}
+
+impl_wire!(Message1<A, B, RA, RB> {
+    swap_id,
+    PKs_alpha,
+    PKs_beta,
+    bulletproof_round_1_bob,
+});
+
+impl<A, B, RA, RB> Message1<A, B, RA, RB>
+where
+    A: Hash,
+    B: Hash,
+    RA: KeyExchangeRound,
+    RB: KeyExchangeRound,
+{
+    pub fn append_to(&self, transcript: &mut Transcript) {
+        transcript.append_hash(b"PKs_alpha", &self.PKs_alpha);
+        transcript.append_hash(b"PKs_beta", &self.PKs_beta);
+        let (alpha, beta) = &self.bulletproof_round_1_bob;
+        alpha.append_to(b"bulletproof_round_1_bob", transcript);
+        beta.append_to(b"bulletproof_round_1_bob", transcript);
+    }
 }
 
 // Sent by Alice
 pub struct Message2<B> {
+    pub swap_id: SwapId,
     pub opening: Opening,
     pub beta_redeemer_sigs: B,
 }
 
+impl_wire!(Message2<B> {
+    swap_id,
+    opening,
+    beta_redeemer_sigs,
+});
+
 // Sent by Bob
 pub struct Message3<A, B> {
+    pub swap_id: SwapId,
     pub alpha_redeemer_sigs: A,
     pub beta_redeem_encsig: B,
 }
 
+impl_wire!(Message3<A, B> {
+    swap_id,
+    alpha_redeemer_sigs,
+    beta_redeem_encsig,
+});
+
 // Sent by Alice
 pub struct Message4<A> {
+    pub swap_id: SwapId,
     pub alpha_redeem_encsig: A,
 }
+
+impl_wire!(Message4<A> {
+    swap_id,
+    alpha_redeem_encsig,
+});
+
+/// The messages of the protocol when Bob sends the first message. Alice still
+/// generates `y` and funds first, but she only reveals her keys once Bob has
+/// committed to his.
+pub mod bob_initiates {
+    use super::SwapId;
+    use crate::{
+        commit::{CoinTossingKeys, Commitment, Opening},
+        ledger::KeyExchangeRound,
+        transcript::Transcript,
+        PublicKey,
+    };
+
+    // Sent by Bob
+    pub struct Message0<RA, RB> {
+        pub swap_id: SwapId,
+        pub commitment: Commitment,
+        pub bulletproof_round_1_bob: (RA, RB),
+    }
+
+    impl_wire!(Message0<RA, RB> {
+        swap_id,
+        commitment,
+        bulletproof_round_1_bob,
+    });
+
+    impl<RA, RB> Message0<RA, RB>
+    where
+        RA: KeyExchangeRound,
+        RB: KeyExchangeRound,
+    {
+        pub fn append_to(&self, transcript: &mut Transcript) {
+            transcript.append_hash(b"commitment", &self.commitment);
+            let (alpha, beta) = &self.bulletproof_round_1_bob;
+            alpha.append_to(b"bulletproof_round_1_bob", transcript);
+            beta.append_to(b"bulletproof_round_1_bob", transcript);
+        }
+    }
+
+    // Sent by Alice
+    pub struct Message1<RA, RB> {
+        pub swap_id: SwapId,
+        pub PKs_alpha: CoinTossingKeys,
+        pub PKs_beta: CoinTossingKeys,
+        pub Y: PublicKey,
+        pub bulletproof_round_1_alice: (RA, RB),
+    }
+
+    impl_wire!(Message1<RA, RB> {
+        swap_id,
+        PKs_alpha,
+        PKs_beta,
+        Y,
+        bulletproof_round_1_alice,
+    });
+
+    impl<RA, RB> Message1<RA, RB>
+    where
+        RA: KeyExchangeRound,
+        RB: KeyExchangeRound,
+    {
+        pub fn append_to(&self, transcript: &mut Transcript) {
+            transcript.append_public_keys(b"PKs_alpha", &self.PKs_alpha);
+            transcript.append_public_keys(b"PKs_beta", &self.PKs_beta);
+            transcript.append_public_key(b"Y", &self.Y);
+            let (alpha, beta) = &self.bulletproof_round_1_alice;
+            alpha.append_to(b"bulletproof_round_1_alice", transcript);
+            beta.append_to(b"bulletproof_round_1_alice", transcript);
+        }
+    }
+
+    // Sent by Bob
+    pub struct Message2<A> {
+        pub swap_id: SwapId,
+        pub opening: Opening,
+        pub alpha_redeemer_sigs: A,
+    }
+
+    impl_wire!(Message2<A> {
+        swap_id,
+        opening,
+        alpha_redeemer_sigs,
+    });
+
+    // Sent by Alice
+    pub struct Message3<A, B> {
+        pub swap_id: SwapId,
+        pub alpha_redeem_encsig: A,
+        pub beta_redeemer_sigs: B,
+    }
+
+    impl_wire!(Message3<A, B> {
+        swap_id,
+        alpha_redeem_encsig,
+        beta_redeemer_sigs,
+    });
+
+    // Sent by Bob
+    pub struct Message4<B> {
+        pub swap_id: SwapId,
+        pub beta_redeem_encsig: B,
+    }
+
+    impl_wire!(Message4<B> {
+        swap_id,
+        beta_redeem_encsig,
+    });
+}
+
+// Cooperative refunds let both parties unwind a stalled swap after funding,
+// without waiting for the expiries. They must happen in a fixed order: Alice
+// first signs the cooperative refund of the beta ledger and only once Bob's
+// beta refund is confirmed does Bob sign the cooperative refund of the alpha
+// ledger. Alice holds `y`, so the other way around she could take back the
+// alpha asset and still redeem the beta asset.
+//
+// If Bob never funded the beta ledger, he can sign the alpha cooperative
+// refund right away, as long as he does not fund afterwards.
+
+// Sent by the funder of a ledger to ask for a cooperative refund. Empty on
+// ledgers where the redeemer can sign on their own
+pub struct CooperativeRefundRequest<T> {
+    pub swap_id: SwapId,
+    pub request: T,
+}
+
+// Sent by the redeemer of a ledger, completing the cooperative refund
+pub struct CooperativeRefundResponse<T> {
+    pub swap_id: SwapId,
+    pub response: T,
+}
+
+// Sent by either party instead of their next message, as long as they have not
+// funded yet. Receiving it ends the swap for the receiver as well
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("peer aborted swap {swap_id} during {phase:?}: {reason_code:?}")]
+pub struct Abort {
+    pub swap_id: SwapId,
+    pub reason_code: ReasonCode,
+    pub phase: Phase,
+}
+
+impl_wire!(Abort {
+    swap_id,
+    reason_code,
+    phase,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReasonCode {
+    // The opening did not match Alice's commitment
+    InvalidOpening,
+    // A signature or partial signature of the peer failed to verify
+    InvalidSignature,
+    // The offers or their expiries are not acceptable
+    InvalidOffer,
+    // The user cancelled the swap
+    Cancelled,
+    Other,
+}
+
+impl ReasonCode {
+    /// Pick the reason to report to the peer after one of our checks failed.
+    pub fn from_error(error: &Error) -> Self {
+        match error.root() {
+            Error::Protocol(ProtocolError::InvalidOpening) => ReasonCode::InvalidOpening,
+            Error::Protocol(ProtocolError::InvalidOfferSignature(_))
+            | Error::Protocol(ProtocolError::InvalidOffer(_)) => ReasonCode::InvalidOffer,
+            Error::Crypto(CryptoError::InvalidSignature)
+            | Error::Bitcoin(BitcoinError::InvalidRedeemerSignature(_))
+            | Error::Grin(GrinError::InvalidRedeemerSignature(_)) => ReasonCode::InvalidSignature,
+            _ => ReasonCode::Other,
+        }
+    }
+}
+
+/// The state the sender of an `Abort` was in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    // Alice0 and Bob0
+    KeyExchange,
+    // Alice1 and Bob1
+    Signing,
+    // Bob2, before funding the beta ledger
+    Funding,
+}
+
+impl Encode for ReasonCode {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let code: u8 = match self {
+            ReasonCode::InvalidOpening => 0,
+            ReasonCode::InvalidSignature => 1,
+            ReasonCode::InvalidOffer => 2,
+            ReasonCode::Cancelled => 3,
+            ReasonCode::Other => 4,
+        };
+        code.encode(buf)
+    }
+}
+
+// Codes added by newer versions are reported as `Other`
+impl Decode for ReasonCode {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok(match u8::decode(bytes)? {
+            0 => ReasonCode::InvalidOpening,
+            1 => ReasonCode::InvalidSignature,
+            2 => ReasonCode::InvalidOffer,
+            3 => ReasonCode::Cancelled,
+            _ => ReasonCode::Other,
+        })
+    }
+}
+
+impl Encode for Phase {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let phase: u8 = match self {
+            Phase::KeyExchange => 0,
+            Phase::Signing => 1,
+            Phase::Funding => 2,
+        };
+        phase.encode(buf)
+    }
+}
+
+impl Decode for Phase {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(Phase::KeyExchange),
+            1 => Ok(Phase::Signing),
+            2 => Ok(Phase::Funding),
+            other => Err(malformed(format!("unknown phase {}", other))),
+        }
+    }
+}
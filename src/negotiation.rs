@@ -0,0 +1,418 @@
+//! Agreeing on the offers before the protocol starts.
+//!
+//! A maker publishes a `Quote` with the terms they are willing to swap at. A
+//! taker chooses an amount of the alpha asset within the bounds of the quote
+//! and both parties sign the resulting `OfferPair` together with the
+//! `OfferContext` it is agreed in, so that a signature cannot be replayed in
+//! another swap or with another counterparty. Each of them then starts the
+//! protocol with the offers of the `SignedOfferPair`, which are also bound into
+//! the initial commitment.
+
+use crate::{
+    error::{ProtocolError, RequestError},
+    keypair::{verify_ecdsa, KeyPair, PublicKey},
+    ledger::LedgerOffer,
+    messages::SwapId,
+    wire::{malformed, Decode, Encode},
+    Hash,
+};
+use blake2::{Blake2b, Digest};
+use secp256k1zkp::{Message, Signature};
+use serde::Deserialize;
+
+/// How much of the beta asset is exchanged for the alpha asset:
+/// `beta_asset = alpha_asset * numerator / denominator`, rounded down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl_wire!(Rate {
+    numerator,
+    denominator,
+});
+
+impl Rate {
+    pub fn apply(&self, alpha_asset: u64) -> crate::Result<u64> {
+        if self.denominator == 0 {
+            return Err(ProtocolError::InvalidOffer("rate denominator is zero".into()).into());
+        }
+
+        let beta_asset =
+            u128::from(alpha_asset) * u128::from(self.numerator) / u128::from(self.denominator);
+
+        if beta_asset > u128::from(u64::max_value()) {
+            return Err(ProtocolError::InvalidOffer("beta asset amount overflows".into()).into());
+        }
+
+        Ok(beta_asset as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Party {
+    Maker,
+    Taker,
+}
+
+/// Who takes which role in the protocol, as chosen by the maker.
+///
+/// Alice generates `y`, funds the alpha ledger and redeems on the beta ledger.
+/// She must be the one to fund first, so holding the secret and funding the
+/// alpha ledger cannot be separated. The initiator sends the first message of
+/// the protocol and commits to their keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Roles {
+    pub alice: Party,
+    pub initiator: Party,
+}
+
+impl_wire!(Roles { alice, initiator });
+
+impl Encode for Party {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Party::Maker => 0u8.encode(buf),
+            Party::Taker => 1u8.encode(buf),
+        }
+    }
+}
+
+impl Decode for Party {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(Party::Maker),
+            1 => Ok(Party::Taker),
+            other => Err(malformed(format!("unknown party {}", other))),
+        }
+    }
+}
+
+impl Roles {
+    pub fn is_alice(&self, party: Party) -> bool {
+        self.alice == party
+    }
+
+    pub fn is_initiator(&self, party: Party) -> bool {
+        self.initiator == party
+    }
+}
+
+/// The terms published by a maker.
+///
+/// The fees and expiries are given by the alpha and beta offers, whose assets
+/// are ignored and replaced according to the amount requested by the taker.
+#[derive(Debug, Clone)]
+pub struct Quote<A, B> {
+    pub alpha: A,
+    pub beta: B,
+    pub rate: Rate,
+    pub min_alpha_asset: u64,
+    pub max_alpha_asset: u64,
+    pub maker: PublicKey,
+    pub roles: Roles,
+}
+
+impl_wire!(Quote<A, B> {
+    alpha,
+    beta,
+    rate,
+    min_alpha_asset,
+    max_alpha_asset,
+    maker,
+    roles,
+});
+
+impl<A, B> Quote<A, B>
+where
+    A: LedgerOffer,
+    B: LedgerOffer,
+{
+    pub fn offers(&self, alpha_asset: u64) -> crate::Result<OfferPair<A, B>> {
+        if alpha_asset < self.min_alpha_asset || alpha_asset > self.max_alpha_asset {
+            return Err(ProtocolError::InvalidOffer(format!(
+                "requested amount {} is outside of the quoted range [{}, {}]",
+                alpha_asset, self.min_alpha_asset, self.max_alpha_asset
+            ))
+            .into());
+        }
+
+        Ok(OfferPair {
+            alpha: self.alpha.clone().with_asset(alpha_asset),
+            beta: self.beta.clone().with_asset(self.rate.apply(alpha_asset)?),
+        })
+    }
+
+    /// Taker's side: request `alpha_asset` according to the quote, for the
+    /// swap `swap_id`.
+    pub fn take(
+        &self,
+        alpha_asset: u64,
+        swap_id: SwapId,
+        taker_identity: &KeyPair,
+    ) -> crate::Result<(Taking<A, B>, TakeRequest)> {
+        let offers = self.offers(alpha_asset)?;
+        let context = OfferContext {
+            swap_id,
+            maker: self.maker,
+            taker: taker_identity.public_key,
+        };
+        let taker_sig = offers.sign(&context, taker_identity);
+
+        let state = Taking {
+            offers,
+            context,
+            taker_sig,
+        };
+
+        let request = TakeRequest {
+            alpha_asset,
+            taker: taker_identity.public_key,
+            taker_sig,
+        };
+
+        Ok((state, request))
+    }
+
+    /// Maker's side: agree to the amount requested by the taker for the swap
+    /// `swap_id`, returning the signature to be sent back to them.
+    pub fn accept(
+        &self,
+        request: TakeRequest,
+        swap_id: SwapId,
+        maker_identity: &KeyPair,
+    ) -> crate::Result<(SignedOfferPair<A, B>, Signature)> {
+        if maker_identity.public_key != self.maker {
+            return Err(
+                RequestError::Invalid("quote was not published by this maker".into()).into(),
+            );
+        }
+
+        let offers = self.offers(request.alpha_asset)?;
+        let context = OfferContext {
+            swap_id,
+            maker: self.maker,
+            taker: request.taker,
+        };
+        let maker_sig = offers.sign(&context, maker_identity);
+
+        let signed_offers = SignedOfferPair::new(offers, context, maker_sig, request.taker_sig)?;
+
+        Ok((signed_offers, maker_sig))
+    }
+}
+
+// Sent by the taker
+#[derive(Debug, Clone)]
+pub struct TakeRequest {
+    pub alpha_asset: u64,
+    pub taker: PublicKey,
+    pub taker_sig: Signature,
+}
+
+impl_wire!(TakeRequest {
+    alpha_asset,
+    taker,
+    taker_sig,
+});
+
+pub struct Taking<A, B> {
+    offers: OfferPair<A, B>,
+    context: OfferContext,
+    taker_sig: Signature,
+}
+
+impl<A, B> Taking<A, B>
+where
+    A: LedgerOffer,
+    B: LedgerOffer,
+{
+    pub fn receive(self, maker_sig: Signature) -> crate::Result<SignedOfferPair<A, B>> {
+        SignedOfferPair::new(self.offers, self.context, maker_sig, self.taker_sig)
+    }
+}
+
+/// The swap and the parties offers are signed for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OfferContext {
+    pub swap_id: SwapId,
+    pub maker: PublicKey,
+    pub taker: PublicKey,
+}
+
+impl Hash for OfferContext {
+    fn hash(&self) -> [u8; 64] {
+        let mut hasher = Blake2b::new();
+
+        hasher.input(self.swap_id.0);
+        hasher.input(self.maker.0);
+        hasher.input(self.taker.0);
+
+        let mut hash = [0u8; 64];
+        hash.copy_from_slice(&hasher.result());
+        hash
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OfferPair<A, B> {
+    pub alpha: A,
+    pub beta: B,
+}
+
+impl<A, B> OfferPair<A, B>
+where
+    A: LedgerOffer,
+    B: LedgerOffer,
+{
+    pub fn sign(&self, context: &OfferContext, identity: &KeyPair) -> Signature {
+        identity.sign_ecdsa(&self.message(context))
+    }
+
+    pub fn verify(&self, context: &OfferContext, sig: &Signature, identity: &PublicKey) -> bool {
+        verify_ecdsa(&self.message(context), sig, identity)
+    }
+
+    fn message(&self, context: &OfferContext) -> Message {
+        let mut hasher = Blake2b::new();
+
+        hasher.input(&self.hash()[..]);
+        hasher.input(&context.hash()[..]);
+
+        Message::from_slice(&hasher.result()[..32]).expect("should not fail because it is a hash")
+    }
+}
+
+impl<A, B> Hash for OfferPair<A, B>
+where
+    A: Hash,
+    B: Hash,
+{
+    fn hash(&self) -> [u8; 64] {
+        let mut hasher = Blake2b::new();
+
+        hasher.input(&self.alpha.hash()[..]);
+        hasher.input(&self.beta.hash()[..]);
+
+        let mut hash = [0u8; 64];
+        hash.copy_from_slice(&hasher.result());
+        hash
+    }
+}
+
+/// Offers both the maker and the taker have agreed to.
+#[derive(Debug, Clone)]
+pub struct SignedOfferPair<A, B> {
+    pub offers: OfferPair<A, B>,
+    pub context: OfferContext,
+    pub maker_sig: Signature,
+    pub taker_sig: Signature,
+}
+
+impl<A, B> SignedOfferPair<A, B>
+where
+    A: LedgerOffer,
+    B: LedgerOffer,
+{
+    pub fn new(
+        offers: OfferPair<A, B>,
+        context: OfferContext,
+        maker_sig: Signature,
+        taker_sig: Signature,
+    ) -> crate::Result<Self> {
+        if !offers.verify(&context, &maker_sig, &context.maker) {
+            return Err(ProtocolError::InvalidOfferSignature("maker").into());
+        }
+
+        if !offers.verify(&context, &taker_sig, &context.taker) {
+            return Err(ProtocolError::InvalidOfferSignature("taker").into());
+        }
+
+        Ok(Self {
+            offers,
+            context,
+            maker_sig,
+            taker_sig,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{bitcoin, grin};
+
+    fn offers() -> OfferPair<grin::Offer, bitcoin::Offer> {
+        OfferPair {
+            alpha: grin::Offer {
+                asset: 10_000_000_000,
+                base_fee: grin::DEFAULT_BASE_FEE,
+                expiry: grin::Expiry::Absolute(1_440),
+            },
+            beta: bitcoin::Offer {
+                asset: 100_000_000,
+                fee: 1_000,
+                expiry: 500_000_000,
+                chain: bitcoin::ChainParams::bitcoin_regtest(),
+            },
+        }
+    }
+
+    fn offer_context(maker: &KeyPair, taker: &KeyPair) -> OfferContext {
+        OfferContext {
+            swap_id: SwapId::random(),
+            maker: maker.public_key,
+            taker: taker.public_key,
+        }
+    }
+
+    #[test]
+    fn signed_offers_verify() {
+        let maker = KeyPair::new_random();
+        let taker = KeyPair::new_random();
+        let context = offer_context(&maker, &taker);
+
+        let offers = offers();
+        let sig = offers.sign(&context, &maker);
+
+        assert!(offers.verify(&context, &sig, &maker.public_key));
+        assert!(!offers.verify(&context, &sig, &taker.public_key));
+    }
+
+    #[test]
+    fn tampered_offers_do_not_verify() {
+        let maker = KeyPair::new_random();
+        let taker = KeyPair::new_random();
+        let context = offer_context(&maker, &taker);
+
+        let sig = offers().sign(&context, &maker);
+
+        let mut tampered = offers();
+        tampered.beta.asset += 1;
+        assert!(!tampered.verify(&context, &sig, &maker.public_key));
+    }
+
+    #[test]
+    fn signature_is_bound_to_swap_and_parties() {
+        let maker = KeyPair::new_random();
+        let taker = KeyPair::new_random();
+        let context = offer_context(&maker, &taker);
+
+        let offers = offers();
+        let sig = offers.sign(&context, &maker);
+
+        let other_swap = OfferContext {
+            swap_id: SwapId::random(),
+            ..context
+        };
+        assert!(!offers.verify(&other_swap, &sig, &maker.public_key));
+
+        let other_taker = OfferContext {
+            taker: KeyPair::new_random().public_key,
+            ..context
+        };
+        assert!(!offers.verify(&other_taker, &sig, &maker.public_key));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::{
+    error::CryptoError,
     grin,
     keypair::{KeyPair, Negate, PublicKey, SecretKey, XCoor, YCoor, SECP},
+    wire::{Decode, Encode},
 };
 use secp256k1zkp::{aggsig, Message, Signature};
 use std::convert::{TryFrom, TryInto};
@@ -11,25 +13,37 @@ pub type PartialEncryptedSignature = PartialSignature;
 #[derive(Debug, Clone)]
 pub struct PartialSignature(pub SecretKey);
 
+impl Encode for PartialSignature {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf)
+    }
+}
+
+impl Decode for PartialSignature {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok(PartialSignature(SecretKey::decode(bytes)?))
+    }
+}
+
 pub fn sign_2p_0(
     x0: &KeyPair,
     r0: &KeyPair,
     X1: &PublicKey,
     R1: &PublicKey,
     message: &Message,
-) -> anyhow::Result<PartialSignature> {
+) -> crate::Result<PartialSignature> {
     let R = PublicKey::from_combination(&*SECP, vec![&r0.public_key, &R1])?;
     let X = PublicKey::from_combination(&*SECP, vec![&x0.public_key, &X1])?;
 
     grin::calculate_partial_sig(
         &*SECP,
-        &x0.secret_key,
-        &r0.secret_key,
+        x0.expose_secret_key(),
+        r0.expose_secret_key(),
         &R,
         Some(&X),
         &message,
     )
-    .map_err(|_| Error::CalculatePartialSig)?
+    .map_err(|_| CryptoError::Calculate("partial sig"))?
     .try_into()
 }
 
@@ -40,20 +54,20 @@ pub fn sign_2p_1(
     R0: &PublicKey,
     message: &Message,
     partial_sig_0: &PartialSignature,
-) -> anyhow::Result<(Signature, PublicKey)> {
+) -> crate::Result<(Signature, PublicKey)> {
     let R = PublicKey::from_combination(&*SECP, vec![&r1.public_key, &R0])?;
     let X = PublicKey::from_combination(&*SECP, vec![&x1.public_key, &X0])?;
 
     let partial_sig_1 = PartialSignature::try_from(
         grin::calculate_partial_sig(
             &*SECP,
-            &x1.secret_key,
-            &r1.secret_key,
+            x1.expose_secret_key(),
+            r1.expose_secret_key(),
             &R,
             Some(&X),
             message,
         )
-        .map_err(|_| Error::CalculatePartialSig)?,
+        .map_err(|_| CryptoError::Calculate("partial sig"))?,
     )?;
 
     let sig = {
@@ -64,7 +78,7 @@ pub fn sign_2p_1(
     };
 
     if !aggsig::verify_single(&*SECP, &sig, message, None, &X, Some(&X), None, false) {
-        return Err(Error::VerifySig.into());
+        return Err(CryptoError::InvalidSignature.into());
     }
 
     Ok((sig, X))
@@ -77,20 +91,20 @@ pub fn encsign_2p_0(
     R1: &PublicKey,
     Y: &PublicKey,
     message: &Message,
-) -> anyhow::Result<PartialEncryptedSignature> {
+) -> crate::Result<PartialEncryptedSignature> {
     let R = PublicKey::from_combination(&*SECP, vec![&r0.public_key, &R1, &Y])?;
 
     let X = PublicKey::from_combination(&*SECP, vec![&x0.public_key, &X1])?;
 
     grin::calculate_partial_sig(
         &*SECP,
-        &x0.secret_key,
-        &r0.secret_key,
+        x0.expose_secret_key(),
+        r0.expose_secret_key(),
         &R,
         Some(&X),
         &message,
     )
-    .map_err(|_| Error::CalculatePartialEncSig)?
+    .map_err(|_| CryptoError::Calculate("partial encsig"))?
     .try_into()
 }
 
@@ -102,7 +116,7 @@ pub fn encsign_2p_1(
     Y: &PublicKey,
     message: &Message,
     partial_encsig_0: &PartialEncryptedSignature,
-) -> anyhow::Result<EncryptedSignature> {
+) -> crate::Result<EncryptedSignature> {
     let R_hat = PublicKey::from_combination(&*SECP, vec![&r1.public_key, &R0])?;
     let R = PublicKey::from_combination(&*SECP, vec![&R_hat, &Y])?;
 
@@ -111,13 +125,13 @@ pub fn encsign_2p_1(
     let partial_encsig_1 = PartialEncryptedSignature::try_from(
         grin::calculate_partial_sig(
             &*SECP,
-            &x1.secret_key,
-            &r1.secret_key,
+            x1.expose_secret_key(),
+            r1.expose_secret_key(),
             &R,
             Some(&X),
             message,
         )
-        .map_err(|_| Error::CalculatePartialEncSig)?,
+        .map_err(|_| CryptoError::Calculate("partial encsig"))?,
     )?;
 
     let encsig = {
@@ -128,7 +142,7 @@ pub fn encsign_2p_1(
     };
 
     if !aggsig::verify_single(&*SECP, &encsig, message, Some(&R), &X, Some(&X), None, true) {
-        return Err(Error::VerifySig.into());
+        return Err(CryptoError::InvalidSignature.into());
     }
 
     Ok(encsig)
@@ -139,7 +153,7 @@ pub fn decsig(
     y: &KeyPair,
     encsig: &EncryptedSignature,
     R_hat: &PublicKey,
-) -> anyhow::Result<Signature> {
+) -> crate::Result<Signature> {
     // let mut R_hat_x = [0u8; 32];
     // R_hat_x.copy_from_slice(&encsig.as_ref()[0..32]);
     let R = PublicKey::from_combination(&*SECP, vec![&R_hat, &y.public_key])?;
@@ -147,7 +161,7 @@ pub fn decsig(
     let mut s_hat = [0u8; 32];
     s_hat.copy_from_slice(&encsig.as_ref()[32..64]);
     let mut s = SecretKey::from_slice(&*SECP, &s_hat)?;
-    s.add_assign(&*SECP, &y.secret_key)?;
+    s.add_assign(&*SECP, y.expose_secret_key())?;
 
     let mut buffer = [0u8; 64];
     buffer[0..32].copy_from_slice(&R.x_coor()[..]);
@@ -155,7 +169,7 @@ pub fn decsig(
     Ok(Signature::from_raw_data(&buffer)?)
 }
 
-pub fn recover(sig: &Signature, recovery_key: &RecoveryKey) -> anyhow::Result<KeyPair> {
+pub fn recover(sig: &Signature, recovery_key: &RecoveryKey) -> crate::Result<KeyPair> {
     let s = SecretKey::from_slice(&*SECP, &sig.as_ref()[32..64])?;
     let s_hat = &recovery_key.0;
 
@@ -167,9 +181,21 @@ pub fn recover(sig: &Signature, recovery_key: &RecoveryKey) -> anyhow::Result<Ke
 #[derive(Debug, Clone)]
 pub struct RecoveryKey(pub SecretKey);
 
+impl Encode for RecoveryKey {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf)
+    }
+}
+
+impl Decode for RecoveryKey {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok(RecoveryKey(SecretKey::decode(bytes)?))
+    }
+}
+
 impl TryFrom<EncryptedSignature> for RecoveryKey {
-    type Error = anyhow::Error;
-    fn try_from(from: EncryptedSignature) -> anyhow::Result<RecoveryKey> {
+    type Error = crate::Error;
+    fn try_from(from: EncryptedSignature) -> crate::Result<RecoveryKey> {
         Ok(RecoveryKey(SecretKey::from_slice(
             &*SECP,
             &from.as_ref()[32..64],
@@ -178,8 +204,8 @@ impl TryFrom<EncryptedSignature> for RecoveryKey {
 }
 
 impl TryFrom<Signature> for PartialSignature {
-    type Error = anyhow::Error;
-    fn try_from(from: Signature) -> anyhow::Result<PartialSignature> {
+    type Error = crate::Error;
+    fn try_from(from: Signature) -> crate::Result<PartialSignature> {
         let mut s = [0u8; 32];
         s.copy_from_slice(&from.as_ref()[32..64]);
 
@@ -188,7 +214,7 @@ impl TryFrom<Signature> for PartialSignature {
 }
 
 impl PartialSignature {
-    pub fn to_signature(&self, R: &PublicKey) -> anyhow::Result<Signature> {
+    pub fn to_signature(&self, R: &PublicKey) -> crate::Result<Signature> {
         let mut sig = [0u8; 64];
         sig[0..32].copy_from_slice(&R.x_coor()[..]);
         sig[32..64].copy_from_slice(&(self.0).0[..]);
@@ -197,22 +223,12 @@ impl PartialSignature {
     }
 }
 
-#[derive(Debug, Clone, thiserror::Error)]
-pub enum Error {
-    #[error("failed to calculate partial encsig")]
-    CalculatePartialEncSig,
-    #[error("failed to calculate partial sig")]
-    CalculatePartialSig,
-    #[error("failed to verify sig")]
-    VerifySig,
-}
-
 // This is only used during tests when you have all the secret keys
 pub fn normalize_keypairs(
     r0: KeyPair,
     r1: KeyPair,
     y: KeyPair,
-) -> anyhow::Result<(KeyPair, KeyPair, KeyPair)> {
+) -> crate::Result<(KeyPair, KeyPair, KeyPair)> {
     let R =
         PublicKey::from_combination(&*SECP, vec![&r0.public_key, &r1.public_key, &y.public_key])?;
     let mut R_y = purerust_secp256k1::curve::Field::default();
@@ -230,7 +246,7 @@ mod test {
     use super::*;
 
     #[test]
-    fn sign_and_verify() -> anyhow::Result<()> {
+    fn sign_and_verify() -> crate::Result<()> {
         let x0 = KeyPair::new_random();
         let x1 = KeyPair::new_random();
         let r0 = KeyPair::new_random();
@@ -254,7 +270,7 @@ mod test {
     }
 
     #[test]
-    fn encsign_and_encverify() -> anyhow::Result<()> {
+    fn encsign_and_encverify() -> crate::Result<()> {
         let x0 = KeyPair::new_random();
         let x1 = KeyPair::new_random();
         let r0 = KeyPair::new_random();
@@ -288,7 +304,7 @@ mod test {
     }
 
     #[test]
-    fn encsign_and_decsig() -> anyhow::Result<()> {
+    fn encsign_and_decsig() -> crate::Result<()> {
         let x0 = KeyPair::new_random();
         let x1 = KeyPair::new_random();
         let r0 = KeyPair::new_random();
@@ -339,7 +355,7 @@ mod test {
     }
 
     #[test]
-    fn recover_key_from_decrypted_signature() -> anyhow::Result<()> {
+    fn recover_key_from_decrypted_signature() -> crate::Result<()> {
         let x0 = KeyPair::new_random();
         let x1 = KeyPair::new_random();
         let r0 = KeyPair::new_random();
@@ -376,7 +392,7 @@ mod test {
         let rec_key = RecoveryKey::try_from(encsig)?;
         let y_tag = recover(&sig, &rec_key)?;
 
-        assert_eq!(y.secret_key, y_tag.secret_key);
+        assert_eq!(y.expose_secret_key(), y_tag.expose_secret_key());
 
         Ok(())
     }
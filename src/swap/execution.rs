@@ -0,0 +1,1148 @@
+use crate::{
+    bitcoin::{self, Client, OutPoint},
+    ecdsa,
+    error::{Recovery, RequestError},
+    expiry::{unix_time_now, BlockClock, REDEEM_SAFETY_MARGIN},
+    grin::{self, recovery::RecoveryData},
+    history::{Action, ChainEvent, Entry},
+    ledger::LedgerOffer,
+    schnorr,
+    swap::{Ledger, Wallets},
+    watchtower::Package,
+    wire::{malformed, Decode, Encode},
+    Execute, KeyPair, LookFor, Receipt,
+};
+use serde::{Deserialize, Serialize};
+use std::{thread, time::Duration};
+
+/// How far a swap has progressed since the key exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    KeysExchanged,
+    Funded,
+    Redeemed,
+    Refunded,
+    // Given up before funding
+    Aborted,
+}
+
+impl Status {
+    pub fn is_final(self) -> bool {
+        match self {
+            Status::KeysExchanged | Status::Funded => false,
+            Status::Redeemed | Status::Refunded | Status::Aborted => true,
+        }
+    }
+}
+
+/// What a party does on the ledgers once the key exchange is over.
+pub enum Execution {
+    /// Fund the alpha ledger, then redeem on the beta ledger once Bob funded
+    /// it or refund once the alpha ledger allows it. Alice does not redeem
+    /// once Bob's refund is within `REDEEM_SAFETY_MARGIN`.
+    Alice { fund: Funder, redeem: Redeemer },
+    /// Fund the beta ledger once Alice funded the alpha ledger, then redeem
+    /// on the alpha ledger with the secret revealed by Alice's redeem or
+    /// refund once the beta ledger allows it.
+    Bob {
+        redeem: EncryptedRedeemer,
+        fund: Funder,
+        secret: Secret,
+    },
+}
+
+pub enum Funder {
+    Grin {
+        offer: grin::Offer,
+        fund: grin::action::Fund,
+        refund: grin::action::Refund,
+    },
+    Bitcoin {
+        offer: bitcoin::Offer,
+        fund: bitcoin::action::Fund,
+        refund: bitcoin::action::Refund,
+        // Rebuilds the refund from the keys if it has to be swept by hand
+        recovery_data: bitcoin::recovery::RecoveryData,
+    },
+}
+
+// Alice's side of the beta ledger
+pub enum Redeemer {
+    Grin {
+        offer: grin::Offer,
+        fund_event: grin::event::Fund,
+        redeem: grin::action::Redeem,
+    },
+    Bitcoin {
+        offer: bitcoin::Offer,
+        redeem: bitcoin::action::Redeem,
+    },
+}
+
+// Bob's side of the alpha ledger
+pub enum EncryptedRedeemer {
+    Grin {
+        fund_event: grin::event::Fund,
+        encrypted_redeem: grin::action::EncryptedRedeem,
+    },
+    Bitcoin {
+        encrypted_redeem: bitcoin::action::EncryptedRedeem,
+    },
+}
+
+/// Where Bob learns `y` from once Alice redeems on the beta ledger.
+pub enum Secret {
+    Grin {
+        redeem_event: grin::event::Redeem,
+        recovery_key: schnorr::RecoveryKey,
+    },
+    Bitcoin {
+        fund_outpoint: OutPoint,
+        redeem_event: bitcoin::event::Redeem,
+        recovery_key: ecdsa::RecoveryKey,
+    },
+}
+
+/// The outcome of a step of the execution.
+pub struct Step {
+    pub status: Status,
+    /// What was observed on the ledgers and done about it, for the history
+    pub entries: Vec<Entry>,
+}
+
+impl Execution {
+    /// Take the next step of the swap if the ledgers allow it, returning the
+    /// resulting status. The status is unchanged if there is nothing to do
+    /// yet.
+    pub fn step(&self, status: Status, wallets: &Wallets) -> crate::Result<Step> {
+        let mut entries = Vec::new();
+
+        let status = match (self, status) {
+            (Execution::Alice { fund, .. }, Status::KeysExchanged) => {
+                entries.push(fund.fund(wallets)?);
+                Status::Funded
+            }
+            (Execution::Alice { fund, redeem }, Status::Funded) => {
+                if redeem.is_funded(wallets)? && !redeem.deadline_passed(wallets)? {
+                    entries.push(observed(redeem.ledger(), ChainEvent::Funded));
+                    entries.push(redeem.redeem(wallets)?);
+                    Status::Redeemed
+                } else if fund.refund_unlocked(wallets)? {
+                    entries.push(observed(fund.ledger(), ChainEvent::RefundUnlocked));
+                    entries.push(fund.refund(wallets)?);
+                    Status::Refunded
+                } else {
+                    Status::Funded
+                }
+            }
+            (Execution::Bob { redeem, fund, .. }, Status::KeysExchanged) => {
+                if redeem.is_funded(wallets)? {
+                    entries.push(observed(redeem.ledger(), ChainEvent::Funded));
+                    entries.push(fund.fund(wallets)?);
+                    Status::Funded
+                } else if fund.refund_unlocked(wallets)? {
+                    // Alice never funded, and funding now would leave her with
+                    // the option to refund on the alpha ledger after Bob
+                    entries.push(observed(fund.ledger(), ChainEvent::RefundUnlocked));
+                    Status::Aborted
+                } else {
+                    Status::KeysExchanged
+                }
+            }
+            (
+                Execution::Bob {
+                    redeem,
+                    fund,
+                    secret,
+                },
+                Status::Funded,
+            ) => {
+                if let Some(y) = secret.look_for(wallets)? {
+                    entries.push(observed(fund.ledger(), ChainEvent::Redeemed));
+                    entries.push(redeem.redeem(&y, wallets)?);
+                    Status::Redeemed
+                } else if fund.refund_unlocked(wallets)? {
+                    entries.push(observed(fund.ledger(), ChainEvent::RefundUnlocked));
+                    entries.push(fund.refund(wallets)?);
+                    Status::Refunded
+                } else {
+                    Status::Funded
+                }
+            }
+            (_, status) => status,
+        };
+
+        Ok(Step { status, entries })
+    }
+
+    /// Step through the swap until it is over, calling `on_step` after every
+    /// change of status so that it can be saved.
+    ///
+    /// Steps failing with an error which can be retried, such as an
+    /// unreachable node, are reported to `on_retry` and taken again after
+    /// `poll_interval`. Any other error ends the run.
+    pub fn run(
+        &self,
+        mut status: Status,
+        wallets: &Wallets,
+        poll_interval: Duration,
+        mut on_step: impl FnMut(Step) -> crate::Result<()>,
+        mut on_retry: impl FnMut(&crate::Error),
+    ) -> crate::Result<Status> {
+        while !status.is_final() {
+            let step = match self.step(status, wallets) {
+                Ok(step) => step,
+                Err(e) if Recovery::of(&e) == Some(Recovery::Retry) => {
+                    on_retry(&e);
+                    thread::sleep(poll_interval);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if step.status == status {
+                thread::sleep(poll_interval);
+            } else {
+                status = step.status;
+                on_step(step)?;
+            }
+        }
+
+        Ok(status)
+    }
+
+    /// Refund without waiting for the peer any longer. The refund lock must
+    /// have been reached already.
+    pub fn refund(&self, status: Status, wallets: &Wallets) -> crate::Result<Step> {
+        let fund = match self {
+            Execution::Alice { fund, .. } | Execution::Bob { fund, .. } => fund,
+        };
+
+        match status {
+            // Nothing to get back yet
+            Status::KeysExchanged => Ok(Step {
+                status: Status::Aborted,
+                entries: Vec::new(),
+            }),
+            Status::Funded => {
+                if !fund.refund_unlocked(wallets)? {
+                    return Err(RequestError::Refused("the refund is still locked".into()).into());
+                }
+
+                Ok(Step {
+                    status: Status::Refunded,
+                    entries: vec![fund.refund(wallets)?],
+                })
+            }
+            status => Err(RequestError::Refused(format!("swap is already {:?}", status)).into()),
+        }
+    }
+
+    /// What a watchtower needs to refund on our behalf. It holds no key which
+    /// could spend our funds elsewhere.
+    pub fn refund_package(&self) -> Package {
+        let fund = match self {
+            Execution::Alice { fund, .. } | Execution::Bob { fund, .. } => fund,
+        };
+
+        match fund {
+            Funder::Grin { offer, refund, .. } => Package::Grin {
+                transaction: refund.transaction_to_special_output().clone(),
+                lock: offer.refund_lock(),
+            },
+            Funder::Bitcoin { offer, refund, .. } => Package::Bitcoin {
+                refund: refund.clone(),
+                lock: offer.refund_lock(),
+            },
+        }
+    }
+
+    /// The special outputs of Grin this party may have to sweep into its
+    /// wallet if a transaction of the swap was only partially published.
+    pub fn grin_recovery_data(&self, wallets: &Wallets) -> crate::Result<Vec<RecoveryData>> {
+        let mut recovery_data = Vec::new();
+
+        match self {
+            Execution::Alice { fund, redeem } => {
+                if let Funder::Grin {
+                    offer,
+                    fund,
+                    refund,
+                } = fund
+                {
+                    recovery_data.push(fund.recovery_data(offer.spend_fee()));
+                    recovery_data.push(refund.recovery_data());
+                }
+                if let Redeemer::Grin { redeem, .. } = redeem {
+                    recovery_data.push(redeem.recovery_data());
+                }
+            }
+            Execution::Bob {
+                redeem,
+                fund,
+                secret,
+            } => {
+                if let Funder::Grin {
+                    offer,
+                    fund,
+                    refund,
+                } = fund
+                {
+                    recovery_data.push(fund.recovery_data(offer.spend_fee()));
+                    recovery_data.push(refund.recovery_data());
+                }
+                // The redeem transaction is only known once Alice revealed `y`
+                if let EncryptedRedeemer::Grin {
+                    encrypted_redeem, ..
+                } = redeem
+                {
+                    if let Some(y) = secret.look_for(wallets)? {
+                        recovery_data.push(encrypted_redeem.clone().decrypt(&y)?.recovery_data());
+                    }
+                }
+            }
+        }
+
+        Ok(recovery_data)
+    }
+
+    /// What this party needs to sweep its Bitcoin fund output back into its
+    /// wallet, if it funds on Bitcoin.
+    pub fn bitcoin_recovery_data(&self) -> Option<&bitcoin::recovery::RecoveryData> {
+        match self {
+            Execution::Alice {
+                fund: Funder::Bitcoin { recovery_data, .. },
+                ..
+            }
+            | Execution::Bob {
+                fund: Funder::Bitcoin { recovery_data, .. },
+                ..
+            } => Some(recovery_data),
+            _ => None,
+        }
+    }
+}
+
+impl Funder {
+    fn ledger(&self) -> Ledger {
+        match self {
+            Funder::Grin { .. } => Ledger::Grin,
+            Funder::Bitcoin { .. } => Ledger::Bitcoin,
+        }
+    }
+
+    fn fund(&self, wallets: &Wallets) -> crate::Result<Entry> {
+        match self {
+            Funder::Grin { fund, .. } => fund.clone().execute(&*wallets.grin),
+            Funder::Bitcoin { fund, .. } => fund.clone().execute(wallets.bitcoin_funder()?),
+        }
+        .map(|receipt| executed(Action::Fund, receipt))
+    }
+
+    fn refund(&self, wallets: &Wallets) -> crate::Result<Entry> {
+        match self {
+            Funder::Grin { refund, .. } => refund.clone().execute(&*wallets.grin),
+            Funder::Bitcoin { refund, .. } => refund.clone().execute(wallets.bitcoin_funder()?),
+        }
+        .map(|receipt| executed(Action::Refund, receipt))
+    }
+
+    fn refund_unlocked(&self, wallets: &Wallets) -> crate::Result<bool> {
+        match self {
+            Funder::Grin { offer, .. } => offer
+                .refund_lock()
+                .is_unlocked(wallets.grin.get_chain_tip()?, None),
+            Funder::Bitcoin { offer, .. } => offer.refund_lock().is_unlocked(
+                wallets.bitcoin.get_block_count()?,
+                Some(wallets.bitcoin.get_median_time_past()?),
+            ),
+        }
+    }
+}
+
+impl Redeemer {
+    fn ledger(&self) -> Ledger {
+        match self {
+            Redeemer::Grin { .. } => Ledger::Grin,
+            Redeemer::Bitcoin { .. } => Ledger::Bitcoin,
+        }
+    }
+
+    fn is_funded(&self, wallets: &Wallets) -> crate::Result<bool> {
+        match self {
+            Redeemer::Grin { fund_event, .. } => grin_is_funded(fund_event, wallets),
+            Redeemer::Bitcoin { redeem, .. } => bitcoin_is_funded(&redeem.transaction, wallets),
+        }
+    }
+
+    // Whether Bob's refund unlocks too soon for our redeem to be confirmed
+    // before it
+    fn deadline_passed(&self, wallets: &Wallets) -> crate::Result<bool> {
+        let unlock_time = match self {
+            Redeemer::Grin { offer, .. } => {
+                BlockClock::anchored_now(wallets.grin.get_chain_tip()?, grin::BLOCK_TIME_SEC)
+                    .unlock_time(offer.refund_lock())
+            }
+            // A timestamp lock is compared to the median time past, which runs
+            // behind the wall clock, so the refund unlocks later than this
+            Redeemer::Bitcoin { offer, .. } => {
+                BlockClock::anchored_now(wallets.bitcoin.get_block_count()?, offer.chain.block_time)
+                    .unlock_time(offer.refund_lock())
+            }
+        };
+
+        Ok(unlock_time <= unix_time_now() + REDEEM_SAFETY_MARGIN)
+    }
+
+    fn redeem(&self, wallets: &Wallets) -> crate::Result<Entry> {
+        match self {
+            Redeemer::Grin { redeem, .. } => redeem.clone().execute(&*wallets.grin),
+            Redeemer::Bitcoin { redeem, .. } => redeem.clone().execute(&wallets.bitcoin),
+        }
+        .map(|receipt| executed(Action::Redeem, receipt))
+    }
+}
+
+impl EncryptedRedeemer {
+    fn ledger(&self) -> Ledger {
+        match self {
+            EncryptedRedeemer::Grin { .. } => Ledger::Grin,
+            EncryptedRedeemer::Bitcoin { .. } => Ledger::Bitcoin,
+        }
+    }
+
+    fn is_funded(&self, wallets: &Wallets) -> crate::Result<bool> {
+        match self {
+            EncryptedRedeemer::Grin { fund_event, .. } => grin_is_funded(fund_event, wallets),
+            EncryptedRedeemer::Bitcoin { encrypted_redeem } => {
+                bitcoin_is_funded(&encrypted_redeem.transaction, wallets)
+            }
+        }
+    }
+
+    fn redeem(&self, y: &KeyPair, wallets: &Wallets) -> crate::Result<Entry> {
+        match self {
+            EncryptedRedeemer::Grin {
+                encrypted_redeem, ..
+            } => encrypted_redeem.clone().decrypt(y)?.execute(&*wallets.grin),
+            EncryptedRedeemer::Bitcoin { encrypted_redeem } => encrypted_redeem
+                .clone()
+                .decrypt(y)
+                .execute(&wallets.bitcoin),
+        }
+        .map(|receipt| executed(Action::Redeem, receipt))
+    }
+}
+
+fn observed(ledger: Ledger, event: ChainEvent) -> Entry {
+    Entry::EventObserved { ledger, event }
+}
+
+fn executed(action: Action, receipt: Receipt) -> Entry {
+    Entry::ActionExecuted { action, receipt }
+}
+
+impl Secret {
+    fn look_for(&self, wallets: &Wallets) -> crate::Result<Option<KeyPair>> {
+        match self {
+            Secret::Grin {
+                redeem_event,
+                recovery_key,
+            } => {
+                if wallets
+                    .grin
+                    .find_kernel_height(&redeem_event.excess)?
+                    .is_none()
+                {
+                    return Ok(None);
+                }
+
+                let sig = wallets.grin.look_for(redeem_event.clone())?;
+                schnorr::recover(&sig, recovery_key).map(Some)
+            }
+            Secret::Bitcoin {
+                fund_outpoint,
+                redeem_event,
+                recovery_key,
+            } => {
+                // The fund output can only be spent by Alice's redeem as long
+                // as Bob has not refunded
+                if wallets.bitcoin.get_txout(fund_outpoint)?.is_some() {
+                    return Ok(None);
+                }
+
+                let sig = wallets.bitcoin_funder()?.look_for(redeem_event.clone())?;
+                ecdsa::recover(&sig, recovery_key).map(Some)
+            }
+        }
+    }
+}
+
+// Funded once the fund kernel has `min_confirmations` and its output is still
+// unspent
+fn grin_is_funded(fund_event: &grin::event::Fund, wallets: &Wallets) -> crate::Result<bool> {
+    Ok(wallets
+        .grin
+        .look_for(fund_event.clone())?
+        .map_or(false, |confirmed| {
+            confirmed.confirmations >= wallets.min_confirmations.grin
+        }))
+}
+
+// The redeem transaction spends the fund output, whose outpoint commits to
+// the whole fund transaction and therefore to the amount
+fn bitcoin_is_funded(
+    redeem_transaction: &bitcoin::Transaction,
+    wallets: &Wallets,
+) -> crate::Result<bool> {
+    Ok(wallets
+        .bitcoin
+        .get_txout_confirmations(&redeem_transaction.input[0].previous_output)?
+        .map_or(false, |confirmations| {
+            confirmations >= wallets.min_confirmations.bitcoin
+        }))
+}
+
+impl Encode for Execution {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Execution::Alice { fund, redeem } => {
+                0u8.encode(buf);
+                fund.encode(buf);
+                redeem.encode(buf);
+            }
+            Execution::Bob {
+                redeem,
+                fund,
+                secret,
+            } => {
+                1u8.encode(buf);
+                redeem.encode(buf);
+                fund.encode(buf);
+                secret.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for Execution {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(Execution::Alice {
+                fund: Decode::decode(bytes)?,
+                redeem: Decode::decode(bytes)?,
+            }),
+            1 => Ok(Execution::Bob {
+                redeem: Decode::decode(bytes)?,
+                fund: Decode::decode(bytes)?,
+                secret: Decode::decode(bytes)?,
+            }),
+            other => Err(malformed(format!("unknown role {}", other))),
+        }
+    }
+}
+
+impl Encode for Funder {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Funder::Grin {
+                offer,
+                fund,
+                refund,
+            } => {
+                0u8.encode(buf);
+                offer.encode(buf);
+                fund.encode(buf);
+                refund.encode(buf);
+            }
+            Funder::Bitcoin {
+                offer,
+                fund,
+                refund,
+                recovery_data,
+            } => {
+                1u8.encode(buf);
+                offer.encode(buf);
+                fund.encode(buf);
+                refund.encode(buf);
+                recovery_data.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for Funder {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(Funder::Grin {
+                offer: Decode::decode(bytes)?,
+                fund: Decode::decode(bytes)?,
+                refund: Decode::decode(bytes)?,
+            }),
+            1 => Ok(Funder::Bitcoin {
+                offer: Decode::decode(bytes)?,
+                fund: Decode::decode(bytes)?,
+                refund: Decode::decode(bytes)?,
+                recovery_data: Decode::decode(bytes)?,
+            }),
+            other => Err(malformed(format!("unknown ledger {}", other))),
+        }
+    }
+}
+
+impl Encode for Redeemer {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Redeemer::Grin {
+                offer,
+                fund_event,
+                redeem,
+            } => {
+                0u8.encode(buf);
+                offer.encode(buf);
+                fund_event.encode(buf);
+                redeem.encode(buf);
+            }
+            Redeemer::Bitcoin { offer, redeem } => {
+                1u8.encode(buf);
+                offer.encode(buf);
+                redeem.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for Redeemer {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(Redeemer::Grin {
+                offer: Decode::decode(bytes)?,
+                fund_event: Decode::decode(bytes)?,
+                redeem: Decode::decode(bytes)?,
+            }),
+            1 => Ok(Redeemer::Bitcoin {
+                offer: Decode::decode(bytes)?,
+                redeem: Decode::decode(bytes)?,
+            }),
+            other => Err(malformed(format!("unknown ledger {}", other))),
+        }
+    }
+}
+
+impl Encode for EncryptedRedeemer {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            EncryptedRedeemer::Grin {
+                fund_event,
+                encrypted_redeem,
+            } => {
+                0u8.encode(buf);
+                fund_event.encode(buf);
+                encrypted_redeem.encode(buf);
+            }
+            EncryptedRedeemer::Bitcoin { encrypted_redeem } => {
+                1u8.encode(buf);
+                encrypted_redeem.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for EncryptedRedeemer {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(EncryptedRedeemer::Grin {
+                fund_event: Decode::decode(bytes)?,
+                encrypted_redeem: Decode::decode(bytes)?,
+            }),
+            1 => Ok(EncryptedRedeemer::Bitcoin {
+                encrypted_redeem: Decode::decode(bytes)?,
+            }),
+            other => Err(malformed(format!("unknown ledger {}", other))),
+        }
+    }
+}
+
+impl Encode for Secret {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Secret::Grin {
+                redeem_event,
+                recovery_key,
+            } => {
+                0u8.encode(buf);
+                redeem_event.encode(buf);
+                recovery_key.encode(buf);
+            }
+            Secret::Bitcoin {
+                fund_outpoint,
+                redeem_event,
+                recovery_key,
+            } => {
+                1u8.encode(buf);
+                fund_outpoint.encode(buf);
+                redeem_event.encode(buf);
+                recovery_key.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for Secret {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(Secret::Grin {
+                redeem_event: Decode::decode(bytes)?,
+                recovery_key: Decode::decode(bytes)?,
+            }),
+            1 => Ok(Secret::Bitcoin {
+                fund_outpoint: Decode::decode(bytes)?,
+                redeem_event: Decode::decode(bytes)?,
+                recovery_key: Decode::decode(bytes)?,
+            }),
+            other => Err(malformed(format!("unknown ledger {}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::{
+        alice::Alice0,
+        bitcoin::{wallet::RedeemerWallet, TxOut, WalletOutputs},
+        bob::Bob0,
+        error::{GrinError, WalletError},
+        expiry::{BlockClock, ExpiryValidator},
+        grin::GrinWallet,
+        messages::SwapId,
+        negotiation::{OfferPair, Party, Roles},
+        swap::{BitcoinAddresses, MinConfirmations, Offers, Terms},
+    };
+    use grin_core::core::{Transaction, TxKernel};
+    use grin_wallet_libwallet::{ParticipantData, Slate};
+    use secp256k1zkp::pedersen::Commitment;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    const NOW: u64 = 1_600_000_000;
+    pub(crate) const GRIN_HEIGHT: u64 = 1_000;
+    pub(crate) const GRIN_EXPIRY: u64 = GRIN_HEIGHT + 24 * 60;
+
+    // Sees every kernel and unspent output at `fund_height` if set, and
+    // finalizes every invoice it issues as is. The node cannot be reached for
+    // the first `node_failures` queries of the tip
+    struct StubWallet {
+        tip: u64,
+        fund_height: Option<u64>,
+        node_failures: AtomicUsize,
+        posted: Arc<Mutex<Vec<Transaction>>>,
+    }
+
+    impl GrinWallet for StubWallet {
+        fn get_chain_tip(&self) -> crate::Result<u64> {
+            if self.node_failures.load(Ordering::SeqCst) > 0 {
+                self.node_failures.fetch_sub(1, Ordering::SeqCst);
+                return Err(GrinError::Node("connection refused".into()).into());
+            }
+
+            Ok(self.tip)
+        }
+
+        fn process_invoice(&self, _: Slate) -> crate::Result<Slate> {
+            Err(WalletError::Unavailable("not used in this test".into()).into())
+        }
+
+        fn issue_invoice(&self, amount: u64) -> crate::Result<Slate> {
+            let mut slate = Slate::blank(2);
+            slate.amount = amount;
+            slate.participant_data.push(ParticipantData {
+                id: 1,
+                public_blind_excess: KeyPair::new_random().public_key,
+                public_nonce: KeyPair::new_random().public_key,
+                part_sig: None,
+                message: None,
+                message_sig: None,
+            });
+
+            Ok(slate)
+        }
+
+        fn finalize_invoice(&self, slate: Slate) -> crate::Result<Transaction> {
+            Ok(slate.tx)
+        }
+
+        fn post_transaction(&self, transaction: Transaction) -> crate::Result<()> {
+            self.posted
+                .lock()
+                .expect("posted lock poisoned")
+                .push(transaction);
+            Ok(())
+        }
+
+        fn get_balance(&self) -> crate::Result<u64> {
+            Err(WalletError::Unavailable("not used in this test".into()).into())
+        }
+
+        fn find_kernel(&self, _: &Commitment) -> crate::Result<TxKernel> {
+            Err(WalletError::Unavailable("not used in this test".into()).into())
+        }
+
+        fn find_kernel_height(&self, _: &Commitment) -> crate::Result<Option<u64>> {
+            Ok(self.fund_height)
+        }
+
+        fn find_unspent_output_height(&self, _: &Commitment) -> crate::Result<Option<u64>> {
+            Ok(self.fund_height)
+        }
+    }
+
+    /// Wallets whose Grin chain is at `grin_tip`, with the Grin transactions
+    /// they post. The Bitcoin node is never reached.
+    pub(crate) fn wallets(grin_tip: u64) -> (Wallets, Arc<Mutex<Vec<Transaction>>>) {
+        stub_wallets(grin_tip, None, 0)
+    }
+
+    // Wallets which see the Grin fund transaction confirmed at `GRIN_HEIGHT`
+    fn funded_wallets(grin_tip: u64) -> (Wallets, Arc<Mutex<Vec<Transaction>>>) {
+        stub_wallets(grin_tip, Some(GRIN_HEIGHT), 0)
+    }
+
+    fn stub_wallets(
+        grin_tip: u64,
+        fund_height: Option<u64>,
+        node_failures: usize,
+    ) -> (Wallets, Arc<Mutex<Vec<Transaction>>>) {
+        let posted = Arc::new(Mutex::new(Vec::new()));
+        let wallets = Wallets {
+            grin: Box::new(StubWallet {
+                tip: grin_tip,
+                fund_height,
+                node_failures: AtomicUsize::new(node_failures),
+                posted: posted.clone(),
+            }),
+            bitcoin: RedeemerWallet::new("http://localhost:1".into()),
+            bitcoin_funder: None,
+            grin_seed: grin::SwapSeed::new_random(),
+            bitcoin_addresses: BitcoinAddresses {
+                change: KeyPair::new_random().to_bitcoin_address(),
+                refund: KeyPair::new_random().to_bitcoin_address(),
+                redeem: KeyPair::new_random().to_bitcoin_address(),
+            },
+            min_confirmations: MinConfirmations::default(),
+        };
+
+        (wallets, posted)
+    }
+
+    /// Bob's side of a swap of bitcoin for grin after the key exchange, which
+    /// funds on Grin.
+    pub(crate) fn bob_funds_grin() -> crate::Result<(Terms, Execution)> {
+        let (terms, _, bob) = bitcoin_for_grin()?;
+
+        Ok((terms, bob))
+    }
+
+    // Alice's side of a swap of bitcoin for grin, which redeems on Grin
+    fn alice_redeems_grin() -> crate::Result<Execution> {
+        let (_, alice, _) = bitcoin_for_grin()?;
+
+        Ok(alice)
+    }
+
+    // The executions of Alice and Bob once the key exchange is over, as seen
+    // by Bob
+    fn bitcoin_for_grin() -> crate::Result<(Terms, Execution, Execution)> {
+        let validator = ExpiryValidator::new(
+            BlockClock {
+                anchor_height: 100,
+                anchor_time: NOW,
+                block_time: 600,
+            },
+            BlockClock {
+                anchor_height: GRIN_HEIGHT,
+                anchor_time: NOW,
+                block_time: grin::BLOCK_TIME_SEC,
+            },
+        );
+        let offers = OfferPair {
+            alpha: bitcoin::Offer {
+                asset: 100_000_000,
+                fee: 1_000,
+                expiry: (NOW + 2 * 24 * 60 * 60) as u32,
+                chain: bitcoin::ChainParams::bitcoin_regtest(),
+            },
+            beta: grin::Offer {
+                asset: 10_000_000_000,
+                base_fee: grin::DEFAULT_BASE_FEE,
+                expiry: grin::Expiry::Absolute(GRIN_EXPIRY),
+            },
+        };
+
+        let funder = grin::SpecialOutputKeyPairsFunder::new_random();
+        let redeemer = grin::SpecialOutputKeyPairsRedeemer::new_random();
+        let special_outputs = grin::SpecialOutputs {
+            fund_input_key: funder.fund_input_key.public_key,
+            redeem_output_key: redeemer.redeem_output_key.public_key,
+            refund_output_key: funder.refund_output_key.public_key,
+        };
+
+        let fund_input_keypair = KeyPair::new_random();
+        let fund_input = TxOut {
+            value: offers.alpha.asset + 2 * offers.alpha.fee + 1_000_000,
+            script_pubkey: fund_input_keypair.to_bitcoin_address().script_pubkey(),
+        };
+        let wallet_outputs = WalletOutputs {
+            fund_input: bitcoin::wallet::Output::new(
+                fund_input_keypair,
+                OutPoint::null(),
+                fund_input,
+            )
+            .into(),
+            fund_change_address: KeyPair::new_random().to_bitcoin_address(),
+            redeem_address: KeyPair::new_random().to_bitcoin_address(),
+            refund_address: KeyPair::new_random().to_bitcoin_address(),
+        };
+
+        let swap_id = SwapId::random();
+        let (alice0, message0) = Alice0::new(
+            swap_id,
+            bitcoin::AliceFunder0::new(offers.alpha.clone(), wallet_outputs.clone()),
+            grin::AliceRedeemer0::new(offers.beta.clone(), special_outputs.clone(), redeemer)?,
+            &validator,
+        )?;
+        let (bob0, message1) = Bob0::new(
+            swap_id,
+            bitcoin::BobRedeemer0::new(offers.alpha.clone(), wallet_outputs),
+            grin::BobFunder0::new(offers.beta.clone(), special_outputs, funder)?,
+            &validator,
+            message0,
+        )?;
+        let (alice1, message2) = alice0.receive(message1)?;
+        let (bob1, message3) = bob0.receive(message2)?;
+        let (alice2, message4) = alice1.receive(message3)?;
+        let bob2 = bob1.receive(message4)?;
+
+        let alice = Execution::Alice {
+            fund: Funder::Bitcoin {
+                offer: offers.alpha.clone(),
+                fund: alice2.alpha_state.fund_action,
+                refund: alice2.alpha_state.refund_action,
+                recovery_data: alice2.alpha_state.recovery_data,
+            },
+            redeem: Redeemer::Grin {
+                offer: offers.beta.clone(),
+                fund_event: alice2.beta_state.fund_event,
+                redeem: alice2.beta_state.redeem_action,
+            },
+        };
+        let bob = Execution::Bob {
+            redeem: EncryptedRedeemer::Bitcoin {
+                encrypted_redeem: bob2.alpha_state.encrypted_redeem_action,
+            },
+            fund: Funder::Grin {
+                offer: offers.beta.clone(),
+                fund: bob2.beta_state.fund_action,
+                refund: bob2.beta_state.refund_action,
+            },
+            secret: Secret::Grin {
+                redeem_event: bob2.beta_state.redeem_event,
+                recovery_key: bob2.beta_state.recovery_key,
+            },
+        };
+        let terms = Terms {
+            offers: Offers::BitcoinGrin(offers),
+            roles: Roles {
+                alice: Party::Taker,
+                initiator: Party::Taker,
+            },
+            party: Party::Maker,
+        };
+
+        Ok((terms, alice, bob))
+    }
+
+    fn posted_count(posted: &Mutex<Vec<Transaction>>) -> usize {
+        posted.lock().expect("posted lock poisoned").len()
+    }
+
+    #[test]
+    fn refund_before_funding_aborts() -> crate::Result<()> {
+        let (_, execution) = bob_funds_grin()?;
+        let (wallets, posted) = wallets(GRIN_HEIGHT);
+
+        let step = execution.refund(Status::KeysExchanged, &wallets)?;
+
+        assert_eq!(step.status, Status::Aborted);
+        assert!(step.entries.is_empty());
+        assert_eq!(posted_count(&posted), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refund_waits_for_the_refund_lock() -> crate::Result<()> {
+        let (_, execution) = bob_funds_grin()?;
+
+        let (wallets, posted) = wallets(GRIN_EXPIRY - 1);
+        assert!(execution.refund(Status::Funded, &wallets).is_err());
+        assert_eq!(posted_count(&posted), 0);
+
+        let (wallets, posted) = wallets(GRIN_EXPIRY);
+        let step = execution.refund(Status::Funded, &wallets)?;
+        assert_eq!(step.status, Status::Refunded);
+        match step.entries.as_slice() {
+            [Entry::ActionExecuted {
+                action: Action::Refund,
+                ..
+            }] => {}
+            entries => panic!("unexpected entries {:?}", entries),
+        }
+        assert_eq!(posted_count(&posted), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refund_is_rejected_once_over() -> crate::Result<()> {
+        let (_, execution) = bob_funds_grin()?;
+        let (wallets, posted) = wallets(GRIN_EXPIRY);
+
+        for &status in &[Status::Redeemed, Status::Refunded, Status::Aborted] {
+            assert!(execution.refund(status, &wallets).is_err());
+        }
+        assert_eq!(posted_count(&posted), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn funded_swap_is_refunded_once_unlocked() -> crate::Result<()> {
+        let (_, execution) = bob_funds_grin()?;
+
+        // Alice has not redeemed, but the refund is still locked
+        let (wallets, posted) = wallets(GRIN_EXPIRY - 1);
+        let step = execution.step(Status::Funded, &wallets)?;
+        assert_eq!(step.status, Status::Funded);
+        assert!(step.entries.is_empty());
+        assert_eq!(posted_count(&posted), 0);
+
+        let (wallets, posted) = wallets(GRIN_EXPIRY);
+        let step = execution.step(Status::Funded, &wallets)?;
+        assert_eq!(step.status, Status::Refunded);
+        match step.entries.as_slice() {
+            [Entry::EventObserved {
+                ledger: Ledger::Grin,
+                event: ChainEvent::RefundUnlocked,
+            }, Entry::ActionExecuted {
+                action: Action::Refund,
+                ..
+            }] => {}
+            entries => panic!("unexpected entries {:?}", entries),
+        }
+        assert_eq!(posted_count(&posted), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn alice_stops_redeeming_within_the_safety_margin() -> crate::Result<()> {
+        let execution = alice_redeems_grin()?;
+        let redeem = match &execution {
+            Execution::Alice { redeem, .. } => redeem,
+            Execution::Bob { .. } => unreachable!(),
+        };
+
+        let margin_blocks = REDEEM_SAFETY_MARGIN / grin::BLOCK_TIME_SEC;
+        let (wallets, _) = funded_wallets(GRIN_EXPIRY - margin_blocks + 1);
+        assert!(redeem.deadline_passed(&wallets)?);
+
+        let (wallets, posted) = funded_wallets(GRIN_EXPIRY - margin_blocks - 10);
+        assert!(!redeem.deadline_passed(&wallets)?);
+        let step = execution.step(Status::Funded, &wallets)?;
+        assert_eq!(step.status, Status::Redeemed);
+        match step.entries.as_slice() {
+            [Entry::EventObserved {
+                ledger: Ledger::Grin,
+                event: ChainEvent::Funded,
+            }, Entry::ActionExecuted {
+                action: Action::Redeem,
+                ..
+            }] => {}
+            entries => panic!("unexpected entries {:?}", entries),
+        }
+        assert_eq!(posted_count(&posted), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_retries_while_the_node_is_unreachable() -> crate::Result<()> {
+        let (_, execution) = bob_funds_grin()?;
+        let (wallets, posted) = stub_wallets(GRIN_EXPIRY, None, 2);
+
+        let mut steps = Vec::new();
+        let mut retries = 0;
+        let status = execution.run(
+            Status::Funded,
+            &wallets,
+            Duration::from_millis(1),
+            |step| {
+                steps.push(step.status);
+                Ok(())
+            },
+            |_| retries += 1,
+        )?;
+
+        assert_eq!(status, Status::Refunded);
+        assert_eq!(steps, vec![Status::Refunded]);
+        assert_eq!(retries, 2);
+        assert_eq!(posted_count(&posted), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bitcoin_recovery_data_spends_the_fund_transaction() -> crate::Result<()> {
+        let alice =
+            crate::wire::from_bytes::<Execution>(&crate::wire::to_bytes(&alice_redeems_grin()?))?;
+        let fund = match &alice {
+            Execution::Alice {
+                fund: Funder::Bitcoin { fund, .. },
+                ..
+            } => fund,
+            _ => unreachable!(),
+        };
+
+        let recovery_data = alice
+            .bitcoin_recovery_data()
+            .expect("Alice funds on Bitcoin");
+        assert_eq!(recovery_data.fund_outpoint()?, OutPoint {
+            txid: fund.transaction.txid(),
+            vout: 0,
+        });
+
+        let (_, bob) = bob_funds_grin()?;
+        assert!(bob.bitcoin_recovery_data().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn final_status_is_not_stepped() -> crate::Result<()> {
+        let (_, execution) = bob_funds_grin()?;
+        let (wallets, posted) = wallets(GRIN_EXPIRY);
+
+        for &status in &[Status::Redeemed, Status::Refunded, Status::Aborted] {
+            let step = execution.step(status, &wallets)?;
+            assert_eq!(step.status, status);
+            assert!(step.entries.is_empty());
+        }
+        assert_eq!(posted_count(&posted), 0);
+
+        Ok(())
+    }
+}
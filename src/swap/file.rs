@@ -0,0 +1,154 @@
+use crate::{
+    error::{Context, Error, RequestError, StorageError},
+    expiry::unix_time_now,
+    keypair::{PublicKey, SECP},
+    messages::SwapId,
+    swap::{Execution, Ledger, Role, Status, Terms},
+    wire,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use zeroize::Zeroize;
+
+/// What is kept on disk about a swap, one JSON file per swap.
+///
+/// The execution holds the secret keys of the swap transactions and must be
+/// protected like the wallets themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapFile {
+    pub swap_id: String,
+    pub role: Role,
+    pub alpha_ledger: Ledger,
+    pub alpha_asset: u64,
+    pub beta_ledger: Ledger,
+    pub beta_asset: u64,
+    // Identity key of the peer, hex encoded
+    pub peer: String,
+    pub status: Status,
+    // Seconds since the UNIX epoch
+    pub updated_at: u64,
+    // `Execution` in the wire format, hex encoded
+    execution: ExecutionHex,
+}
+
+// Holds the secret keys of the execution, like `KeyPair`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+struct ExecutionHex(String);
+
+impl fmt::Debug for ExecutionHex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl Drop for ExecutionHex {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl SwapFile {
+    pub fn new(swap_id: SwapId, terms: &Terms, peer: &PublicKey, execution: &Execution) -> Self {
+        let (alpha_ledger, alpha_asset) = terms.offers.alpha();
+        let (beta_ledger, beta_asset) = terms.offers.beta();
+
+        let mut execution_bytes = wire::to_bytes(execution);
+        let execution = ExecutionHex(hex::encode(&execution_bytes));
+        execution_bytes.zeroize();
+
+        Self {
+            swap_id: swap_id.to_string(),
+            role: terms.role(),
+            alpha_ledger,
+            alpha_asset,
+            beta_ledger,
+            beta_asset,
+            peer: hex::encode(&peer.serialize_vec(&*SECP, true)[..]),
+            status: Status::KeysExchanged,
+            updated_at: unix_time_now(),
+            execution,
+        }
+    }
+
+    pub fn path(dir: &Path, swap_id: &str) -> PathBuf {
+        dir.join(format!("{}.json", swap_id))
+    }
+
+    pub fn load(dir: &Path, swap_id: &str) -> crate::Result<Self> {
+        let path = Self::path(dir, swap_id);
+        let mut json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(RequestError::UnknownSwap(swap_id.to_string()).into());
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        let file = serde_json::from_str(&json);
+        json.zeroize();
+
+        file.map_err(|e| corrupted(&path, e))
+    }
+
+    /// Replace the file of the swap, so that a crash leaves either the
+    /// previous or the new version behind.
+    pub fn save(&self, dir: &Path) -> crate::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        let path = Self::path(dir, &self.swap_id);
+        let tmp_path = path.with_extension("json.tmp");
+        let mut json = serde_json::to_string_pretty(self).expect("swap files serialize to JSON");
+        let written = fs::write(&tmp_path, &json);
+        json.zeroize();
+        written?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    pub fn set_status(&mut self, status: Status) {
+        self.status = status;
+        self.updated_at = unix_time_now();
+    }
+
+    /// All the swaps saved in `dir`, oldest update first.
+    pub fn list(dir: &Path) -> crate::Result<Vec<Self>> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut swaps = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(false, |extension| extension == "json")
+            {
+                let json = fs::read_to_string(&path)?;
+                swaps.push(serde_json::from_str::<Self>(&json).map_err(|e| corrupted(&path, e))?);
+            }
+        }
+        swaps.sort_by_key(|swap| swap.updated_at);
+
+        Ok(swaps)
+    }
+
+    pub fn execution(&self) -> crate::Result<Execution> {
+        let corrupted_execution =
+            |e: &dyn fmt::Display| StorageError::Corrupted(format!("swap {}: {}", self.swap_id, e));
+
+        let mut execution_bytes =
+            hex::decode(&self.execution.0).map_err(|e| corrupted_execution(&e))?;
+        let execution = wire::from_bytes(&execution_bytes);
+        execution_bytes.zeroize();
+
+        Ok(execution.map_err(|e| corrupted_execution(&e))?)
+    }
+}
+
+fn corrupted(path: &Path, error: impl fmt::Display) -> Error {
+    StorageError::Corrupted(format!("swap file {}: {}", path.display(), error)).into()
+}
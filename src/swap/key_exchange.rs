@@ -0,0 +1,317 @@
+use crate::{
+    alice::{Alice0, Alice2, AliceResponder0},
+    bitcoin::{self, OutPoint},
+    bob::{Bob0, Bob2, BobInitiator0},
+    commit::CoinTossingKeys,
+    error::Error,
+    expiry::ExpiryValidator,
+    grin,
+    ledger::{
+        AliceFunder0, AliceFunder1, AliceRedeemer0, AliceRedeemer1, BobFunder0, BobFunder1,
+        BobRedeemer0, BobRedeemer1,
+    },
+    messages::{Phase, SwapId},
+    swap::{
+        execution::{EncryptedRedeemer, Funder, Redeemer, Secret},
+        setup::Outputs,
+        Execution, Offers, Peer, Role, Terms, Wallets,
+    },
+    wire::{Decode, Encode},
+};
+use std::{
+    convert::TryInto,
+    io::{Read, Write},
+};
+
+/// Run the protocol with the peer in the role agreed on in `terms`.
+///
+/// If one of our checks fails, the peer is told to abort before the error is
+/// returned.
+pub fn run<S: Read + Write>(
+    peer: &mut Peer<S>,
+    terms: &Terms,
+    outputs: Outputs,
+    wallets: &Wallets,
+) -> crate::Result<Execution> {
+    let mut phase = Phase::KeyExchange;
+
+    let result = execution(peer, terms, outputs, wallets, &mut phase);
+    if let Err(error) = &result {
+        match error.root() {
+            Error::Abort(_) => {}
+            _ => peer.abort(phase, error),
+        }
+    }
+
+    result
+}
+
+fn execution<S: Read + Write>(
+    peer: &mut Peer<S>,
+    terms: &Terms,
+    outputs: Outputs,
+    wallets: &Wallets,
+    phase: &mut Phase,
+) -> crate::Result<Execution> {
+    let swap_id = peer.swap_id();
+    let validator = wallets.expiry_validator(&terms.offers)?;
+    let Outputs {
+        special_outputs,
+        grin_keypairs,
+        wallet_outputs,
+    } = outputs;
+
+    match (&terms.offers, terms.role()) {
+        (Offers::GrinBitcoin(offers), Role::Alice) => {
+            let alpha = grin::AliceFunder0::new(
+                offers.alpha.clone(),
+                special_outputs,
+                grin_keypairs.funder()?,
+            )?;
+            let beta = bitcoin::AliceRedeemer0::new(offers.beta.clone(), wallet_outputs);
+
+            let alice2 = if terms.is_initiator() {
+                alice_initiates(peer, swap_id, alpha, beta, &validator, phase)?
+            } else {
+                alice_responds(peer, swap_id, alpha, beta, &validator, phase)?
+            };
+
+            Ok(Execution::Alice {
+                fund: Funder::Grin {
+                    offer: offers.alpha.clone(),
+                    fund: alice2.alpha_state.0.fund_action,
+                    refund: alice2.alpha_state.0.refund_action,
+                },
+                redeem: Redeemer::Bitcoin {
+                    offer: offers.beta.clone(),
+                    redeem: alice2.beta_state.redeem_action,
+                },
+            })
+        }
+        (Offers::GrinBitcoin(offers), Role::Bob) => {
+            let alpha = grin::BobRedeemer0::new(
+                offers.alpha.clone(),
+                special_outputs,
+                grin_keypairs.redeemer()?,
+            )?;
+            let beta = bitcoin::BobFunder0::new(offers.beta.clone(), wallet_outputs);
+
+            let bob2 = if terms.is_initiator() {
+                bob_initiates(peer, swap_id, alpha, beta, &validator, phase)?
+            } else {
+                bob_responds(peer, swap_id, alpha, beta, &validator, phase)?
+            };
+
+            let fund_outpoint = OutPoint {
+                txid: bob2.beta_state.fund_action.transaction.txid(),
+                vout: 0,
+            };
+
+            Ok(Execution::Bob {
+                redeem: EncryptedRedeemer::Grin {
+                    fund_event: bob2.alpha_state.fund_event,
+                    encrypted_redeem: bob2.alpha_state.encrypted_redeem_action,
+                },
+                fund: Funder::Bitcoin {
+                    offer: offers.beta.clone(),
+                    fund: bob2.beta_state.fund_action,
+                    refund: bob2.beta_state.refund_action,
+                    recovery_data: bob2.beta_state.recovery_data,
+                },
+                secret: Secret::Bitcoin {
+                    fund_outpoint,
+                    redeem_event: bob2.beta_state.redeem_event,
+                    recovery_key: bob2.beta_state.recovery_key,
+                },
+            })
+        }
+        (Offers::BitcoinGrin(offers), Role::Alice) => {
+            let alpha = bitcoin::AliceFunder0::new(offers.alpha.clone(), wallet_outputs);
+            let beta = grin::AliceRedeemer0::new(
+                offers.beta.clone(),
+                special_outputs,
+                grin_keypairs.redeemer()?,
+            )?;
+
+            let alice2 = if terms.is_initiator() {
+                alice_initiates(peer, swap_id, alpha, beta, &validator, phase)?
+            } else {
+                alice_responds(peer, swap_id, alpha, beta, &validator, phase)?
+            };
+
+            Ok(Execution::Alice {
+                fund: Funder::Bitcoin {
+                    offer: offers.alpha.clone(),
+                    fund: alice2.alpha_state.fund_action,
+                    refund: alice2.alpha_state.refund_action,
+                    recovery_data: alice2.alpha_state.recovery_data,
+                },
+                redeem: Redeemer::Grin {
+                    offer: offers.beta.clone(),
+                    fund_event: alice2.beta_state.fund_event,
+                    redeem: alice2.beta_state.redeem_action,
+                },
+            })
+        }
+        (Offers::BitcoinGrin(offers), Role::Bob) => {
+            let alpha = bitcoin::BobRedeemer0::new(offers.alpha.clone(), wallet_outputs);
+            let beta = grin::BobFunder0::new(
+                offers.beta.clone(),
+                special_outputs,
+                grin_keypairs.funder()?,
+            )?;
+
+            let bob2 = if terms.is_initiator() {
+                bob_initiates(peer, swap_id, alpha, beta, &validator, phase)?
+            } else {
+                bob_responds(peer, swap_id, alpha, beta, &validator, phase)?
+            };
+
+            Ok(Execution::Bob {
+                redeem: EncryptedRedeemer::Bitcoin {
+                    encrypted_redeem: bob2.alpha_state.encrypted_redeem_action,
+                },
+                fund: Funder::Grin {
+                    offer: offers.beta.clone(),
+                    fund: bob2.beta_state.fund_action,
+                    refund: bob2.beta_state.refund_action,
+                },
+                secret: Secret::Grin {
+                    redeem_event: bob2.beta_state.redeem_event,
+                    recovery_key: bob2.beta_state.recovery_key,
+                },
+            })
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn alice_initiates<S, AL, BL>(
+    peer: &mut Peer<S>,
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    validator: &ExpiryValidator,
+    phase: &mut Phase,
+) -> crate::Result<Alice2<<AL::Next as AliceFunder1>::Next, <BL::Next as AliceRedeemer1>::Next>>
+where
+    S: Read + Write,
+    AL: AliceFunder0,
+    BL: AliceRedeemer0,
+    AL::PKs: Decode,
+    BL::PKs: Decode,
+    BL::RedeemerSigs: Encode,
+    <AL::Next as AliceFunder1>::RedeemerSigs: Decode,
+    <AL::Next as AliceFunder1>::EncryptedSignature: Encode,
+    <BL::Next as AliceRedeemer1>::EncryptedSignature: Decode,
+{
+    let (alice0, message0) = Alice0::new(swap_id, alpha_state, beta_state, validator)?;
+    peer.send(&message0)?;
+
+    let (alice1, message2) = alice0.receive(peer.receive()?)?;
+    *phase = Phase::Signing;
+    peer.send(&message2)?;
+
+    let (alice2, message4) = alice1.receive(peer.receive()?)?;
+    peer.send(&message4)?;
+
+    Ok(alice2)
+}
+
+#[allow(clippy::type_complexity)]
+fn alice_responds<S, AL, BL>(
+    peer: &mut Peer<S>,
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    validator: &ExpiryValidator,
+    phase: &mut Phase,
+) -> crate::Result<Alice2<<AL::Next as AliceFunder1>::Next, <BL::Next as AliceRedeemer1>::Next>>
+where
+    S: Read + Write,
+    AL: AliceFunder0,
+    BL: AliceRedeemer0,
+    CoinTossingKeys: TryInto<AL::PKs, Error = crate::Error>,
+    CoinTossingKeys: TryInto<BL::PKs, Error = crate::Error>,
+    BL::RedeemerSigs: Encode,
+    <AL::Next as AliceFunder1>::RedeemerSigs: Decode,
+    <AL::Next as AliceFunder1>::EncryptedSignature: Encode,
+    <BL::Next as AliceRedeemer1>::EncryptedSignature: Decode,
+{
+    let (alice0, message1) =
+        AliceResponder0::new(swap_id, alpha_state, beta_state, validator, peer.receive()?)?;
+    peer.send(&message1)?;
+
+    let (alice1, message3) = alice0.receive(peer.receive()?)?;
+    *phase = Phase::Signing;
+    peer.send(&message3)?;
+
+    alice1.receive(peer.receive()?)
+}
+
+#[allow(clippy::type_complexity)]
+fn bob_responds<S, AL, BL>(
+    peer: &mut Peer<S>,
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    validator: &ExpiryValidator,
+    phase: &mut Phase,
+) -> crate::Result<Bob2<<AL::Next as BobRedeemer1>::Next, <BL::Next as BobFunder1>::Next>>
+where
+    S: Read + Write,
+    AL: BobRedeemer0,
+    BL: BobFunder0,
+    CoinTossingKeys: TryInto<AL::PKs, Error = crate::Error>,
+    CoinTossingKeys: TryInto<BL::PKs, Error = crate::Error>,
+    AL::PKs: Encode,
+    BL::PKs: Encode,
+    AL::RedeemerSigs: Encode,
+    BL::RedeemerSigs: Decode,
+    BL::EncryptedSignature: Encode,
+    <AL::Next as BobRedeemer1>::EncryptedSignature: Decode,
+{
+    let (bob0, message1) = Bob0::new(swap_id, alpha_state, beta_state, validator, peer.receive()?)?;
+    peer.send(&message1)?;
+
+    let (bob1, message3) = bob0.receive(peer.receive()?)?;
+    *phase = Phase::Signing;
+    peer.send(&message3)?;
+
+    bob1.receive(peer.receive()?)
+}
+
+#[allow(clippy::type_complexity)]
+fn bob_initiates<S, AL, BL>(
+    peer: &mut Peer<S>,
+    swap_id: SwapId,
+    alpha_state: AL,
+    beta_state: BL,
+    validator: &ExpiryValidator,
+    phase: &mut Phase,
+) -> crate::Result<Bob2<<AL::Next as BobRedeemer1>::Next, <BL::Next as BobFunder1>::Next>>
+where
+    S: Read + Write,
+    AL: BobRedeemer0,
+    BL: BobFunder0,
+    AL::PKs: Into<CoinTossingKeys>,
+    BL::PKs: Into<CoinTossingKeys>,
+    CoinTossingKeys: TryInto<AL::PKs, Error = crate::Error>,
+    CoinTossingKeys: TryInto<BL::PKs, Error = crate::Error>,
+    AL::RedeemerSigs: Encode,
+    BL::RedeemerSigs: Decode,
+    BL::EncryptedSignature: Encode,
+    <AL::Next as BobRedeemer1>::EncryptedSignature: Decode,
+{
+    let (bob0, message0) = BobInitiator0::new(swap_id, alpha_state, beta_state, validator)?;
+    peer.send(&message0)?;
+
+    let (bob1, message2) = bob0.receive(peer.receive()?)?;
+    *phase = Phase::Signing;
+    peer.send(&message2)?;
+
+    let (bob2, message4) = bob1.receive(peer.receive()?)?;
+    peer.send(&message4)?;
+
+    Ok(bob2)
+}
@@ -0,0 +1,205 @@
+//! Running one side of a swap against a peer.
+//!
+//! The states in `alice.rs` and `bob.rs` only turn messages into messages.
+//! This module drives them over an encrypted session with the peer:
+//!
+//! 1. `setup` agrees on the offers with the peer and exchanges the outputs each
+//!    party's transactions are built from,
+//! 2. `key_exchange` runs the protocol until each party holds the actions and
+//!    events of its role, together an `Execution`,
+//! 3. `execution` publishes the transactions of the role, waiting for those of
+//!    the peer in between.
+//!
+//! The swap is saved in a `SwapFile` once the key exchange is over and after
+//! every step of the execution, so that it can be resumed after a restart. A
+//! swap interrupted before that is simply abandoned, since neither party can
+//! have funded it yet. Every step is also recorded in the history kept in the
+//! swap directory.
+
+use crate::{
+    bitcoin::{
+        self,
+        wallet::{FunderWallet, RedeemerWallet},
+        Address, Client,
+    },
+    error::WalletError,
+    expiry::{BlockClock, ExpiryValidator},
+    grin::{self, GrinWallet, SwapSeed},
+    history::{self, Entry},
+    watchtower,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    path::Path,
+    time::Duration,
+};
+
+pub mod config;
+pub mod execution;
+pub mod file;
+pub mod key_exchange;
+pub mod peer;
+pub mod setup;
+
+pub use execution::{Execution, Status};
+pub use file::SwapFile;
+pub use peer::Peer;
+pub use setup::{AnyQuote, Offers, Terms};
+
+/// Exchange outputs and keys with the peer according to `terms`, and save the
+/// resulting swap in `dir`.
+pub fn start<S: Read + Write>(
+    peer: &mut Peer<S>,
+    terms: &Terms,
+    wallets: &Wallets,
+    dir: &Path,
+) -> crate::Result<(SwapFile, Execution)> {
+    let bitcoin_fund_input = if terms.funds_grin() {
+        None
+    } else {
+        Some(wallets.bitcoin_funder()?.fund_input())
+    };
+
+    let outputs = setup::exchange_outputs(peer, terms, wallets, bitcoin_fund_input)?;
+    let execution = key_exchange::run(peer, terms, outputs, wallets)?;
+
+    let file = SwapFile::new(peer.swap_id(), terms, peer.remote_identity(), &execution);
+    file.save(dir)?;
+    history::in_swap_dir(dir).append(&file.swap_id, Entry::StatusChanged {
+        status: file.status,
+    })?;
+
+    Ok((file, execution))
+}
+
+/// Run a saved swap until it is over, saving every change of status in `dir`.
+///
+/// The refund is deposited with the `watchtower` for as long as the swap runs,
+/// in case this process stops before it is over. Temporary failures are
+/// reported to `on_retry`, see `Execution::run`.
+pub fn run(
+    mut file: SwapFile,
+    execution: &Execution,
+    wallets: &Wallets,
+    dir: &Path,
+    poll_interval: Duration,
+    watchtower: Option<&watchtower::Client>,
+    on_retry: impl FnMut(&crate::Error),
+) -> crate::Result<Status> {
+    let history = history::in_swap_dir(dir);
+    let swap_id = file.swap_id.parse()?;
+
+    if let Some(watchtower) = watchtower {
+        watchtower.deposit(swap_id, execution.refund_package())?;
+    }
+
+    let status = execution.run(
+        file.status,
+        wallets,
+        poll_interval,
+        |step| {
+            file.set_status(step.status);
+            file.save(dir)?;
+
+            history.append_all(&file.swap_id, step.entries)?;
+            history.append(&file.swap_id, Entry::StatusChanged {
+                status: step.status,
+            })
+        },
+        on_retry,
+    )?;
+
+    if let Some(watchtower) = watchtower {
+        watchtower.cancel(swap_id)?;
+    }
+
+    Ok(status)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ledger {
+    Grin,
+    Bitcoin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Alice,
+    Bob,
+}
+
+/// The wallets a party swaps from and into.
+pub struct Wallets {
+    pub grin: Box<dyn GrinWallet + Send + Sync>,
+    /// Watches the Bitcoin chain and redeems
+    pub bitcoin: RedeemerWallet,
+    /// Only needed to fund on Bitcoin
+    pub bitcoin_funder: Option<FunderWallet>,
+    /// The special outputs of every swap are derived from it
+    pub grin_seed: SwapSeed,
+    pub bitcoin_addresses: BitcoinAddresses,
+    pub min_confirmations: MinConfirmations,
+}
+
+/// How deep a fund transaction of the other party must be buried before it is
+/// acted upon, so that a reorganization is unlikely to undo it after we have
+/// funded or redeemed.
+#[derive(Debug, Clone, Copy)]
+pub struct MinConfirmations {
+    pub grin: u64,
+    pub bitcoin: u64,
+}
+
+impl Default for MinConfirmations {
+    fn default() -> Self {
+        Self {
+            grin: 10,
+            bitcoin: 3,
+        }
+    }
+}
+
+/// Where the Bitcoin transactions of the swap pay to.
+///
+/// They are chosen by the operator rather than generated by the wallet, whose
+/// keys are not kept across restarts.
+#[derive(Debug, Clone)]
+pub struct BitcoinAddresses {
+    pub change: Address,
+    pub refund: Address,
+    pub redeem: Address,
+}
+
+impl Wallets {
+    pub fn bitcoin_funder(&self) -> crate::Result<&FunderWallet> {
+        self.bitcoin_funder
+            .as_ref()
+            .ok_or_else(|| WalletError::NoFundingOutput.into())
+    }
+
+    /// Both clocks are anchored at the current tip of their chain.
+    pub fn expiry_validator(&self, offers: &Offers) -> crate::Result<ExpiryValidator> {
+        let grin_clock = BlockClock::anchored_now(self.grin.get_chain_tip()?, grin::BLOCK_TIME_SEC);
+
+        let validator = match offers {
+            Offers::GrinBitcoin(offers) => {
+                ExpiryValidator::new(grin_clock, self.bitcoin_clock(&offers.beta.chain)?)
+            }
+            Offers::BitcoinGrin(offers) => {
+                ExpiryValidator::new(self.bitcoin_clock(&offers.alpha.chain)?, grin_clock)
+            }
+        };
+
+        Ok(validator)
+    }
+
+    fn bitcoin_clock(&self, chain: &bitcoin::ChainParams) -> crate::Result<BlockClock> {
+        Ok(BlockClock::anchored_now(
+            self.bitcoin.get_block_count()?,
+            chain.block_time,
+        ))
+    }
+}
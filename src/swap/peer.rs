@@ -0,0 +1,115 @@
+use crate::{
+    channel::{self, Session},
+    error::ProtocolError,
+    history::{Entry, History},
+    keypair::PublicKey,
+    messages::{Abort, Phase, ReasonCode, SwapId},
+    wire::{self, Decode, Encode},
+    KeyPair,
+};
+use std::io::{Read, Write};
+
+// Every message is wrapped in an envelope, so that an `Abort` can be sent in
+// place of any protocol message
+const MESSAGE: u8 = 0;
+const ABORT: u8 = 1;
+
+/// The other party of a swap, reached through an encrypted session.
+pub struct Peer<S> {
+    session: Session,
+    stream: S,
+    history: Option<History>,
+}
+
+impl<S: Read + Write> Peer<S> {
+    /// Open a session as the party which connected to the peer.
+    pub fn connect(mut stream: S, identity: KeyPair) -> crate::Result<Self> {
+        let session = channel::connect(&mut stream, identity)?;
+
+        Ok(Self {
+            session,
+            stream,
+            history: None,
+        })
+    }
+
+    /// Open a session as the party which accepted the peer's connection.
+    pub fn accept(mut stream: S, identity: KeyPair) -> crate::Result<Self> {
+        let session = channel::accept(&mut stream, identity)?;
+
+        Ok(Self {
+            session,
+            stream,
+            history: None,
+        })
+    }
+
+    /// Record every message exchanged from now on in `history`.
+    pub fn with_history(self, history: History) -> Self {
+        Self {
+            history: Some(history),
+            ..self
+        }
+    }
+
+    pub fn remote_identity(&self) -> &PublicKey {
+        self.session.remote_identity()
+    }
+
+    pub fn swap_id(&self) -> SwapId {
+        self.session.swap_id()
+    }
+
+    pub fn send<T: Encode>(&mut self, message: &T) -> crate::Result<()> {
+        let mut envelope = vec![MESSAGE];
+        message.encode(&mut envelope);
+
+        self.session.send(&mut self.stream, &envelope)?;
+        self.record(Entry::message_sent::<T>(&envelope[1..]))
+    }
+
+    /// Receive the next message of the swap. An `Abort` sent by the peer
+    /// instead is returned as the error.
+    pub fn receive<T: Decode>(&mut self) -> crate::Result<T> {
+        let envelope = self.session.receive(&mut self.stream)?;
+
+        match envelope.split_first() {
+            Some((&MESSAGE, message)) => {
+                self.record(Entry::message_received::<T>(message))?;
+                wire::from_bytes(message)
+                    .map_err(|e| ProtocolError::Malformed(e.to_string()).into())
+            }
+            Some((&ABORT, abort)) => {
+                self.record(Entry::message_received::<Abort>(abort))?;
+                Err(wire::from_bytes::<Abort>(abort)?.into())
+            }
+            _ => Err(ProtocolError::Malformed("invalid envelope".into()).into()),
+        }
+    }
+
+    /// Let the peer know that we are not going to continue because of `error`.
+    ///
+    /// This is best effort: the peer may already be gone, in which case it
+    /// finds out when the connection drops.
+    pub fn abort(&mut self, phase: Phase, error: &crate::Error) {
+        let abort = Abort {
+            swap_id: self.swap_id(),
+            reason_code: ReasonCode::from_error(error),
+            phase,
+        };
+
+        let mut envelope = vec![ABORT];
+        abort.encode(&mut envelope);
+
+        if self.session.send(&mut self.stream, &envelope).is_ok() {
+            let _ = self.record(Entry::message_sent::<Abort>(&envelope[1..]));
+        }
+    }
+
+    fn record(&self, entry: Entry) -> crate::Result<()> {
+        match &self.history {
+            Some(history) => history.append(&self.swap_id().to_string(), entry),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use crate::{
+    bitcoin::{self, wallet::Output, Address, FundInput, WalletOutputs},
+    error::{ProtocolError, RequestError, WalletError},
+    grin::{self, SpecialOutputKeyPairsFunder, SpecialOutputKeyPairsRedeemer, SpecialOutputs},
+    keypair::PublicKey,
+    negotiation::{OfferPair, Party, Quote, Roles, TakeRequest},
+    swap::{Ledger, Peer, Role, Wallets},
+    wire::{malformed, Decode, Encode},
+    KeyPair,
+};
+use secp256k1zkp::Signature;
+use std::io::{Read, Write};
+
+/// A quote for either pair of ledgers, as the maker may swap in both
+/// directions.
+#[derive(Debug, Clone)]
+pub enum AnyQuote {
+    GrinBitcoin(Quote<grin::Offer, bitcoin::Offer>),
+    BitcoinGrin(Quote<bitcoin::Offer, grin::Offer>),
+}
+
+impl AnyQuote {
+    pub fn maker(&self) -> &PublicKey {
+        match self {
+            AnyQuote::GrinBitcoin(quote) => &quote.maker,
+            AnyQuote::BitcoinGrin(quote) => &quote.maker,
+        }
+    }
+
+    pub fn roles(&self) -> Roles {
+        match self {
+            AnyQuote::GrinBitcoin(quote) => quote.roles,
+            AnyQuote::BitcoinGrin(quote) => quote.roles,
+        }
+    }
+
+    pub fn offers(&self, alpha_asset: u64) -> crate::Result<Offers> {
+        match self {
+            AnyQuote::GrinBitcoin(quote) => quote.offers(alpha_asset).map(Offers::GrinBitcoin),
+            AnyQuote::BitcoinGrin(quote) => quote.offers(alpha_asset).map(Offers::BitcoinGrin),
+        }
+    }
+}
+
+impl Encode for AnyQuote {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            AnyQuote::GrinBitcoin(quote) => {
+                0u8.encode(buf);
+                quote.encode(buf);
+            }
+            AnyQuote::BitcoinGrin(quote) => {
+                1u8.encode(buf);
+                quote.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for AnyQuote {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(AnyQuote::GrinBitcoin(Decode::decode(bytes)?)),
+            1 => Ok(AnyQuote::BitcoinGrin(Decode::decode(bytes)?)),
+            other => Err(malformed(format!("unknown pair of ledgers {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Offers {
+    GrinBitcoin(OfferPair<grin::Offer, bitcoin::Offer>),
+    BitcoinGrin(OfferPair<bitcoin::Offer, grin::Offer>),
+}
+
+impl Offers {
+    pub fn alpha(&self) -> (Ledger, u64) {
+        match self {
+            Offers::GrinBitcoin(offers) => (Ledger::Grin, offers.alpha.asset),
+            Offers::BitcoinGrin(offers) => (Ledger::Bitcoin, offers.alpha.asset),
+        }
+    }
+
+    pub fn beta(&self) -> (Ledger, u64) {
+        match self {
+            Offers::GrinBitcoin(offers) => (Ledger::Bitcoin, offers.beta.asset),
+            Offers::BitcoinGrin(offers) => (Ledger::Grin, offers.beta.asset),
+        }
+    }
+
+    pub fn grin(&self) -> &grin::Offer {
+        match self {
+            Offers::GrinBitcoin(offers) => &offers.alpha,
+            Offers::BitcoinGrin(offers) => &offers.beta,
+        }
+    }
+
+    pub fn bitcoin(&self) -> &bitcoin::Offer {
+        match self {
+            Offers::GrinBitcoin(offers) => &offers.beta,
+            Offers::BitcoinGrin(offers) => &offers.alpha,
+        }
+    }
+}
+
+/// Everything agreed on with the peer before the key exchange.
+pub struct Terms {
+    pub offers: Offers,
+    pub roles: Roles,
+    // Whether we are the maker or the taker
+    pub party: Party,
+}
+
+impl Terms {
+    pub fn role(&self) -> Role {
+        if self.roles.is_alice(self.party) {
+            Role::Alice
+        } else {
+            Role::Bob
+        }
+    }
+
+    pub fn is_initiator(&self) -> bool {
+        self.roles.is_initiator(self.party)
+    }
+
+    /// Alice funds the alpha ledger and Bob the beta ledger.
+    pub fn funds_grin(&self) -> bool {
+        match (&self.offers, self.role()) {
+            (Offers::GrinBitcoin(_), Role::Alice) | (Offers::BitcoinGrin(_), Role::Bob) => true,
+            (Offers::GrinBitcoin(_), Role::Bob) | (Offers::BitcoinGrin(_), Role::Alice) => false,
+        }
+    }
+}
+
+/// Maker's side: send the quote and sign the offers requested by the peer.
+pub fn make<S: Read + Write>(
+    peer: &mut Peer<S>,
+    quote: &AnyQuote,
+    identity: &KeyPair,
+) -> crate::Result<Terms> {
+    peer.send(quote)?;
+
+    let request = peer.receive::<TakeRequest>()?;
+    if &request.taker != peer.remote_identity() {
+        return Err(ProtocolError::UnexpectedIdentity.into());
+    }
+
+    let (offers, maker_sig) = match quote {
+        AnyQuote::GrinBitcoin(quote) => {
+            let (signed, maker_sig) = quote.accept(request, peer.swap_id(), identity)?;
+            (Offers::GrinBitcoin(signed.offers), maker_sig)
+        }
+        AnyQuote::BitcoinGrin(quote) => {
+            let (signed, maker_sig) = quote.accept(request, peer.swap_id(), identity)?;
+            (Offers::BitcoinGrin(signed.offers), maker_sig)
+        }
+    };
+
+    peer.send(&maker_sig)?;
+
+    Ok(Terms {
+        offers,
+        roles: quote.roles(),
+        party: Party::Maker,
+    })
+}
+
+/// Taker's side: request `alpha_asset` from the quote of the peer, if
+/// `confirm` accepts the resulting offers.
+pub fn take<S: Read + Write>(
+    peer: &mut Peer<S>,
+    alpha_asset: u64,
+    identity: &KeyPair,
+    confirm: impl FnOnce(&Offers, Roles) -> crate::Result<()>,
+) -> crate::Result<Terms> {
+    let quote = peer.receive::<AnyQuote>()?;
+    if quote.maker() != peer.remote_identity() {
+        return Err(ProtocolError::UnexpectedIdentity.into());
+    }
+
+    confirm(&quote.offers(alpha_asset)?, quote.roles())?;
+
+    let offers = match &quote {
+        AnyQuote::GrinBitcoin(quote) => {
+            let (taking, request) = quote.take(alpha_asset, peer.swap_id(), identity)?;
+            peer.send(&request)?;
+
+            let maker_sig = peer.receive::<Signature>()?;
+            Offers::GrinBitcoin(taking.receive(maker_sig)?.offers)
+        }
+        AnyQuote::BitcoinGrin(quote) => {
+            let (taking, request) = quote.take(alpha_asset, peer.swap_id(), identity)?;
+            peer.send(&request)?;
+
+            let maker_sig = peer.receive::<Signature>()?;
+            Offers::BitcoinGrin(taking.receive(maker_sig)?.offers)
+        }
+    };
+
+    Ok(Terms {
+        offers,
+        roles: quote.roles(),
+        party: Party::Taker,
+    })
+}
+
+/// The outputs both parties agreed to build the transactions of the swap from.
+pub struct Outputs {
+    pub special_outputs: SpecialOutputs,
+    pub grin_keypairs: GrinKeyPairs,
+    pub wallet_outputs: WalletOutputs,
+}
+
+pub enum GrinKeyPairs {
+    Funder(SpecialOutputKeyPairsFunder),
+    Redeemer(SpecialOutputKeyPairsRedeemer),
+}
+
+impl GrinKeyPairs {
+    pub fn funder(self) -> crate::Result<SpecialOutputKeyPairsFunder> {
+        match self {
+            GrinKeyPairs::Funder(keypairs) => Ok(keypairs),
+            GrinKeyPairs::Redeemer(_) => Err(RequestError::Invalid(
+                "outputs of the redeemer on Grin given to the funder".into(),
+            )
+            .into()),
+        }
+    }
+
+    pub fn redeemer(self) -> crate::Result<SpecialOutputKeyPairsRedeemer> {
+        match self {
+            GrinKeyPairs::Redeemer(keypairs) => Ok(keypairs),
+            GrinKeyPairs::Funder(_) => Err(RequestError::Invalid(
+                "outputs of the funder on Grin given to the redeemer".into(),
+            )
+            .into()),
+        }
+    }
+}
+
+// Each party sends the public half of the outputs of its role on both ledgers
+struct OwnOutputs {
+    grin: GrinOutputs,
+    bitcoin: BitcoinOutputs,
+}
+
+impl_wire!(OwnOutputs { grin, bitcoin });
+
+enum GrinOutputs {
+    Funder {
+        fund_input_key: PublicKey,
+        refund_output_key: PublicKey,
+    },
+    Redeemer {
+        redeem_output_key: PublicKey,
+    },
+}
+
+enum BitcoinOutputs {
+    Funder {
+        fund_input: FundInput,
+        fund_change_address: Address,
+        refund_address: Address,
+    },
+    Redeemer {
+        redeem_address: Address,
+    },
+}
+
+/// Exchange the outputs of both parties.
+///
+/// The special outputs of Grin are derived from the swap seed at the index
+/// of the swap, so that they can be found again after a crash. Those of
+/// Bitcoin come from the wallets, except for `bitcoin_fund_input` which must
+/// be given if we fund on Bitcoin. The peer checks it is worth enough when
+/// building the fund transaction.
+pub fn exchange_outputs<S: Read + Write>(
+    peer: &mut Peer<S>,
+    terms: &Terms,
+    wallets: &Wallets,
+    bitcoin_fund_input: Option<Output>,
+) -> crate::Result<Outputs> {
+    let bitcoin_fund_input = match (terms.funds_grin(), bitcoin_fund_input) {
+        (true, _) => None,
+        (false, Some(fund_input)) => Some(FundInput::from(fund_input)),
+        (false, None) => return Err(WalletError::NoFundingOutput.into()),
+    };
+
+    let swap_index = peer.swap_id().keychain_index();
+    let (grin_keypairs, grin_outputs) = if terms.funds_grin() {
+        let keypairs = SpecialOutputKeyPairsFunder::derive(
+            &wallets.grin_seed,
+            swap_index,
+            terms.offers.grin(),
+        )?;
+        let outputs = GrinOutputs::Funder {
+            fund_input_key: keypairs.fund_input_key.public_key,
+            refund_output_key: keypairs.refund_output_key.public_key,
+        };
+
+        (GrinKeyPairs::Funder(keypairs), outputs)
+    } else {
+        let keypairs = SpecialOutputKeyPairsRedeemer::derive(
+            &wallets.grin_seed,
+            swap_index,
+            terms.offers.grin(),
+        )?;
+        let outputs = GrinOutputs::Redeemer {
+            redeem_output_key: keypairs.redeem_output_key.public_key,
+        };
+
+        (GrinKeyPairs::Redeemer(keypairs), outputs)
+    };
+
+    let bitcoin_outputs = if terms.funds_grin() {
+        BitcoinOutputs::Redeemer {
+            redeem_address: wallets.bitcoin_addresses.redeem.clone(),
+        }
+    } else {
+        BitcoinOutputs::Funder {
+            fund_input: bitcoin_fund_input.clone().expect("checked above"),
+            fund_change_address: wallets.bitcoin_addresses.change.clone(),
+            refund_address: wallets.bitcoin_addresses.refund.clone(),
+        }
+    };
+
+    peer.send(&OwnOutputs {
+        grin: grin_outputs,
+        bitcoin: bitcoin_outputs,
+    })?;
+    let theirs = peer.receive::<OwnOutputs>()?;
+
+    let special_outputs = match (&grin_keypairs, theirs.grin) {
+        (GrinKeyPairs::Funder(keypairs), GrinOutputs::Redeemer { redeem_output_key }) => {
+            SpecialOutputs {
+                fund_input_key: keypairs.fund_input_key.public_key,
+                redeem_output_key,
+                refund_output_key: keypairs.refund_output_key.public_key,
+            }
+        }
+        (
+            GrinKeyPairs::Redeemer(keypairs),
+            GrinOutputs::Funder {
+                fund_input_key,
+                refund_output_key,
+            },
+        ) => SpecialOutputs {
+            fund_input_key,
+            redeem_output_key: keypairs.redeem_output_key.public_key,
+            refund_output_key,
+        },
+        _ => {
+            return Err(ProtocolError::Malformed(
+                "peer sent Grin outputs for the wrong role".into(),
+            )
+            .into())
+        }
+    };
+
+    let wallet_outputs = match theirs.bitcoin {
+        BitcoinOutputs::Funder {
+            fund_input,
+            fund_change_address,
+            refund_address,
+        } if terms.funds_grin() => WalletOutputs {
+            fund_input,
+            fund_change_address,
+            redeem_address: wallets.bitcoin_addresses.redeem.clone(),
+            refund_address,
+        },
+        BitcoinOutputs::Redeemer { redeem_address } if !terms.funds_grin() => WalletOutputs {
+            fund_input: bitcoin_fund_input.expect("checked above"),
+            fund_change_address: wallets.bitcoin_addresses.change.clone(),
+            redeem_address,
+            refund_address: wallets.bitcoin_addresses.refund.clone(),
+        },
+        _ => {
+            return Err(ProtocolError::Malformed(
+                "peer sent Bitcoin outputs for the wrong role".into(),
+            )
+            .into())
+        }
+    };
+
+    Ok(Outputs {
+        special_outputs,
+        grin_keypairs,
+        wallet_outputs,
+    })
+}
+
+impl Encode for GrinOutputs {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            GrinOutputs::Funder {
+                fund_input_key,
+                refund_output_key,
+            } => {
+                0u8.encode(buf);
+                fund_input_key.encode(buf);
+                refund_output_key.encode(buf);
+            }
+            GrinOutputs::Redeemer { redeem_output_key } => {
+                1u8.encode(buf);
+                redeem_output_key.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for GrinOutputs {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(GrinOutputs::Funder {
+                fund_input_key: Decode::decode(bytes)?,
+                refund_output_key: Decode::decode(bytes)?,
+            }),
+            1 => Ok(GrinOutputs::Redeemer {
+                redeem_output_key: Decode::decode(bytes)?,
+            }),
+            other => Err(malformed(format!("unknown Grin role {}", other))),
+        }
+    }
+}
+
+impl Encode for BitcoinOutputs {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            BitcoinOutputs::Funder {
+                fund_input,
+                fund_change_address,
+                refund_address,
+            } => {
+                0u8.encode(buf);
+                fund_input.encode(buf);
+                fund_change_address.encode(buf);
+                refund_address.encode(buf);
+            }
+            BitcoinOutputs::Redeemer { redeem_address } => {
+                1u8.encode(buf);
+                redeem_address.encode(buf);
+            }
+        }
+    }
+}
+
+impl Decode for BitcoinOutputs {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(BitcoinOutputs::Funder {
+                fund_input: Decode::decode(bytes)?,
+                fund_change_address: Decode::decode(bytes)?,
+                refund_address: Decode::decode(bytes)?,
+            }),
+            1 => Ok(BitcoinOutputs::Redeemer {
+                redeem_address: Decode::decode(bytes)?,
+            }),
+            other => Err(malformed(format!("unknown Bitcoin role {}", other))),
+        }
+    }
+}
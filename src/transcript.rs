@@ -0,0 +1,127 @@
+//! A running hash of everything the parties have agreed on during a swap.
+//!
+//! Every value is added under a label and both are length-prefixed, so that no
+//! two different sequences of messages produce the same transcript. Values
+//! derived from the transcript are also labelled, which keeps a hash computed
+//! for one purpose from being reused for another one, or in another swap.
+
+use crate::{
+    keypair::{PublicKey, SecretKey, SECP},
+    messages::SwapId,
+    negotiation::OfferPair,
+    Hash,
+};
+use blake2::{Blake2b, Digest};
+
+const SWAP_DOMAIN: &[u8] = b"grin-btc-poc/swap/v1";
+
+#[derive(Clone)]
+pub struct Transcript {
+    hasher: Blake2b,
+}
+
+impl Transcript {
+    pub fn new(domain: &[u8]) -> Self {
+        let mut transcript = Self {
+            hasher: Blake2b::new(),
+        };
+        transcript.append_message(b"domain", domain);
+
+        transcript
+    }
+
+    /// The transcript both parties start the protocol with. The initial
+    /// commitment is computed over it.
+    pub fn for_swap<A, B>(swap_id: &SwapId, offers: &OfferPair<A, B>) -> Self
+    where
+        A: Hash,
+        B: Hash,
+    {
+        let mut transcript = Self::new(SWAP_DOMAIN);
+        transcript.append_message(b"swap_id", &swap_id.0);
+        transcript.append_hash(b"offers", offers);
+
+        transcript
+    }
+
+    pub fn append_message(&mut self, label: &[u8], message: &[u8]) {
+        self.append_length_prefixed(label);
+        self.append_length_prefixed(message);
+    }
+
+    pub fn append_hash<H: Hash>(&mut self, label: &[u8], item: &H) {
+        self.append_message(label, &item.hash()[..]);
+    }
+
+    pub fn append_public_key(&mut self, label: &[u8], pk: &PublicKey) {
+        self.append_message(label, &pk.serialize_vec(&*SECP, true));
+    }
+
+    pub fn append_public_keys(&mut self, label: &[u8], pks: &[PublicKey]) {
+        self.append_message(label, &(pks.len() as u64).to_be_bytes());
+        for pk in pks.iter() {
+            self.append_public_key(b"public_key", pk);
+        }
+    }
+
+    /// Derive a value for the purpose given by `label`, without modifying the
+    /// transcript.
+    pub fn challenge(&self, label: &[u8]) -> [u8; 64] {
+        let mut transcript = self.clone();
+        transcript.append_message(b"challenge", label);
+
+        let mut challenge = [0u8; 64];
+        challenge.copy_from_slice(&transcript.hasher.result());
+        challenge
+    }
+
+    pub fn challenge_scalar(&self, label: &[u8]) -> crate::Result<SecretKey> {
+        Ok(SecretKey::from_slice(&*SECP, &self.challenge(label)[..32])?)
+    }
+
+    fn append_length_prefixed(&mut self, data: &[u8]) {
+        self.hasher.input((data.len() as u64).to_be_bytes());
+        self.hasher.input(data);
+    }
+}
+
+impl Hash for Transcript {
+    fn hash(&self) -> [u8; 64] {
+        self.challenge(b"transcript")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keypair::KeyPair;
+
+    #[test]
+    fn messages_are_length_prefixed() {
+        let mut split_early = Transcript::new(b"test");
+        split_early.append_message(b"a", b"bc");
+
+        let mut split_late = Transcript::new(b"test");
+        split_late.append_message(b"ab", b"c");
+
+        assert_ne!(
+            split_early.challenge(b"label")[..],
+            split_late.challenge(b"label")[..]
+        );
+    }
+
+    #[test]
+    fn challenges_are_domain_separated() {
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_public_key(b"X", &KeyPair::new_random().public_key);
+
+        assert_ne!(
+            transcript.challenge(b"one")[..],
+            transcript.challenge(b"two")[..]
+        );
+        assert_ne!(
+            transcript.challenge(b"one")[..],
+            Transcript::new(b"other").challenge(b"one")[..]
+        );
+    }
+}
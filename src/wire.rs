@@ -0,0 +1,455 @@
+//! The binary encoding of the messages exchanged with the peer and of the state
+//! which is saved to resume a swap.
+//!
+//! A value is encoded as the concatenation of its fields. Integers are
+//! big-endian and values of variable length are prefixed with their length as
+//! a `u32`. The encoding does not describe itself, so the receiver must know
+//! which type to expect. That is always the case, since the protocol is a fixed
+//! sequence of messages.
+//!
+//! Types of this crate implement `Encode` and `Decode` next to their
+//! definition. This module covers primitives and the types of other crates.
+
+use crate::{
+    bitcoin,
+    error::{Error, ProtocolError},
+    keypair::{KeyPair, PublicKey, SecretKey, SECP},
+};
+use bitcoin_hashes::sha256d;
+use grin_core::{
+    core::{KernelFeatures, Transaction},
+    ser::ProtocolVersion,
+};
+use secp256k1zkp::{
+    constants::MAX_PROOF_SIZE,
+    pedersen::{Commitment, RangeProof},
+    Message, Signature,
+};
+use std::{convert::TryInto, fmt, str::FromStr};
+
+/// Implement `Encode` and `Decode` for a struct with named fields by encoding
+/// the fields in the given order.
+macro_rules! impl_wire {
+    ($name:ident $(<$($param:ident),+>)? { $($field:ident),+ $(,)? }) => {
+        impl$(<$($param: $crate::wire::Encode),+>)? $crate::wire::Encode for $name$(<$($param),+>)? {
+            fn encode(&self, buf: &mut Vec<u8>) {
+                $($crate::wire::Encode::encode(&self.$field, buf);)+
+            }
+        }
+
+        impl$(<$($param: $crate::wire::Decode),+>)? $crate::wire::Decode for $name$(<$($param),+>)? {
+            fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+                Ok(Self {
+                    $($field: $crate::wire::Decode::decode(bytes)?),+
+                })
+            }
+        }
+    };
+}
+
+pub trait Encode {
+    fn encode(&self, buf: &mut Vec<u8>);
+}
+
+pub trait Decode: Sized {
+    /// Decode a value from the front of `bytes`, leaving the rest.
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self>;
+}
+
+/// The error of bytes which do not decode to the expected value.
+pub fn malformed<R: fmt::Display>(reason: R) -> Error {
+    ProtocolError::Malformed(reason.to_string()).into()
+}
+
+pub fn to_bytes<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    value.encode(&mut buf);
+    buf
+}
+
+/// Decode a value which must take up all of `bytes`.
+pub fn from_bytes<T: Decode>(mut bytes: &[u8]) -> crate::Result<T> {
+    let value = T::decode(&mut bytes)?;
+
+    if !bytes.is_empty() {
+        return Err(malformed(format!(
+            "{} unexpected bytes after the encoded value",
+            bytes.len()
+        )));
+    }
+
+    Ok(value)
+}
+
+pub fn take<'a>(bytes: &mut &'a [u8], n: usize) -> crate::Result<&'a [u8]> {
+    if bytes.len() < n {
+        return Err(malformed(format!(
+            "expected {} more bytes, only {} left",
+            n,
+            bytes.len()
+        )));
+    }
+
+    let (taken, rest) = bytes.split_at(n);
+    *bytes = rest;
+
+    Ok(taken)
+}
+
+/// Encode a variable number of raw bytes.
+pub fn encode_bytes(bytes: &[u8], buf: &mut Vec<u8>) {
+    (bytes.len() as u32).encode(buf);
+    buf.extend_from_slice(bytes);
+}
+
+pub fn decode_bytes<'a>(bytes: &mut &'a [u8]) -> crate::Result<&'a [u8]> {
+    let len = u32::decode(bytes)?;
+    take(bytes, len as usize)
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+
+            impl Decode for $t {
+                fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+                    let taken = take(bytes, std::mem::size_of::<$t>())?;
+                    Ok(<$t>::from_be_bytes(taken.try_into().map_err(malformed)?))
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8, u16, u32, u64);
+
+impl Encode for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as u8).encode(buf)
+    }
+}
+
+impl Decode for bool {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(malformed(format!("invalid boolean {}", other))),
+        }
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), buf)
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.as_str().encode(buf)
+    }
+}
+
+impl Decode for String {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        String::from_utf8(decode_bytes(bytes)?.to_vec()).map_err(malformed)
+    }
+}
+
+impl Encode for () {
+    fn encode(&self, _: &mut Vec<u8>) {}
+}
+
+impl Decode for () {
+    fn decode(_: &mut &[u8]) -> crate::Result<Self> {
+        Ok(())
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            None => 0u8.encode(buf),
+            Some(value) => {
+                1u8.encode(buf);
+                value.encode(buf);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        match u8::decode(bytes)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(bytes)?)),
+            other => Err(malformed(format!("invalid option tag {}", other))),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.len() as u32).encode(buf);
+        for item in self {
+            item.encode(buf);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        let len = u32::decode(bytes)? as usize;
+
+        // Every item takes at least one byte, which bounds the allocation
+        let mut items = Vec::with_capacity(std::cmp::min(len, bytes.len()));
+        for _ in 0..len {
+            items.push(T::decode(bytes)?);
+        }
+
+        Ok(items)
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf);
+        self.1.encode(buf);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok((A::decode(bytes)?, B::decode(bytes)?))
+    }
+}
+
+impl<A: Encode, B: Encode, C: Encode> Encode for (A, B, C) {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf);
+        self.1.encode(buf);
+        self.2.encode(buf);
+    }
+}
+
+impl<A: Decode, B: Decode, C: Decode> Decode for (A, B, C) {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok((A::decode(bytes)?, B::decode(bytes)?, C::decode(bytes)?))
+    }
+}
+
+impl Encode for PublicKey {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.serialize_vec(&*SECP, true));
+    }
+}
+
+impl Decode for PublicKey {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        PublicKey::from_slice(&*SECP, take(bytes, 33)?).map_err(malformed)
+    }
+}
+
+impl Encode for SecretKey {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self[..]);
+    }
+}
+
+impl Decode for SecretKey {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        SecretKey::from_slice(&*SECP, take(bytes, 32)?).map_err(malformed)
+    }
+}
+
+// The public key is derived again when decoding
+impl Encode for KeyPair {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.expose_secret_key().encode(buf)
+    }
+}
+
+impl Decode for KeyPair {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok(KeyPair::new(SecretKey::decode(bytes)?))
+    }
+}
+
+// Both ECDSA and Schnorr signatures are kept in the same 64 byte
+// representation
+impl Encode for Signature {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_ref());
+    }
+}
+
+impl Decode for Signature {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        let mut raw = [0u8; 64];
+        raw.copy_from_slice(take(bytes, 64)?);
+
+        Signature::from_raw_data(&raw).map_err(malformed)
+    }
+}
+
+impl Encode for Message {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self[..]);
+    }
+}
+
+impl Decode for Message {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Message::from_slice(take(bytes, 32)?).map_err(malformed)
+    }
+}
+
+impl Encode for Commitment {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+}
+
+impl Decode for Commitment {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        Ok(Commitment::from_vec(take(bytes, 33)?.to_vec()))
+    }
+}
+
+impl Encode for RangeProof {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode_bytes(&self.proof[..self.plen], buf)
+    }
+}
+
+impl Decode for RangeProof {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        let proof = decode_bytes(bytes)?;
+        if proof.len() > MAX_PROOF_SIZE {
+            return Err(malformed("range proof is too long"));
+        }
+
+        let mut range_proof = RangeProof::zero();
+        range_proof.proof[..proof.len()].copy_from_slice(proof);
+        range_proof.plen = proof.len();
+
+        Ok(range_proof)
+    }
+}
+
+fn encode_grin<T: grin_core::ser::Writeable>(value: &T, buf: &mut Vec<u8>) {
+    let bytes = grin_core::ser::ser_vec(value, ProtocolVersion::local())
+        .expect("serializing to a vector cannot fail");
+    encode_bytes(&bytes, buf)
+}
+
+fn decode_grin<T: grin_core::ser::Readable>(bytes: &mut &[u8]) -> crate::Result<T> {
+    let mut serialized = decode_bytes(bytes)?;
+
+    grin_core::ser::deserialize(&mut serialized, ProtocolVersion::local())
+        .map_err(|e| malformed(format!("invalid Grin value: {}", e)))
+}
+
+impl Encode for Transaction {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode_grin(self, buf)
+    }
+}
+
+impl Decode for Transaction {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        decode_grin(bytes)
+    }
+}
+
+impl Encode for KernelFeatures {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode_grin(self, buf)
+    }
+}
+
+impl Decode for KernelFeatures {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        decode_grin(bytes)
+    }
+}
+
+macro_rules! impl_bitcoin_consensus {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    encode_bytes(&::bitcoin::consensus::encode::serialize(self), buf)
+                }
+            }
+
+            impl Decode for $t {
+                fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+                    ::bitcoin::consensus::encode::deserialize(decode_bytes(bytes)?)
+                        .map_err(malformed)
+                }
+            }
+        )*
+    };
+}
+
+impl_bitcoin_consensus!(
+    bitcoin::Transaction,
+    bitcoin::OutPoint,
+    bitcoin::TxOut,
+    bitcoin::Script,
+    sha256d::Hash
+);
+
+impl Encode for bitcoin::Address {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.to_string().encode(buf)
+    }
+}
+
+impl Decode for bitcoin::Address {
+    fn decode(bytes: &mut &[u8]) -> crate::Result<Self> {
+        bitcoin::Address::from_str(&String::decode(bytes)?).map_err(malformed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn values_roundtrip() -> crate::Result<()> {
+        let keypair = KeyPair::new_random();
+        let value = (
+            vec![(1u64, keypair.public_key)],
+            Some(String::from("swap")),
+            keypair,
+        );
+
+        let decoded: (Vec<(u64, PublicKey)>, Option<String>, KeyPair) =
+            from_bytes(&to_bytes(&value))?;
+
+        assert_eq!(decoded.0, value.0);
+        assert_eq!(decoded.1, value.1);
+        assert_eq!(decoded.2.public_key, value.2.public_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_and_trailing_bytes_are_rejected() {
+        let bytes = to_bytes(&vec![1u64, 2, 3]);
+
+        assert!(from_bytes::<Vec<u64>>(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut extended = bytes;
+        extended.push(0);
+        assert!(from_bytes::<Vec<u64>>(&extended).is_err());
+    }
+}
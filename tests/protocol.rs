@@ -0,0 +1,334 @@
+#![allow(non_snake_case)]
+
+use grin_btc_poc::{
+    alice::{Alice0, AliceResponder0},
+    bitcoin::{self, wallet, OutPoint, TxOut},
+    bob::{Bob0, BobInitiator0},
+    channel,
+    expiry::{BlockClock, ExpiryValidator},
+    grin,
+    messages::{ReasonCode, SwapId},
+    KeyPair,
+};
+
+// The protocol is run up to the point where both parties are ready to fund,
+// which does not require any node
+
+const NOW: u64 = 1_600_000_000;
+const GRIN_HEIGHT: u64 = 1_000;
+const BITCOIN_HEIGHT: u64 = 100;
+
+fn grin_clock() -> BlockClock {
+    BlockClock {
+        anchor_height: GRIN_HEIGHT,
+        anchor_time: NOW,
+        block_time: grin::BLOCK_TIME_SEC,
+    }
+}
+
+fn bitcoin_clock() -> BlockClock {
+    BlockClock {
+        anchor_height: BITCOIN_HEIGHT,
+        anchor_time: NOW,
+        block_time: 600,
+    }
+}
+
+fn grin_offer(expiry: u64) -> grin::Offer {
+    grin::Offer {
+        asset: 10_000_000_000,
+        base_fee: grin::DEFAULT_BASE_FEE,
+        expiry: grin::Expiry::Absolute(expiry),
+    }
+}
+
+fn bitcoin_offer(expiry: u64) -> bitcoin::Offer {
+    bitcoin::Offer {
+        asset: 100_000_000,
+        fee: 1_000,
+        expiry: expiry as u32,
+        chain: bitcoin::ChainParams::bitcoin_regtest(),
+    }
+}
+
+fn grin_outputs() -> (
+    grin::SpecialOutputs,
+    grin::SpecialOutputKeyPairsFunder,
+    grin::SpecialOutputKeyPairsRedeemer,
+) {
+    let funder = grin::SpecialOutputKeyPairsFunder::new_random();
+    let redeemer = grin::SpecialOutputKeyPairsRedeemer::new_random();
+
+    let outputs = grin::SpecialOutputs {
+        fund_input_key: funder.fund_input_key.public_key,
+        redeem_output_key: redeemer.redeem_output_key.public_key,
+        refund_output_key: funder.refund_output_key.public_key,
+    };
+
+    (outputs, funder, redeemer)
+}
+
+fn bitcoin_outputs(offer: &bitcoin::Offer) -> bitcoin::WalletOutputs {
+    let keypair = KeyPair::new_random();
+    let txout = TxOut {
+        value: offer.asset + 2 * offer.fee + 1_000_000,
+        script_pubkey: keypair.to_bitcoin_address().script_pubkey(),
+    };
+
+    bitcoin::WalletOutputs {
+        fund_input: wallet::Output::new(keypair, OutPoint::null(), txout).into(),
+        fund_change_address: KeyPair::new_random().to_bitcoin_address(),
+        redeem_address: KeyPair::new_random().to_bitcoin_address(),
+        refund_address: KeyPair::new_random().to_bitcoin_address(),
+    }
+}
+
+#[test]
+fn grin_bitcoin_alice_initiates() -> anyhow::Result<()> {
+    let validator = ExpiryValidator::new(grin_clock(), bitcoin_clock());
+    let offer_grin = grin_offer(GRIN_HEIGHT + 24 * 60);
+    let offer_bitcoin = bitcoin_offer(NOW + 60 * 60);
+    let (outputs_grin, keypairs_funder, keypairs_redeemer) = grin_outputs();
+    let outputs_bitcoin = bitcoin_outputs(&offer_bitcoin);
+
+    let swap_id = SwapId::random();
+
+    let (alice0, message0) = Alice0::new(
+        swap_id,
+        grin::AliceFunder0::new(offer_grin.clone(), outputs_grin.clone(), keypairs_funder)?,
+        bitcoin::AliceRedeemer0::new(offer_bitcoin.clone(), outputs_bitcoin.clone()),
+        &validator,
+    )?;
+    let (bob0, message1) = Bob0::new(
+        swap_id,
+        grin::BobRedeemer0::new(offer_grin, outputs_grin, keypairs_redeemer)?,
+        bitcoin::BobFunder0::new(offer_bitcoin, outputs_bitcoin),
+        &validator,
+        message0,
+    )?;
+
+    let (alice1, message2) = alice0.receive(message1)?;
+    let (bob1, message3) = bob0.receive(message2)?;
+    let (_alice2, message4) = alice1.receive(message3)?;
+    let _bob2 = bob1.receive(message4)?;
+
+    Ok(())
+}
+
+#[test]
+fn grin_bitcoin_bob_initiates() -> anyhow::Result<()> {
+    let validator = ExpiryValidator::new(grin_clock(), bitcoin_clock());
+    let offer_grin = grin_offer(GRIN_HEIGHT + 24 * 60);
+    let offer_bitcoin = bitcoin_offer(NOW + 60 * 60);
+    let (outputs_grin, keypairs_funder, keypairs_redeemer) = grin_outputs();
+    let outputs_bitcoin = bitcoin_outputs(&offer_bitcoin);
+
+    let swap_id = SwapId::random();
+
+    let (bob0, message0) = BobInitiator0::new(
+        swap_id,
+        grin::BobRedeemer0::new(offer_grin.clone(), outputs_grin.clone(), keypairs_redeemer)?,
+        bitcoin::BobFunder0::new(offer_bitcoin.clone(), outputs_bitcoin.clone()),
+        &validator,
+    )?;
+    let (alice0, message1) = AliceResponder0::new(
+        swap_id,
+        grin::AliceFunder0::new(offer_grin, outputs_grin, keypairs_funder)?,
+        bitcoin::AliceRedeemer0::new(offer_bitcoin, outputs_bitcoin),
+        &validator,
+        message0,
+    )?;
+
+    let (bob1, message2) = bob0.receive(message1)?;
+    let (alice1, message3) = alice0.receive(message2)?;
+    let (_bob2, message4) = bob1.receive(message3)?;
+    let _alice2 = alice1.receive(message4)?;
+
+    Ok(())
+}
+
+#[test]
+fn bitcoin_grin_alice_initiates() -> anyhow::Result<()> {
+    let validator = ExpiryValidator::new(bitcoin_clock(), grin_clock());
+    let offer_bitcoin = bitcoin_offer(NOW + 2 * 24 * 60 * 60);
+    let offer_grin = grin_offer(GRIN_HEIGHT + 60);
+    let (outputs_grin, keypairs_funder, keypairs_redeemer) = grin_outputs();
+    let outputs_bitcoin = bitcoin_outputs(&offer_bitcoin);
+
+    let swap_id = SwapId::random();
+
+    let (alice0, message0) = Alice0::new(
+        swap_id,
+        bitcoin::AliceFunder0::new(offer_bitcoin.clone(), outputs_bitcoin.clone()),
+        grin::AliceRedeemer0::new(offer_grin.clone(), outputs_grin.clone(), keypairs_redeemer)?,
+        &validator,
+    )?;
+    let (bob0, message1) = Bob0::new(
+        swap_id,
+        bitcoin::BobRedeemer0::new(offer_bitcoin, outputs_bitcoin),
+        grin::BobFunder0::new(offer_grin, outputs_grin, keypairs_funder)?,
+        &validator,
+        message0,
+    )?;
+
+    let (alice1, message2) = alice0.receive(message1)?;
+    let (bob1, message3) = bob0.receive(message2)?;
+    let (_alice2, message4) = alice1.receive(message3)?;
+    let _bob2 = bob1.receive(message4)?;
+
+    Ok(())
+}
+
+#[test]
+fn bitcoin_grin_bob_initiates() -> anyhow::Result<()> {
+    let validator = ExpiryValidator::new(bitcoin_clock(), grin_clock());
+    let offer_bitcoin = bitcoin_offer(NOW + 2 * 24 * 60 * 60);
+    let offer_grin = grin_offer(GRIN_HEIGHT + 60);
+    let (outputs_grin, keypairs_funder, keypairs_redeemer) = grin_outputs();
+    let outputs_bitcoin = bitcoin_outputs(&offer_bitcoin);
+
+    let swap_id = SwapId::random();
+
+    let (bob0, message0) = BobInitiator0::new(
+        swap_id,
+        bitcoin::BobRedeemer0::new(offer_bitcoin.clone(), outputs_bitcoin.clone()),
+        grin::BobFunder0::new(offer_grin.clone(), outputs_grin.clone(), keypairs_funder)?,
+        &validator,
+    )?;
+    let (alice0, message1) = AliceResponder0::new(
+        swap_id,
+        bitcoin::AliceFunder0::new(offer_bitcoin, outputs_bitcoin),
+        grin::AliceRedeemer0::new(offer_grin, outputs_grin, keypairs_redeemer)?,
+        &validator,
+        message0,
+    )?;
+
+    let (bob1, message2) = bob0.receive(message1)?;
+    let (alice1, message3) = alice0.receive(message2)?;
+    let (_bob2, message4) = bob1.receive(message3)?;
+    let _alice2 = alice1.receive(message4)?;
+
+    Ok(())
+}
+
+#[test]
+fn opening_for_different_offers_is_rejected() -> anyhow::Result<()> {
+    let validator = ExpiryValidator::new(grin_clock(), bitcoin_clock());
+    let offer_grin = grin_offer(GRIN_HEIGHT + 24 * 60);
+    let offer_bitcoin = bitcoin_offer(NOW + 60 * 60);
+    let (outputs_grin, keypairs_funder, keypairs_redeemer) = grin_outputs();
+    let outputs_bitcoin = bitcoin_outputs(&offer_bitcoin);
+
+    let swap_id = SwapId::random();
+
+    let (alice0, message0) = Alice0::new(
+        swap_id,
+        grin::AliceFunder0::new(offer_grin.clone(), outputs_grin.clone(), keypairs_funder)?,
+        bitcoin::AliceRedeemer0::new(offer_bitcoin.clone(), outputs_bitcoin.clone()),
+        &validator,
+    )?;
+
+    // Bob thinks he is getting more grin than Alice agreed to
+    let bob_offer_grin = grin::Offer {
+        asset: offer_grin.asset * 2,
+        ..offer_grin
+    };
+    let (bob0, message1) = Bob0::new(
+        swap_id,
+        grin::BobRedeemer0::new(bob_offer_grin, outputs_grin, keypairs_redeemer)?,
+        bitcoin::BobFunder0::new(offer_bitcoin, outputs_bitcoin),
+        &validator,
+        message0,
+    )?;
+
+    let (_alice1, message2) = alice0.receive(message1)?;
+    let error = bob0.receive(message2).err().expect("opening should fail");
+
+    assert_eq!(ReasonCode::from_error(&error), ReasonCode::InvalidOpening);
+
+    Ok(())
+}
+
+#[test]
+fn message_for_another_swap_is_rejected() -> anyhow::Result<()> {
+    let validator = ExpiryValidator::new(grin_clock(), bitcoin_clock());
+    let offer_grin = grin_offer(GRIN_HEIGHT + 24 * 60);
+    let offer_bitcoin = bitcoin_offer(NOW + 60 * 60);
+    let (outputs_grin, keypairs_funder, keypairs_redeemer) = grin_outputs();
+    let outputs_bitcoin = bitcoin_outputs(&offer_bitcoin);
+
+    let swap_id = SwapId::random();
+
+    let (alice0, message0) = Alice0::new(
+        swap_id,
+        grin::AliceFunder0::new(offer_grin.clone(), outputs_grin.clone(), keypairs_funder)?,
+        bitcoin::AliceRedeemer0::new(offer_bitcoin.clone(), outputs_bitcoin.clone()),
+        &validator,
+    )?;
+    let (_bob0, mut message1) = Bob0::new(
+        swap_id,
+        grin::BobRedeemer0::new(offer_grin, outputs_grin, keypairs_redeemer)?,
+        bitcoin::BobFunder0::new(offer_bitcoin, outputs_bitcoin),
+        &validator,
+        message0,
+    )?;
+
+    message1.swap_id = SwapId::random();
+
+    assert!(alice0.receive(message1).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn relaying_between_two_sessions_is_detected() -> anyhow::Result<()> {
+    let validator = ExpiryValidator::new(grin_clock(), bitcoin_clock());
+    let offer_grin = grin_offer(GRIN_HEIGHT + 24 * 60);
+    let offer_bitcoin = bitcoin_offer(NOW + 60 * 60);
+    let (outputs_grin, keypairs_funder, keypairs_redeemer) = grin_outputs();
+    let outputs_bitcoin = bitcoin_outputs(&offer_bitcoin);
+
+    // Mallory runs one handshake with Alice and another one with Bob
+    let (alice_session, _) = handshake(&KeyPair::new_random(), &KeyPair::new_random())?;
+    let (_, bob_session) = handshake(&KeyPair::new_random(), &KeyPair::new_random())?;
+
+    let (alice0, mut message0) = Alice0::new(
+        alice_session.swap_id(),
+        grin::AliceFunder0::new(offer_grin.clone(), outputs_grin.clone(), keypairs_funder)?,
+        bitcoin::AliceRedeemer0::new(offer_bitcoin.clone(), outputs_bitcoin.clone()),
+        &validator,
+    )?;
+
+    // Even if Mallory rewrites the identifiers, the commitments were made for
+    // the other session
+    message0.swap_id = bob_session.swap_id();
+    let (bob0, mut message1) = Bob0::new(
+        bob_session.swap_id(),
+        grin::BobRedeemer0::new(offer_grin, outputs_grin, keypairs_redeemer)?,
+        bitcoin::BobFunder0::new(offer_bitcoin, outputs_bitcoin),
+        &validator,
+        message0,
+    )?;
+
+    message1.swap_id = alice_session.swap_id();
+    let (_alice1, mut message2) = alice0.receive(message1)?;
+
+    message2.swap_id = bob_session.swap_id();
+    let error = bob0.receive(message2).err().expect("opening should fail");
+
+    assert_eq!(ReasonCode::from_error(&error), ReasonCode::InvalidOpening);
+
+    Ok(())
+}
+
+fn handshake(
+    initiator_identity: &KeyPair,
+    responder_identity: &KeyPair,
+) -> anyhow::Result<(channel::Session, channel::Session)> {
+    let (initiator, message0) = channel::Initiator0::new(initiator_identity.clone());
+    let (responder, message1) = channel::Responder0::new(responder_identity.clone(), &message0)?;
+    let (initiator_session, message2) = initiator.receive(&message1)?;
+    let responder_session = responder.receive(&message2)?;
+
+    Ok((initiator_session, responder_session))
+}
@@ -4,6 +4,7 @@ use grin_btc_poc::{
     ecdsa,
     keypair::{KeyPair, PublicKey, SECP},
     schnorr,
+    transcript::Transcript,
 };
 use secp256k1zkp::{aggsig, Message};
 use std::convert::TryFrom;
@@ -42,7 +43,7 @@ fn recover_from_ecdsa_to_decrypt_schnorr() -> anyhow::Result<()> {
 
     let x = KeyPair::new_random();
 
-    let ecdsa_encsig = ecdsa::encsign(&x, &y.public_key, message);
+    let ecdsa_encsig = ecdsa::encsign(&x, &y.public_key, message, &Transcript::new(b"test"));
 
     let ecdsa_sig = ecdsa::decsig(&y, &ecdsa_encsig);
 
@@ -100,7 +101,7 @@ fn recover_from_schnorr_to_decrypt_ecdsa() -> anyhow::Result<()> {
 
     let x = KeyPair::new_random();
 
-    let ecdsa_encsig = ecdsa::encsign(&x, &y.public_key, message);
+    let ecdsa_encsig = ecdsa::encsign(&x, &y.public_key, message, &Transcript::new(b"test"));
 
     let R_hat = PublicKey::from_combination(&*SECP, vec![&r0.public_key, &r1.public_key]).unwrap();
     let schnorr_sig = schnorr::decsig(&y, &schnorr_encsig, &R_hat)?;